@@ -3,10 +3,12 @@ pub struct Program {
     pub imports: Vec<Import>,
     pub exports: Vec<Export>,
     pub extern_functions: Vec<ExternFunction>,
+    pub extern_structs: Vec<ExternStruct>,
     pub functions: Vec<Function>,
     pub structs: Vec<Struct>,
     pub enums: Vec<Enum>,
     pub global_variables: Vec<GlobalVariable>,
+    pub impls: Vec<Impl>,
 }
 
 #[derive(Debug, Clone)]
@@ -14,6 +16,7 @@ pub struct GlobalVariable {
     pub name: String,
     pub var_type: Option<Type>,
     pub mutable: bool,
+    pub constant: bool, // `const NAME: T = value;`, emitted as a C `static const`
     pub initializer: Option<Expression>,
 }
 
@@ -22,28 +25,118 @@ pub struct Function {
     pub name: String,
     pub parameters: Vec<Parameter>,
     pub return_type: Option<Type>,
+    pub return_type_inferred: bool, // `return_type` was inferred from the body, not written
     pub body: Vec<Statement>,
+    pub attributes: Vec<Attribute>,
+    pub is_const: bool, // `const fn`: callable at compile time with constant arguments
+    pub const_params: Vec<String>, // `<const N: int>`: array lengths fixed by each call's arguments
+    pub line: usize,    // Position of the name, for diagnostics
+    pub column: usize,
+}
+
+// Attribute attached to a declaration, e.g. `@inline`, `@deprecated("use bar instead")` or `@repr(C)`
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub name: String,
+    pub argument: Option<String>,
+}
+
+impl Function {
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attributes.iter().any(|a| a.name == name)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    // Methods take `self` as their first parameter; associated functions don't
+    pub fn is_method(&self) -> bool {
+        self.parameters.first().map(|p| p.name == "self").unwrap_or(false)
+    }
+
+    // Values of the const parameters for a call whose array arguments have the given fixed
+    // lengths (`None` when not known at compile time), in `const_params` order
+    pub fn const_param_values(&self, argument_lengths: &[Option<usize>]) -> Result<Vec<usize>, String> {
+        let mut values: Vec<Option<usize>> = vec![None; self.const_params.len()];
+        for (param, length) in self.parameters.iter().zip(argument_lengths) {
+            let Type::Array(_, Some(ArrayLength::Param(name))) = &param.param_type else { continue };
+            let Some(index) = self.const_params.iter().position(|p| p == name) else { continue };
+            let Some(length) = *length else {
+                return Err(format!("cannot infer `{}`: the argument for `{}` is not a fixed-size array", name, param.name));
+            };
+            match values[index] {
+                Some(value) if value != length => {
+                    return Err(format!("`{}` is both {} and {} in this call", name, value, length));
+                }
+                _ => values[index] = Some(length),
+            }
+        }
+        self.const_params.iter().zip(values)
+            .map(|(name, value)| value.ok_or_else(|| format!("cannot infer `{}` from the arguments", name)))
+            .collect()
+    }
+
+    // The copy of a function with const parameters specialized on `values` is `name_3`
+    pub fn specialized_name(&self, values: &[usize]) -> String {
+        let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        format!("{}_{}", self.name, values.join("_"))
+    }
+}
+
+// `impl Type { ... }` block of methods and associated functions
+#[derive(Debug, Clone)]
+pub struct Impl {
+    pub type_name: String,
+    pub functions: Vec<Function>,
+}
+
+impl Impl {
+    // Impl functions are emitted as free functions named `Type_function`
+    pub fn mangled_name(type_name: &str, func_name: &str) -> String {
+        format!("{}_{}", type_name, func_name)
+    }
+
+    // Copies of the functions renamed to their mangled names
+    pub fn lowered_functions(&self) -> Vec<Function> {
+        self.functions.iter().map(|f| Function {
+            name: Impl::mangled_name(&self.type_name, &f.name),
+            ..f.clone()
+        }).collect()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Parameter {
     pub name: String,
     pub param_type: Type,
+    pub by_ref: bool,  // `ref n: int`: passed by pointer, read-only in the callee
+    pub mutable: bool, // `mut ref n: int`: the callee may assign through it
+}
+
+impl Parameter {
+    pub fn new(name: String, param_type: Type) -> Self {
+        Parameter { name, param_type, by_ref: false, mutable: false }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Type {
     Int,
+    Long, // 64-bit integer; `int` operands widen to it
     Float,
     Bool,
     Char,
     String,
-    Array(Box<Type>),
+    Array(Box<Type>, Option<ArrayLength>), // `[T]`, or `[T; len]` with a fixed length
     DynamicArray(Box<Type>),
     Pointer(Box<Type>),
     Struct(String),
     Enum(String),  // Enum type by name
+    Function(Vec<Type>, Box<Type>), // Function pointer: parameter types and return type
+    Tuple(Vec<Type>), // `(int, string)`, at least two elements
     Void,
+    Nil, // type of `nil` before context gives it a pointer or string type
     // Generic type with type parameters (e.g., Option<int>, Result<int, string>)
     Generic {
         name: String,
@@ -53,16 +146,48 @@ pub enum Type {
     TypeParam(String),
 }
 
+impl Type {
+    // Integer types ordered by width; arithmetic on two of them yields the wider one
+    pub fn integer_rank(&self) -> Option<u8> {
+        match self {
+            Type::Int => Some(1),
+            Type::Long => Some(2),
+            _ => None,
+        }
+    }
+}
+
+// Length of a fixed-size array type `[T; len]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArrayLength {
+    Literal(usize),
+    // A `const fn` call with literal arguments, evaluated at compile time
+    ConstCall { function: String, arguments: Vec<i64> },
+    // `N` of the enclosing function's `<const N: int>`
+    Param(String),
+}
+
 #[derive(Debug, Clone)]
 pub struct Struct {
     pub name: String,
     pub fields: Vec<Field>,
+    pub attributes: Vec<Attribute>, // `@repr(C)`, `@packed`
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Struct {
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attributes.iter().any(|a| a.name == name)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Enum {
     pub name: String,
     pub variants: Vec<EnumVariant>,
+    pub line: usize,
+    pub column: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -71,18 +196,26 @@ pub struct EnumVariant {
     pub value: Option<i64>,  // Explicit value if specified
 }
 
+// Opaque C type declared with `extern struct NAME;`, usable only behind a pointer
+#[derive(Debug, Clone)]
+pub struct ExternStruct {
+    pub name: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct ExternFunction {
     pub name: String,
     pub parameters: Vec<Parameter>,
     pub return_type: Option<Type>,
     pub variadic: bool,
+    pub header: Option<String>, // `extern "stdio.h" fn ...` includes the header instead of declaring
 }
 
 #[derive(Debug, Clone)]
 pub struct Field {
     pub name: String,
     pub field_type: Type,
+    pub default: Option<Expression>, // `name: T = value`, used when a literal omits the field
 }
 
 #[derive(Debug, Clone)]
@@ -111,6 +244,12 @@ pub enum Statement {
         mutable: bool,
         initializer: Option<Expression>,
     },
+    // `let (q, r) = divmod(a, b);`; a `_` name skips its element
+    LetTuple {
+        names: Vec<String>,
+        mutable: bool,
+        initializer: Expression,
+    },
     Const {
         name: String,
         var_type: Option<Type>,
@@ -135,7 +274,7 @@ pub enum Statement {
         iterable: Expression,
         body: Vec<Statement>,
     },
-    Break,
+    Break(Option<Expression>),  // `break value;` only inside a `loop` used as a value
     Continue,
     Expression(Expression),
 }
@@ -178,6 +317,7 @@ pub enum Expression {
     StructLiteral {
         name: String,
         fields: Vec<(String, Expression)>,
+        base: Option<Box<Expression>>, // `..base` spread for remaining fields
     },
     Range {
         start: Box<Expression>,
@@ -189,6 +329,10 @@ pub enum Expression {
         expression: Box<Expression>,
         target_type: Type,
     },
+    AssertType {
+        expression: Box<Expression>,
+        expected: Type, // assert_type(expr, T): checked at compile time, only `expr` is emitted
+    },
     Ternary {
         condition: Box<Expression>,
         true_expr: Box<Expression>,
@@ -202,9 +346,19 @@ pub enum Expression {
         scrutinee: Box<Expression>,
         arms: Vec<MatchArm>,
     },
+    Loop {
+        body: Vec<Statement>,  // As a value, the loop yields what its `break`s carry
+    },
+    Tuple(Vec<Expression>), // `(a, b)`, at least two elements
     TryOperator {
         expression: Box<Expression>,
     },
+    // `object?.field`: the field of an Option's value or a pointer's target, as an Option that
+    // is None when the object is None or null
+    OptionalField {
+        object: Box<Expression>,
+        field: String,
+    },
     InterpolatedString {
         parts: Vec<StringPart>,  // Alternating text and expressions
     },
@@ -219,18 +373,113 @@ pub enum StringPart {
 #[derive(Debug, Clone)]
 pub struct MatchArm {
     pub pattern: Pattern,
-    pub expression: Expression,
+    pub body: ArmBody,
+}
+
+#[derive(Debug, Clone)]
+pub enum ArmBody {
+    Expression(Expression),  // `pat => expr`
+    Block(Vec<Statement>),   // `pat => { stmts }`, only when the match is a statement
 }
 
 #[derive(Debug, Clone)]
 pub enum Pattern {
     Wildcard,                              // _
     Literal(Literal),                      // 42, 'a', "str"
+    Range { start: Literal, end: Literal, inclusive: bool }, // 1..10 stops before 10, like a for range; 1..=10 and 'a'..='z' include their end
     EnumVariant { 
         enum_name: String, 
         variant: String,
         binding: Option<String>,           // Option::Some(x) - the 'x' part
+        nested: Option<Box<Pattern>>,      // Option::Some(Result::Ok(x)) - a pattern in place of the binding
     }, // TokenKind::EOF or Option::Some(value)
+    Binding {
+        name: String,
+        pattern: Box<Pattern>,
+    }, // v @ Option::Some(_) - binds the whole scrutinee
+    Or(Vec<Pattern>),                      // Color::Red | Color::Blue - binds nothing
+    Tuple(Vec<Pattern>),                   // (0, _) - one pattern per tuple element
+}
+
+impl Pattern {
+    /// The pattern with any outer `name @` bindings stripped
+    pub fn without_binding(&self) -> &Pattern {
+        match self {
+            Pattern::Binding { pattern, .. } => pattern.without_binding(),
+            _ => self,
+        }
+    }
+
+    /// The alternatives of an or-pattern, or else just the pattern itself
+    pub fn alternatives(&self) -> Vec<&Pattern> {
+        match self.without_binding() {
+            Pattern::Or(alternatives) => alternatives.iter().collect(),
+            pattern => vec![pattern],
+        }
+    }
+
+    /// The values an int or char literal or range pattern matches, as inclusive `(low, high)`
+    /// bounds; `None` for other patterns and for ranges that match nothing
+    pub fn matched_values(&self) -> Option<(i64, i64)> {
+        let (low, high) = match self.without_binding() {
+            Pattern::Literal(Literal::Integer(n)) => (*n, *n),
+            Pattern::Literal(Literal::Char(c)) => (*c as i64, *c as i64),
+            Pattern::Range { start, end, inclusive } => {
+                let (low, end) = (start.ordinal()?, end.ordinal()?);
+                (low, if *inclusive { end } else { end.checked_sub(1)? })
+            }
+            _ => return None,
+        };
+        (low <= high).then_some((low, high))
+    }
+
+    /// The parts of `matched_values` that the bounds of earlier patterns leave out, in order;
+    /// empty when the earlier patterns match every value this one does
+    pub fn unmatched_values(&self, earlier: &[(i64, i64)]) -> Option<Vec<(i64, i64)>> {
+        let (low, high) = self.matched_values()?;
+        let mut earlier = earlier.to_vec();
+        earlier.sort_unstable();
+        let mut left = Vec::new();
+        let mut next = Some(low);
+        for (l, h) in earlier {
+            let Some(start) = next.filter(|&start| start <= high) else { break };
+            if h < start || l > high {
+                continue;
+            }
+            if l > start {
+                left.push((start, l - 1));
+            }
+            next = h.checked_add(1);
+        }
+        if let Some(start) = next.filter(|&start| start <= high) {
+            left.push((start, high));
+        }
+        Some(left)
+    }
+
+    /// Whether the pattern binds any variable, including inside nested variant patterns
+    pub fn binds_variables(&self) -> bool {
+        match self {
+            Pattern::Binding { .. } => true,
+            Pattern::EnumVariant { binding: Some(name), .. } => name != "_",
+            Pattern::EnumVariant { nested: Some(nested), .. } => nested.binds_variables(),
+            Pattern::Or(alternatives) => alternatives.iter().any(Pattern::binds_variables),
+            Pattern::Tuple(elements) => elements.iter().any(Pattern::binds_variables),
+            _ => false,
+        }
+    }
+
+    /// Names bound to the whole scrutinee by `name @ pattern`
+    pub fn outer_bindings(&self) -> Vec<&str> {
+        match self {
+            Pattern::Binding { name, pattern } => {
+                let mut names = vec![name.as_str()];
+                names.extend(pattern.outer_bindings());
+                names
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -240,6 +489,18 @@ pub enum Literal {
     Bool(bool),
     Char(char),
     String(String),
+    Nil, // null pointer or string; its type comes from where it is used
+}
+
+impl Literal {
+    /// The value of an int or char literal as an integer, for comparing and ordering patterns
+    pub fn ordinal(&self) -> Option<i64> {
+        match self {
+            Literal::Integer(n) => Some(*n),
+            Literal::Char(c) => Some(*c as i64),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -259,6 +520,18 @@ pub enum BinaryOp {
     Or,
 }
 
+impl BinaryOp {
+    /// The impl method a struct defines to overload the operator: `add` for `+`; `!=` negates `eq`
+    pub fn method_name(&self) -> Option<&'static str> {
+        match self {
+            BinaryOp::Add => Some("add"),
+            BinaryOp::Subtract => Some("sub"),
+            BinaryOp::Equal | BinaryOp::NotEqual => Some("eq"),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum UnaryOp {
     Negate,
@@ -2,7 +2,8 @@ use crate::ast::*;
 use crate::error::{CompilerError, ErrorKind, SourceLocation, Suggestion};
 use crate::modules::ModuleResolver;
 use crate::builtins::BuiltinRegistry;
-use std::path::PathBuf;
+use crate::consteval::ConstEvaluator;
+use std::path::{Path, PathBuf};
 use std::collections::{HashMap, HashSet};
 
 pub struct CCodeGenerator {
@@ -12,14 +13,56 @@ pub struct CCodeGenerator {
     var_types: Vec<HashMap<String, Type>>,
     // Known function return types (unqualified names)
     func_types: HashMap<String, Type>,
+    // Function pointer type of each function, for functions used as values
+    func_pointer_types: HashMap<String, Type>,
     // Current function's return type (for type inference in return statements)
     current_return_type: Option<Type>,
     // Counter for generating unique temporary variables
     temp_counter: usize,
     // Track generic type instantiations that need monomorphization
     generic_instantiations: HashSet<Type>,
+    // Tuple types in first-use order; their typedefs are inserted once all code is generated
+    tuple_types: Vec<Type>,
+    // Dynamic array, Option and Result types compared with `==`/`!=`, each after the types its
+    // comparison goes through; their helpers are inserted with the prototypes
+    eq_types: Vec<Type>,
     // Built-in types registry
     builtins: BuiltinRegistry,
+    // Map of type name -> functions from its impl blocks (unmangled names)
+    impl_functions: HashMap<String, HashMap<String, Function>>,
+    // Flag of the innermost statement match whose switch a loop `break` must escape
+    match_break_flag: Option<String>,
+    // Variable receiving the `break` values of the innermost `loop` used as a value
+    loop_result: Option<String>,
+    // Map of struct name -> fields with a default value, in declaration order
+    struct_defaults: HashMap<String, Vec<(String, Expression)>>,
+    // Map of struct name -> the type of each field, in declaration order
+    struct_fields: HashMap<String, Vec<(String, Type)>>,
+    // `const fn`s by name, for evaluating array lengths
+    const_functions: HashMap<String, Function>,
+    // Functions with `<const N: int>` parameters, emitted once per combination of values used
+    const_generic_functions: HashMap<String, Function>,
+    // Locals of a type with a `drop` method as (scope depth, name, C drop function, flag set when
+    // a nested block moves the local out), innermost last
+    drop_locals: Vec<(usize, String, String, Option<String>)>,
+    // Locals of the function being generated that a nested block may move out
+    conditional_moves: HashSet<String>,
+    // Scope depth of the innermost loop body; `break` and `continue` drop the locals from there on
+    loop_depth: usize,
+    // Which parameters of each function are taken by `ref`, for functions with any
+    ref_functions: HashMap<String, Vec<bool>>,
+    // `ref` parameters of the function being generated, and the scope they are declared in;
+    // they are pointers in C, dereferenced wherever they are used
+    ref_params: HashSet<String>,
+    ref_param_scope: usize,
+    // Specializations requested by calls, in first-use order
+    specializations: Vec<(String, Vec<usize>)>,
+    // Values of the const parameters of the specialization being generated
+    const_args: HashMap<String, usize>,
+    // C names of imported modules' `init` functions, in import order
+    module_inits: Vec<String>,
+    // Build a runner calling the `@test` functions instead of wrapping `main`
+    test_runner: bool,
 }
 
 impl CCodeGenerator {
@@ -29,13 +72,39 @@ impl CCodeGenerator {
             indent_level: 0,
             var_types: Vec::new(),
             func_types: HashMap::new(),
+            func_pointer_types: HashMap::new(),
             current_return_type: None,
             temp_counter: 0,
             generic_instantiations: HashSet::new(),
+            tuple_types: Vec::new(),
+            eq_types: Vec::new(),
             builtins: BuiltinRegistry::new(),
+            impl_functions: HashMap::new(),
+            match_break_flag: None,
+            loop_result: None,
+            struct_defaults: HashMap::new(),
+            struct_fields: HashMap::new(),
+            const_functions: HashMap::new(),
+            const_generic_functions: HashMap::new(),
+            drop_locals: Vec::new(),
+            conditional_moves: HashSet::new(),
+            loop_depth: 0,
+            ref_functions: HashMap::new(),
+            ref_params: HashSet::new(),
+            ref_param_scope: 0,
+            specializations: Vec::new(),
+            const_args: HashMap::new(),
+            module_inits: Vec::new(),
+            test_runner: false,
         }
     }
     
+    /// Emit `@test` functions and a `main` that runs them (`--test`)
+    pub fn with_test_runner(mut self) -> Self {
+        self.test_runner = true;
+        self
+    }
+    
     // Track a generic type instantiation for later generation
     fn track_generic_type(&mut self, ty: &Type) {
         if let Type::Generic { .. } = ty {
@@ -44,16 +113,48 @@ impl CCodeGenerator {
         // Also track nested generic types
         match ty {
             Type::Pointer(inner) => self.track_generic_type(inner),
-            Type::Array(inner) => self.track_generic_type(inner),
+            Type::Array(inner, _) => self.track_generic_type(inner),
             Type::DynamicArray(inner) => self.track_generic_type(inner),
+            // Function pointer types need a typedef, emitted after the types they mention
+            Type::Function(params, ret) => {
+                for param in params {
+                    self.track_generic_type(param);
+                }
+                self.track_generic_type(ret);
+                self.generic_instantiations.insert(ty.clone());
+            }
+            Type::Tuple(elements) => {
+                for element in elements {
+                    self.track_generic_type(element);
+                }
+                if !self.tuple_types.contains(ty) {
+                    self.tuple_types.push(ty.clone());
+                }
+            }
             _ => {}
         }
     }
     
     // Collect all generic types used in an AST
     fn collect_generic_types(&mut self, ast: &Program) {
+        // Field types are needed for the results of `?.`
+        for st in &ast.structs {
+            self.struct_fields.insert(st.name.clone(), st.fields.iter().map(|f| (f.name.clone(), f.field_type.clone())).collect());
+        }
+        // Any function may be passed by name, so know their pointer types before scanning bodies
+        let signatures = ast.functions.iter()
+            .map(|f| (&f.name, &f.parameters, &f.return_type))
+            .chain(ast.extern_functions.iter().map(|f| (&f.name, &f.parameters, &f.return_type)));
+        for (name, parameters, return_type) in signatures {
+            let ty = Type::Function(
+                parameters.iter().map(|p| p.param_type.clone()).collect(),
+                Box::new(return_type.clone().unwrap_or(Type::Void)),
+            );
+            self.func_pointer_types.insert(name.clone(), ty);
+        }
+        
         // Collect from function signatures
-        for func in &ast.functions {
+        for func in ast.functions.iter().chain(ast.impls.iter().flat_map(|i| &i.functions)) {
             if let Some(ret_ty) = &func.return_type {
                 self.track_generic_type(ret_ty);
             }
@@ -76,10 +177,23 @@ impl CCodeGenerator {
     
     fn collect_generic_types_from_stmt(&mut self, stmt: &Statement) {
         match stmt {
-            Statement::Let { var_type, .. } | Statement::Const { var_type, .. } => {
+            Statement::Let { var_type, initializer, .. } => {
+                if let Some(ty) = var_type {
+                    self.track_generic_type(ty);
+                }
+                if let Some(expr) = initializer {
+                    self.collect_generic_types_from_expr(expr);
+                }
+            }
+            Statement::Const { var_type, initializer, .. } => {
                 if let Some(ty) = var_type {
                     self.track_generic_type(ty);
                 }
+                self.collect_generic_types_from_expr(initializer);
+            }
+            Statement::LetTuple { initializer, .. } => self.collect_generic_types_from_expr(initializer),
+            Statement::Assignment { value: expr, .. } | Statement::Return(Some(expr)) | Statement::Break(Some(expr)) => {
+                self.collect_generic_types_from_expr(expr);
             }
             Statement::If { then_branch, else_branch, .. } => {
                 for s in then_branch {
@@ -101,23 +215,147 @@ impl CCodeGenerator {
                     self.collect_generic_types_from_stmt(s);
                 }
             }
+            Statement::Expression(Expression::Match { scrutinee, arms }) => {
+                self.collect_generic_types_from_expr(scrutinee);
+                for arm in arms {
+                    if let ArmBody::Block(body) = &arm.body {
+                        for s in body {
+                            self.collect_generic_types_from_stmt(s);
+                        }
+                    }
+                }
+            }
+            Statement::Expression(expr) => self.collect_generic_types_from_expr(expr),
+            _ => {}
+        }
+    }
+    
+    // Builtins such as env() return generic types that appear in no annotation
+    fn collect_generic_types_from_expr(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Variable(name) => {
+                if let Some(ty) = self.func_pointer_types.get(name).cloned() {
+                    self.track_generic_type(&ty);
+                }
+            }
+            Expression::Call { callee, arguments } => {
+                if let Expression::Variable(name) = &**callee {
+                    if let Some(ty) = builtin_function_type(name) {
+                        self.track_generic_type(&ty);
+                    }
+                }
+                for arg in arguments {
+                    self.collect_generic_types_from_expr(arg);
+                }
+            }
+            Expression::Binary { left, right, .. } => {
+                self.collect_generic_types_from_expr(left);
+                self.collect_generic_types_from_expr(right);
+            }
+            Expression::Unary { operand: inner, .. }
+            | Expression::Cast { expression: inner, .. }
+            | Expression::AssertType { expression: inner, .. }
+            | Expression::TryOperator { expression: inner } => self.collect_generic_types_from_expr(inner),
+            Expression::OptionalField { object, field } => {
+                // The object's struct is unknown here, so declare `Option` of every field by that name
+                let field_types: Vec<Type> = self.struct_fields.keys().filter_map(|name| self.field_type(name, field)).collect();
+                for ty in field_types {
+                    self.track_generic_type(&Type::Generic { name: "Option".to_string(), type_params: vec![ty] });
+                }
+                self.collect_generic_types_from_expr(object);
+            }
+            Expression::Match { scrutinee, arms } => {
+                self.collect_generic_types_from_expr(scrutinee);
+                for arm in arms {
+                    if let ArmBody::Expression(expr) = &arm.body {
+                        self.collect_generic_types_from_expr(expr);
+                    }
+                }
+            }
+            Expression::Loop { body } => {
+                for s in body {
+                    self.collect_generic_types_from_stmt(s);
+                }
+            }
+            Expression::Tuple(elements) => {
+                for element in elements {
+                    self.collect_generic_types_from_expr(element);
+                }
+            }
             _ => {}
         }
     }
     
     // Generate C definitions for all tracked generic types
     fn generate_generic_type_defs(&mut self) -> Result<(), CompilerError> {
-        let instantiations: Vec<Type> = self.generic_instantiations.iter().cloned().collect();
+        let mut instantiations: Vec<Type> = self.generic_instantiations.iter().cloned().collect();
+        // Function pointer typedefs come first, and a type's name is longer than the names
+        // of the types nested in it (`Option_Result_int_string` after `Result_int_string`)
+        instantiations.sort_by_key(|ty| match ty {
+            Type::Function(..) => (0, self.type_to_c(ty).len()),
+            _ => (1, self.type_to_c(ty).len()),
+        });
+        // Distinct types can share a C name (`Option<str>` and `Option<string>`), so dedupe by name
+        let mut emitted: HashSet<String> = HashSet::new();
         for generic_ty in instantiations {
-            if let Type::Generic { name, ref type_params } = generic_ty {
-                if let Some(builtin) = self.builtins.get_generic(&name).cloned() {
-                    self.generate_builtin_generic_def(&builtin, type_params)?;
+            if !emitted.insert(self.type_to_c(&generic_ty)) {
+                continue;
+            }
+            match generic_ty {
+                Type::Generic { name, ref type_params } => {
+                    if let Some(builtin) = self.builtins.get_generic(&name).cloned() {
+                        self.generate_builtin_generic_def(&builtin, type_params)?;
+                    }
+                }
+                Type::Function(ref params, ref ret) => {
+                    let params: Vec<String> = params.iter().map(|t| self.type_to_c(t)).collect();
+                    let params = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+                    self.output.push_str(&format!(
+                        "typedef {} (*{})({});\n\n",
+                        self.type_to_c(ret),
+                        self.type_to_c(&generic_ty),
+                        params
+                    ));
                 }
+                _ => {}
             }
         }
         Ok(())
     }
     
+    // `typedef struct { int _0; char* _1; } Tuple_int_string;` for each tracked tuple type;
+    // a tuple's name is longer than the names of the tuples nested in it
+    fn tuple_typedefs(&self) -> String {
+        let mut tuples: Vec<&Type> = self.tuple_types.iter().collect();
+        tuples.sort_by_key(|ty| self.type_to_c(ty).len());
+        let mut typedefs = String::new();
+        for ty in tuples {
+            let Type::Tuple(elements) = ty else { continue };
+            let fields: Vec<String> = elements.iter().enumerate()
+                .map(|(i, element)| format!("{} _{};", self.type_to_c(element), i))
+                .collect();
+            typedefs.push_str(&format!("typedef struct {{ {} }} {};\n", fields.join(" "), self.type_to_c(ty)));
+        }
+        if !typedefs.is_empty() {
+            typedefs.push('\n');
+        }
+        typedefs
+    }
+    
+    // Type of a tuple expression; elements whose type is unknown take it from the function's
+    // tuple return type, or are ints
+    fn tuple_type(&self, elements: &[Expression]) -> Type {
+        let return_elements = match &self.current_return_type {
+            Some(Type::Tuple(types)) if types.len() == elements.len() => types.as_slice(),
+            _ => &[],
+        };
+        Type::Tuple(elements.iter().enumerate()
+            .map(|(i, element)| self.expr_type(element)
+                .or_else(|| return_elements.get(i).cloned())
+                .unwrap_or(Type::Int))
+            .collect())
+    }
+    
     // Generate C code for a built-in generic type (Option, Result)
     fn generate_builtin_generic_def(
         &mut self,
@@ -206,6 +444,119 @@ impl CCodeGenerator {
         Ok(())
     }
     
+    // Record that values of `ty` are compared with `==`, along with every type the comparison
+    // goes through, so each helper comes after the helpers it calls
+    fn require_eq(&mut self, ty: &Type) {
+        match ty {
+            Type::DynamicArray(elem) => self.require_eq(elem),
+            Type::Tuple(elements) => {
+                for element in elements {
+                    self.require_eq(element);
+                }
+                return;
+            }
+            Type::Struct(name) => {
+                for (_, field_ty) in self.struct_fields.get(name).cloned().unwrap_or_default() {
+                    self.require_eq(&field_ty);
+                }
+                return;
+            }
+            Type::Generic { name, type_params } => {
+                let Some(builtin) = self.builtins.get_generic(name) else { return };
+                let payloads: Vec<Type> = builtin.variants.iter()
+                    .filter_map(|variant| builtin.variant_value_type(&variant.name, type_params))
+                    .collect();
+                for payload in &payloads {
+                    self.require_eq(payload);
+                }
+            }
+            _ => return,
+        }
+        // Distinct types can share a C name (`Option<str>` and `Option<string>`)
+        if !self.eq_types.iter().any(|known| self.type_to_c(known) == self.type_to_c(ty)) {
+            self.eq_types.push(ty.clone());
+        }
+    }
+    
+    // `static inline int DynamicArray_int_eq(DynamicArray_int a, DynamicArray_int b)` for each
+    // type compared with `==`: an array compares its size, then element by element; an Option
+    // or Result its tag, then the payload
+    fn eq_helpers(&self) -> String {
+        let mut helpers = String::new();
+        for ty in &self.eq_types {
+            match ty {
+                Type::DynamicArray(elem) => helpers.push_str(&format!(
+                    "static inline int {0}_eq({1} a, {1} b) {{ if (a.size != b.size) return 0; for (size_t i = 0; i < a.size; i++) if (!({2})) return 0; return 1; }}\n",
+                    self.type_to_mangled_name(ty),
+                    self.type_to_c(ty),
+                    self.c_equality(elem, "a.data[i]", "b.data[i]")
+                )),
+                Type::Generic { name, type_params } => {
+                    let Some(builtin) = self.builtins.get_generic(name) else { continue };
+                    let mangled_name = self.type_to_c(ty);
+                    helpers.push_str(&format!(
+                        "static inline int {0}_eq({0} a, {0} b) {{ if (a.tag != b.tag) return 0; switch (a.tag) {{ ",
+                        mangled_name
+                    ));
+                    for variant in &builtin.variants {
+                        if let Some(concrete_ty) = builtin.variant_value_type(&variant.name, type_params) {
+                            let field = format!("{}_value", variant.name.to_lowercase());
+                            helpers.push_str(&format!(
+                                "case {}_{}: return {}; ",
+                                mangled_name,
+                                variant.name,
+                                self.c_equality(&concrete_ty, &format!("a.data.{}", field), &format!("b.data.{}", field))
+                            ));
+                        }
+                    }
+                    helpers.push_str("default: return 1; } }\n");
+                }
+                _ => {}
+            }
+        }
+        if !helpers.is_empty() {
+            helpers.push('\n');
+        }
+        helpers
+    }
+    
+    // C expression testing two values of `ty` for equality; the helpers it calls must have been
+    // recorded with `require_eq`
+    fn c_equality(&self, ty: &Type, a: &str, b: &str) -> String {
+        match ty {
+            Type::String => format!("strcmp({}, {}) == 0", a, b),
+            Type::Struct(name) if name == "str" => format!("strcmp({}, {}) == 0", a, b),
+            Type::Generic { .. } => format!("{}_eq({}, {})", self.type_to_c(ty), a, b),
+            Type::DynamicArray(_) => format!("{}_eq({}, {})", self.type_to_mangled_name(ty), a, b),
+            Type::Struct(name) => {
+                // Field by field: padding bytes and string contents rule out memcmp
+                let fields = self.struct_fields.get(name).map(Vec::as_slice).unwrap_or_default();
+                Self::conjunction(fields.iter().map(|(field, field_ty)| {
+                    self.c_equality(field_ty, &format!("{}.{}", a, field), &format!("{}.{}", b, field))
+                }))
+            }
+            Type::Tuple(elements) => Self::conjunction(elements.iter().enumerate().map(|(i, element)| {
+                self.c_equality(element, &format!("{}._{}", a, i), &format!("{}._{}", b, i))
+            })),
+            _ => format!("{} == {}", a, b),
+        }
+    }
+    
+    // `(a) && (b) && ...`, or `1` when there is nothing to compare
+    fn conjunction(conditions: impl Iterator<Item = String>) -> String {
+        let conditions: Vec<String> = conditions.map(|condition| format!("({})", condition)).collect();
+        if conditions.is_empty() {
+            "1".to_string()
+        } else {
+            conditions.join(" && ")
+        }
+    }
+    
+    // Type of `field` in struct `struct_name`
+    fn field_type(&self, struct_name: &str, field: &str) -> Option<Type> {
+        self.struct_fields.get(struct_name)?.iter().find(|(name, _)| name == field).map(|(_, ty)| ty.clone())
+    }
+    
     fn indent(&mut self) {
         for _ in 0..self.indent_level {
             self.output.push_str("    ");
@@ -216,10 +567,28 @@ impl CCodeGenerator {
         // First pass: collect all generic type instantiations from main AST
         self.collect_generic_types(ast);
         
+        for imp in &ast.impls {
+            let functions = self.impl_functions.entry(imp.type_name.clone()).or_default();
+            for func in &imp.functions {
+                functions.insert(func.name.clone(), func.clone());
+            }
+        }
+        for func in ast.functions.iter().filter(|f| f.is_const) {
+            self.const_functions.insert(func.name.clone(), func.clone());
+        }
+        for func in ast.functions.iter().filter(|f| !f.const_params.is_empty()) {
+            self.const_generic_functions.insert(func.name.clone(), func.clone());
+        }
+        self.collect_ref_functions(ast);
+        
         // Also collect from imported modules
         for import in &ast.imports {
             let module = resolver.load_module(&import.module)?;
             self.collect_generic_types(&module.program);
+            for func in module.program.functions.iter().filter(|f| !f.const_params.is_empty()) {
+                self.const_generic_functions.insert(func.name.clone(), func.clone());
+            }
+            self.collect_ref_functions(&module.program);
         }
         
         // Add headers
@@ -227,14 +596,33 @@ impl CCodeGenerator {
         self.output.push_str("#include <stdlib.h>\n");
         self.output.push_str("#include <string.h>\n");
     self.output.push_str("#include <stddef.h>\n");
-    self.output.push_str("#include <ctype.h>\n\n");
-        let has_main = ast.functions.iter().any(|f| f.name == "main");
+    self.output.push_str("#include <ctype.h>\n");
+    self.output.push_str("#include <setjmp.h>\n");
+        // Headers requested by extern declarations, in first-use order
+        let mut extern_headers: Vec<&String> = Vec::new();
+        for ext_func in &ast.extern_functions {
+            if let Some(header) = &ext_func.header {
+                if !extern_headers.contains(&header) {
+                    extern_headers.push(header);
+                }
+            }
+        }
+        for header in extern_headers {
+            self.output.push_str(&format!("#include <{}>\n", header));
+        }
+        self.output.push('\n');
+        // The test runner is the entrypoint of a test build
+        let test_runner = self.test_runner;
+        let has_main = test_runner || ast.functions.iter().any(|f| f.name == "main");
         if has_main {
             // Globals and accessors for command-line arguments (define once in entrypoint TU)
             self.output.push_str("static int __rapter_argc = 0;\n");
             self.output.push_str("static char** __rapter_argv = NULL;\n");
             self.output.push_str("int rapter_get_argc() { return __rapter_argc; }\n");
-            self.output.push_str("char* rapter_get_argv(int i) { return (i >= 0 && i < __rapter_argc) ? __rapter_argv[i] : \"\"; }\n\n");
+            self.output.push_str("char* rapter_get_argv(int i) { return (i >= 0 && i < __rapter_argc) ? __rapter_argv[i] : \"\"; }\n");
+            // A failed assert ends the program, or under the test runner only the current test
+            self.output.push_str("static jmp_buf* __rapter_test_jmp = NULL;\n");
+            self.output.push_str("void rapter_assert(int cond, char* message) { if (cond) return; fprintf(stderr, \"assertion failed: %s\\n\", message); if (__rapter_test_jmp) longjmp(*__rapter_test_jmp, 1); exit(1); }\n\n");
         }
         
         // Add typedefs for dynamic arrays
@@ -294,15 +682,16 @@ impl CCodeGenerator {
         
         // Generate definitions for generic type instantiations (Option<int>, Result<T, E>, etc.)
         self.generate_generic_type_defs()?;
+        let tuple_typedefs_at = self.output.len();
         
         // Declare external functions
         for ext_func in &ast.extern_functions {
-            // Skip intrinsic functions - they're already in C stdlib headers
-            if crate::intrinsics::is_intrinsic(&ext_func.name) {
-                continue;
-            }
             // Record return type for externs
             self.func_types.insert(ext_func.name.clone(), ext_func.return_type.clone().unwrap_or(Type::Void));
+            // Skip intrinsic functions and externs with a header - the header declares them
+            if crate::intrinsics::is_intrinsic(&ext_func.name) || ext_func.header.is_some() {
+                continue;
+            }
             self.declare_extern_function(ext_func)?;
             self.output.push_str(";\n");
         }
@@ -315,20 +704,39 @@ impl CCodeGenerator {
             // String helper functions
             self.output.push_str("typedef struct { char** data; size_t size; size_t capacity; } DynamicArray_charptr;\n");
             self.output.push_str("char* rapter_substring(char* str, int start, int end) { if (!str) return NULL; int len = strlen(str); if (start < 0) start = 0; if (end > len) end = len; if (start >= end) return strdup(\"\"); int sublen = end - start; char* result = (char*)malloc(sublen + 1); if (!result) return NULL; strncpy(result, str + start, sublen); result[sublen] = 0; return result; }\n");
+            self.output.push_str("char* rapter_hex(int n) { char* buf = (char*)malloc(2 * sizeof(int) + 1); if (!buf) return NULL; snprintf(buf, 2 * sizeof(int) + 1, \"%x\", (unsigned int)n); return buf; }\n");
+            self.output.push_str("char* rapter_pad(char* str, int width) { size_t columns = (size_t)(width < 0 ? -width : width); size_t len = strlen(str); size_t n = len > columns ? len : columns; char* result = (char*)malloc(n + 1); if (!result) return NULL; size_t start = width < 0 ? n - len : 0; memset(result, ' ', n); memcpy(result + start, str, len); result[n] = 0; return result; }\n");
             self.output.push_str("char* rapter_trim(char* str) { if (!str) return NULL; while (*str && isspace((unsigned char)*str)) str++; if (!*str) return strdup(\"\"); char* end = str + strlen(str) - 1; while (end > str && isspace((unsigned char)*end)) end--; size_t len = end - str + 1; char* result = (char*)malloc(len + 1); if (!result) return NULL; memcpy(result, str, len); result[len] = 0; return result; }\n");
+            self.output.push_str("char* rapter_trim_start(char* str) { if (!str) return NULL; while (*str && isspace((unsigned char)*str)) str++; return strdup(str); }\n");
+            self.output.push_str("char* rapter_trim_end(char* str) { if (!str) return NULL; size_t len = strlen(str); while (len > 0 && isspace((unsigned char)str[len - 1])) len--; char* result = (char*)malloc(len + 1); if (!result) return NULL; memcpy(result, str, len); result[len] = 0; return result; }\n");
+            self.output.push_str("char* rapter_to_upper(char* str) { if (!str) return NULL; char* result = strdup(str); if (!result) return NULL; for (char* c = result; *c; c++) *c = toupper((unsigned char)*c); return result; }\n");
+            self.output.push_str("char* rapter_to_lower(char* str) { if (!str) return NULL; char* result = strdup(str); if (!result) return NULL; for (char* c = result; *c; c++) *c = tolower((unsigned char)*c); return result; }\n");
             self.output.push_str("DynamicArray_charptr rapter_split(char* str, char* delim) { DynamicArray_charptr arr; arr.size = 0; arr.capacity = 4; arr.data = (char**)malloc(arr.capacity * sizeof(char*)); if (!arr.data) return arr; char* copy = strdup(str); char* token = strtok(copy, delim); while (token) { if (arr.size >= arr.capacity) { arr.capacity *= 2; arr.data = (char**)realloc(arr.data, arr.capacity * sizeof(char*)); } arr.data[arr.size++] = strdup(token); token = strtok(NULL, delim); } free(copy); return arr; }\n\n");
+            self.output.push_str("DynamicArray_charptr rapter_read_lines(char* path) { DynamicArray_charptr arr; arr.size = 0; arr.capacity = 4; arr.data = (char**)malloc(arr.capacity * sizeof(char*)); char* text = rapter_read_all(path); if (!arr.data || !text) { free(text); return arr; } char* line = text; while (*line) { char* end = strchr(line, '\\n'); size_t len = end ? (size_t)(end - line) : strlen(line); size_t next = end ? len + 1 : len; if (len > 0 && line[len - 1] == '\\r') len--; if (arr.size >= arr.capacity) { arr.capacity *= 2; arr.data = (char**)realloc(arr.data, arr.capacity * sizeof(char*)); } char* copy = (char*)malloc(len + 1); memcpy(copy, line, len); copy[len] = 0; arr.data[arr.size++] = copy; line += next; } free(text); return arr; }\n\n");
         }
         
         // (structs already defined above)
         
         // Generate function declarations (for forward declarations if needed)
-        for func in &ast.functions {
+        for func in ast.functions.iter().filter(|f| test_runner || !f.has_attribute("test")) {
             // Record local function return types
             self.func_types.insert(func.name.clone(), func.return_type.clone().unwrap_or(Type::Void));
-            self.declare_function(func)?;
+            if !func.const_params.is_empty() {
+                // Declared per specialization, once the calls are known
+                continue;
+            }
+            self.declare_function(func, false)?;
             self.output.push_str(";\n");
         }
         
+        for imp in &ast.impls {
+            for func in imp.lowered_functions() {
+                self.func_types.insert(func.name.clone(), func.return_type.clone().unwrap_or(Type::Void));
+                self.declare_function(&func, false)?;
+                self.output.push_str(";\n");
+            }
+        }
+        
         // Generate declarations for imported functions
         for import in &ast.imports {
             let module = resolver.load_module(&import.module)?;
@@ -338,15 +746,21 @@ impl CCodeGenerator {
                     if let Some(func) = module.program.functions.iter().find(|f| f.name == *name) {
                         // Record imported function return type by unqualified name
                         self.func_types.insert(func.name.clone(), func.return_type.clone().unwrap_or(Type::Void));
-                        self.declare_function(func)?;
+                        if module.init_function().is_some_and(|init| init.name == func.name) || !func.const_params.is_empty() {
+                            // Only the main wrapper calls it, under its per-module name
+                            continue;
+                        }
+                        self.declare_function(func, false)?;
                         self.output.push_str(";\n");
                     }
                 }
             }
         }
+        let specialization_prototypes = self.output.len();
         self.output.push_str("\n");
         
-        // Generate global variable definitions
+        // Generate global variable definitions, in a scope that every function sees
+        self.enter_scope();
         for global_var in &ast.global_variables {
             self.generate_global_variable(global_var)?;
         }
@@ -354,31 +768,150 @@ impl CCodeGenerator {
             self.output.push_str("\n");
         }
         
-        // Generate function definitions
-        for func in &ast.functions {
+        // Imported modules' globals, which their functions (and `init`) use
+        for import in &ast.imports {
+            let module = resolver.load_module(&import.module)?;
+            for global_var in &module.program.global_variables {
+                self.generate_global_variable(global_var)?;
+            }
+            if !module.program.global_variables.is_empty() {
+                self.output.push('\n');
+            }
+        }
+        
+        // Generate function definitions; `@test` functions only exist in test builds
+        for func in ast.functions.iter().filter(|f| (test_runner || !f.has_attribute("test")) && f.const_params.is_empty()) {
             self.generate_function(func)?;
             self.output.push_str("\n");
         }
         
+        for imp in &ast.impls {
+            for func in imp.lowered_functions() {
+                self.generate_function(&func)?;
+                self.output.push('\n');
+            }
+        }
+        
         // Generate imported function definitions (for bootstrap simplicity)
         // TODO: In the future, compile modules separately and link
         for import in &ast.imports {
             let module = resolver.load_module(&import.module)?;
             // Generate ALL functions from the module (exported and internal)
             for func in &module.program.functions {
-                self.generate_function(func)?;
+                if module.init_function().is_some_and(|init| init.name == func.name) {
+                    let init = Function { name: module.init_symbol(), ..func.clone() };
+                    self.generate_function(&init)?;
+                    self.module_inits.push(init.name);
+                } else if !func.const_params.is_empty() {
+                    continue;
+                } else {
+                    self.generate_function(func)?;
+                }
                 self.output.push_str("\n");
             }
         }
         
+        // Specializations of const-generic functions, declared with the other prototypes;
+        // generating one can request further specializations
+        let mut prototypes = String::new();
+        let mut generated = 0;
+        while generated < self.specializations.len() {
+            let (name, values) = self.specializations[generated].clone();
+            generated += 1;
+            let func = self.specialize(&self.const_generic_functions[&name].clone(), &values);
+            let prototype_start = self.output.len();
+            self.declare_function(&func, false)?;
+            prototypes.push_str(&self.output[prototype_start..]);
+            prototypes.push_str(";\n");
+            self.output.truncate(prototype_start);
+            self.const_args = self.const_generic_functions[&name].const_params.iter().cloned()
+                .zip(values.iter().copied())
+                .collect();
+            self.generate_function(&func)?;
+            self.const_args.clear();
+            self.output.push('\n');
+        }
+        let mut declarations = self.eq_helpers();
+        declarations.push_str(&prototypes);
+        self.output.insert_str(specialization_prototypes, &declarations);
+        let typedefs = self.tuple_typedefs();
+        self.output.insert_str(tuple_typedefs_at, &typedefs);
+        
         // Generate main wrapper if there's a main function
-        if has_main {
+        if test_runner {
+            self.generate_test_runner(ast)?;
+        } else if has_main {
             self.generate_main_wrapper()?;
         }
         
         Ok(())
     }
     
+    /// A C header for the program's exported enums, structs and functions, so C code can
+    /// call into it; `guard` is the include guard macro
+    pub fn generate_header(&mut self, ast: &Program, guard: &str) -> Result<(), CompilerError> {
+        let exported: HashSet<&String> = ast.exports.iter()
+            .map(|export| match &export.item {
+                ExportItem::Function(name) | ExportItem::Struct(name) | ExportItem::Enum(name) => name,
+            })
+            .collect();
+        self.output.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+        // `@inline` functions are static in the generated C, so C code cannot link against them
+        // Functions with const parameters only exist as specializations for this program's calls
+        let functions: Vec<&Function> = ast.functions.iter()
+            .filter(|f| exported.contains(&f.name) && !f.has_attribute("inline") && f.const_params.is_empty())
+            .collect();
+        // Besides the exports, the header defines every type their signatures mention
+        let mut used_names: HashSet<String> = HashSet::new();
+        let mut dynamic_arrays: Vec<Type> = Vec::new();
+        for ty in functions.iter().flat_map(|f| f.parameters.iter().map(|p| &p.param_type).chain(&f.return_type)) {
+            header_types(ast, ty, &mut used_names, &mut dynamic_arrays);
+        }
+        let enums: Vec<_> = ast.enums.iter().filter(|e| exported.contains(&e.name) || used_names.contains(&e.name)).collect();
+        let structs: Vec<_> = ast.structs.iter().filter(|s| exported.contains(&s.name) || used_names.contains(&s.name)).collect();
+        for func in &functions {
+            for ty in func.parameters.iter().map(|p| &p.param_type).chain(&func.return_type) {
+                self.track_generic_type(ty);
+            }
+        }
+        for st in &structs {
+            for field in &st.fields {
+                self.track_generic_type(&field.field_type);
+            }
+        }
+        
+        self.output.push_str("#include <stddef.h>\n");
+        self.output.push('\n');
+        for enm in enums {
+            self.generate_enum(enm)?;
+            self.output.push('\n');
+        }
+        for st in structs {
+            self.generate_struct(st)?;
+            self.output.push('\n');
+        }
+        for ty in &dynamic_arrays {
+            let Type::DynamicArray(elem) = ty else { continue };
+            self.output.push_str(&format!(
+                "typedef struct {{ {}* data; size_t size; size_t capacity; }} {};\n",
+                self.type_to_c(elem),
+                self.type_to_c(ty)
+            ));
+        }
+        if !dynamic_arrays.is_empty() {
+            self.output.push('\n');
+        }
+        self.generate_generic_type_defs()?;
+        let typedefs = self.tuple_typedefs();
+        self.output.push_str(&typedefs);
+        for func in functions {
+            self.declare_function(func, true)?;
+            self.output.push_str(";\n");
+        }
+        self.output.push_str(&format!("\n#endif // {}\n", guard));
+        Ok(())
+    }
+    
     fn declare_extern_function(&mut self, func: &ExternFunction) -> Result<(), CompilerError> {
         let return_type = self.type_to_c(&func.return_type.clone().unwrap_or(Type::Void));
         self.output.push_str(&return_type);
@@ -406,7 +939,15 @@ impl CCodeGenerator {
         Ok(())
     }
     
-    fn declare_function(&mut self, func: &Function) -> Result<(), CompilerError> {
+    // Prototypes leave out parameter names: imported functions are inlined into the same
+    // translation unit, and their names could otherwise collide with the importer's
+    fn declare_function(&mut self, func: &Function, with_names: bool) -> Result<(), CompilerError> {
+        if func.has_attribute("inline") {
+            self.output.push_str("static inline ");
+        }
+        if func.has_attribute("noreturn") {
+            self.output.push_str("__attribute__((noreturn)) ");
+        }
         let return_type = self.type_to_c(&func.return_type.clone().unwrap_or(Type::Void));
         self.output.push_str(&return_type);
         self.output.push_str(" ");
@@ -419,15 +960,30 @@ impl CCodeGenerator {
                 self.output.push_str(", ");
             }
             self.output.push_str(&self.type_to_c(&param.param_type));
-            self.output.push_str(" ");
-            self.output.push_str(&param.name);
+            // `ref` parameters are pointers, read-only unless `mut ref`
+            if param.by_ref {
+                self.output.push_str(if param.mutable { "*" } else { " const*" });
+            }
+            if with_names {
+                self.output.push(' ');
+                self.output.push_str(&param.name);
+            }
         }
         self.output.push_str(")");
         Ok(())
     }
     
     fn generate_struct(&mut self, st: &Struct) -> Result<(), CompilerError> {
+        let defaults = st.fields.iter()
+            .filter_map(|f| f.default.as_ref().map(|d| (f.name.clone(), d.clone())))
+            .collect();
+        self.struct_defaults.insert(st.name.clone(), defaults);
+        self.struct_fields.insert(st.name.clone(), st.fields.iter().map(|f| (f.name.clone(), f.field_type.clone())).collect());
         self.output.push_str("typedef struct ");
+        // `@packed`: no padding between fields; `@repr(C)` is the layout C gives anyway
+        if st.has_attribute("packed") {
+            self.output.push_str("__attribute__((packed)) ");
+        }
         self.output.push_str(&st.name);
         self.output.push_str(" {\n");
         self.indent_level += 1;
@@ -493,7 +1049,27 @@ impl CCodeGenerator {
         
         // Generate: static <type> <name> = <initializer>;
         // or: static <type> <name>;
-        self.output.push_str("static ");
+        self.output.push_str(if global_var.constant { "static const " } else { "static " });
+        
+        // An array literal initializes a real C array, `static const int T[4] = {1, 2, 3, 4};`,
+        // rather than a compound literal
+        if let (Type::Array(elem, length), Some(Expression::ArrayLiteral(elements))) = (&ty, &global_var.initializer) {
+            let length = match length {
+                Some(length) => self.array_length(length)?,
+                None => elements.len(),
+            };
+            self.output.push_str(&format!("{} {}[{}] = {{", self.type_to_c(elem), global_var.name, length));
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    self.output.push_str(", ");
+                }
+                self.generate_expression(element)?;
+            }
+            self.output.push_str("};\n");
+            self.set_var_type(&global_var.name, Type::Array(elem.clone(), Some(ArrayLength::Literal(length))));
+            return Ok(());
+        }
+        
         self.output.push_str(&self.type_to_c(&ty));
         self.output.push_str(" ");
         self.output.push_str(&global_var.name);
@@ -515,7 +1091,7 @@ impl CCodeGenerator {
         // Set the current return type for this function
         self.current_return_type = func.return_type.clone();
         
-        self.declare_function(func)?;
+        self.declare_function(func, true)?;
         self.output.push_str(" {\n");
         self.indent_level += 1;
         // Enter a new variable type scope and record parameters
@@ -523,12 +1099,18 @@ impl CCodeGenerator {
         for param in &func.parameters {
             self.set_var_type(&param.name, param.param_type.clone());
         }
+        self.ref_params = func.parameters.iter().filter(|p| p.by_ref).map(|p| p.name.clone()).collect();
+        self.ref_param_scope = self.var_types.len() - 1;
+        self.conditional_moves.clear();
+        conditional_moves(&func.body, &HashSet::new(), &mut self.conditional_moves);
         
         for stmt in &func.body {
             self.generate_statement(stmt)?;
         }
+        self.generate_scope_drops(&func.body);
         
         // Exit function scope
+        self.ref_params.clear();
         self.exit_scope();
         self.current_return_type = None;
         self.indent_level -= 1;
@@ -536,9 +1118,55 @@ impl CCodeGenerator {
         Ok(())
     }
     
+    // Emits `if (...) { ... }` without the trailing newline; an else branch that is a lone `if`
+    // (how the parser represents `else if`) continues the chain as `else if`
+    fn generate_if(&mut self, condition: &Expression, then_branch: &[Statement], else_branch: Option<&[Statement]>) -> Result<(), CompilerError> {
+        self.output.push_str("if (");
+        self.generate_expression(condition)?;
+        self.output.push_str(") {\n");
+        self.indent_level += 1;
+        self.enter_scope();
+        for stmt in then_branch {
+            self.generate_statement(stmt)?;
+        }
+        self.generate_scope_drops(then_branch);
+        self.exit_scope();
+        self.indent_level -= 1;
+        self.indent();
+        self.output.push_str("}");
+        
+        match else_branch {
+            Some([Statement::If { condition, then_branch, else_branch }]) => {
+                self.output.push_str(" else ");
+                self.generate_if(condition, then_branch, else_branch.as_deref())?;
+            }
+            Some(else_stmts) => {
+                self.output.push_str(" else {\n");
+                self.indent_level += 1;
+                self.enter_scope();
+                for stmt in else_stmts {
+                    self.generate_statement(stmt)?;
+                }
+                self.generate_scope_drops(else_stmts);
+                self.exit_scope();
+                self.indent_level -= 1;
+                self.indent();
+                self.output.push_str("}");
+            }
+            None => {}
+        }
+        Ok(())
+    }
+    
     fn generate_statement(&mut self, stmt: &Statement) -> Result<(), CompilerError> {
         self.indent();
         match stmt {
+            Statement::Let { name, var_type: Some(ty @ Type::Array(elem, Some(length))), mutable: _, initializer: None } => {
+                // Fixed-size array: `T name[N];`
+                let length = self.array_length(length)?;
+                self.output.push_str(&format!("{} {}[{}];\n", self.type_to_c(elem), name, length));
+                self.set_var_type(name, ty.clone());
+            }
             Statement::Let { name, var_type, mutable: _, initializer } => {
                 if let Some(ty) = var_type {
                     self.output.push_str(&self.type_to_c(ty));
@@ -566,6 +1194,42 @@ impl CCodeGenerator {
                     self.generate_expression(expr)?;
                 }
                 self.output.push_str(";\n");
+                if let Some(expr) = initializer {
+                    self.move_drop_local(expr);
+                }
+                if let Some(drop_fn) = self.get_var_type(name).and_then(|ty| self.drop_function(&ty)) {
+                    // Whether a nested block moved the local out is only known at run time
+                    let flag = self.conditional_moves.contains(name).then(|| format!("__moved_{}", name));
+                    if let Some(flag) = &flag {
+                        self.indent();
+                        self.output.push_str(&format!("int {} = 0;\n", flag));
+                    }
+                    self.drop_locals.push((self.var_types.len(), name.clone(), drop_fn, flag));
+                }
+            }
+            Statement::LetTuple { names, mutable: _, initializer } => {
+                // The tuple goes to a temporary whose fields initialize the names
+                let Some(Type::Tuple(elements)) = self.expr_type(initializer) else {
+                    return Err(CompilerError::new(
+                        ErrorKind::UnsupportedFeature,
+                        "cannot determine the tuple type to destructure".to_string(),
+                        SourceLocation::new(PathBuf::from("input.rap"), 0, 0),
+                    ).with_suggestion(Suggestion::simple(
+                        "destructure a call to a function declared with a tuple return type"
+                    )));
+                };
+                let tuple_type = Type::Tuple(elements.clone());
+                self.track_generic_type(&tuple_type);
+                let temp_var = format!("__tuple_{}", self.temp_counter);
+                self.temp_counter += 1;
+                self.output.push_str(&format!("{} {} = ", self.type_to_c(&tuple_type), temp_var));
+                self.generate_expression(initializer)?;
+                self.output.push_str(";\n");
+                for (i, (name, ty)) in names.iter().zip(elements).enumerate().filter(|(_, (name, _))| *name != "_") {
+                    self.indent();
+                    self.output.push_str(&format!("{} {} = {}._{};\n", self.type_to_c(&ty), name, temp_var, i));
+                    self.set_var_type(name, ty);
+                }
             }
             Statement::Const { name, var_type, initializer } => {
                 if let Some(ty) = var_type {
@@ -584,12 +1248,43 @@ impl CCodeGenerator {
                 self.output.push_str(";\n");
             }
             Statement::Return(value) => {
-                self.output.push_str("return");
-                if let Some(expr) = value {
-                    self.output.push_str(" ");
-                    self.generate_expression(expr)?;
+                // Every local with a `drop` method goes out of scope, except a returned one
+                let returned = match value {
+                    Some(Expression::Variable(name)) => Some(name.as_str()),
+                    _ => None,
+                };
+                let drops = self.drop_calls(0, returned);
+                match value {
+                    Some(expr) if !drops.is_empty() && returned.is_none() && !matches!(expr, Expression::Literal(_)) => {
+                        // The value may use the dropped locals, so compute it first
+                        let return_type = self.current_return_type.clone().unwrap_or(Type::Int);
+                        let temp_var = format!("__return_{}", self.temp_counter);
+                        self.temp_counter += 1;
+                        self.output.push_str(&format!("{} {} = ", self.type_to_c(&return_type), temp_var));
+                        self.generate_expression(expr)?;
+                        self.output.push_str(";\n");
+                        self.indent();
+                        self.generate_drops(&drops);
+                        self.output.push_str(&format!("return {};\n", temp_var));
+                    }
+                    _ => {
+                        self.generate_drops(&drops);
+                        self.output.push_str("return");
+                        if let Some(expr) = value {
+                            self.output.push_str(" ");
+                            self.generate_expression(expr)?;
+                        }
+                        self.output.push_str(";\n");
+                    }
                 }
-                self.output.push_str(";\n");
+            }
+            Statement::Expression(Expression::Match { scrutinee, arms }) => {
+                self.generate_match(scrutinee, arms, true)?;
+                self.output.push('\n');
+            }
+            Statement::Expression(Expression::Loop { body }) => {
+                self.output.push_str("while (1) ");
+                self.generate_loop_body(body)?;
             }
             Statement::Expression(expr) => {
                 self.generate_expression(expr)?;
@@ -600,52 +1295,47 @@ impl CCodeGenerator {
                 then_branch,
                 else_branch,
             } => {
-                self.output.push_str("if (");
-                self.generate_expression(condition)?;
-                self.output.push_str(") {\n");
-                self.indent_level += 1;
-                self.enter_scope();
-                for stmt in then_branch {
-                    self.generate_statement(stmt)?;
-                }
-                self.exit_scope();
-                self.indent_level -= 1;
-                self.indent();
-                self.output.push_str("}");
-                
-                if let Some(else_stmts) = else_branch {
-                    self.output.push_str(" else {\n");
-                    self.indent_level += 1;
-                    self.enter_scope();
-                    for stmt in else_stmts {
-                        self.generate_statement(stmt)?;
-                    }
-                    self.exit_scope();
-                    self.indent_level -= 1;
-                    self.indent();
-                    self.output.push_str("}");
-                }
+                self.generate_if(condition, then_branch, else_branch.as_deref())?;
                 self.output.push_str("\n");
             }
             Statement::While { condition, body } => {
                 self.output.push_str("while (");
                 self.generate_expression(condition)?;
-                self.output.push_str(") {\n");
-                self.indent_level += 1;
-                self.enter_scope();
-                for stmt in body {
-                    self.generate_statement(stmt)?;
-                }
-                self.exit_scope();
-                self.indent_level -= 1;
-                self.indent();
-                self.output.push_str("}\n");
+                self.output.push_str(") ");
+                self.generate_loop_body(body)?;
             }
             Statement::Assignment { target, value } => {
+                // A local with a `drop` method keeps its old value until the new one is evaluated,
+                // then drops it unless it was moved out
+                let overwritten = match (target, value) {
+                    (Expression::Variable(name), _) if !matches!(value, Expression::Variable(source) if source == name) => {
+                        self.drop_locals.iter().rev().find(|(_, local, ..)| local == name).cloned()
+                    }
+                    _ => None,
+                };
+                let old = format!("__old_{}", self.temp_counter);
+                if let Some((_, name, ..)) = &overwritten {
+                    self.temp_counter += 1;
+                    let c_type = self.get_var_type(name).map(|ty| self.type_to_c(&ty)).unwrap_or_default();
+                    self.output.push_str(&format!("{} {} = {};\n", c_type, old, name));
+                    self.indent();
+                }
                 self.generate_expression(target)?;
                 self.output.push_str(" = ");
                 self.generate_expression(value)?;
                 self.output.push_str(";\n");
+                if let Some((_, _, drop_fn, flag)) = overwritten {
+                    self.indent();
+                    match flag {
+                        Some(flag) => {
+                            self.output.push_str(&format!("if (!{}) {}({});\n", flag, drop_fn, old));
+                            self.indent();
+                            self.output.push_str(&format!("{} = 0;\n", flag));
+                        }
+                        None => self.output.push_str(&format!("{}({});\n", drop_fn, old)),
+                    }
+                }
+                self.move_drop_local(value);
             }
             Statement::For {
                 variable,
@@ -669,9 +1359,14 @@ impl CCodeGenerator {
                     // Scope for for-loop body; track loop variable as int
                     self.enter_scope();
                     self.set_var_type(variable, Type::Int);
+                    let outer_break_flag = self.match_break_flag.take();
+                    let outer_loop_depth = std::mem::replace(&mut self.loop_depth, self.var_types.len());
                     for stmt in body {
                         self.generate_statement(stmt)?;
                     }
+                    self.generate_scope_drops(body);
+                    self.loop_depth = outer_loop_depth;
+                    self.match_break_flag = outer_break_flag;
                     self.exit_scope();
                     self.indent_level -= 1;
                     self.output.push_str("}\n");
@@ -680,10 +1375,24 @@ impl CCodeGenerator {
                     self.output.push_str("// TODO: implement for loop for non-range iterables\n");
                 }
             }
-            Statement::Break => {
-                self.output.push_str("break;\n");
+            Statement::Break(None) => {
+                let drops = self.drop_calls(self.loop_depth, None);
+                self.generate_drops(&drops);
+                self.generate_break();
             }
-            Statement::Continue => {
+            Statement::Break(Some(value)) => {
+                let result_var = self.loop_result.clone().unwrap_or_default();
+                self.output.push_str(&format!("{} = ", result_var));
+                self.generate_expression(value)?;
+                self.output.push_str(";\n");
+                self.indent();
+                let drops = self.drop_calls(self.loop_depth, None);
+                self.generate_drops(&drops);
+                self.generate_break();
+            }
+            Statement::Continue => {
+                let drops = self.drop_calls(self.loop_depth, None);
+                self.generate_drops(&drops);
                 self.output.push_str("continue;\n");
             }
         }
@@ -696,6 +1405,7 @@ impl CCodeGenerator {
                 Literal::Integer(i) => self.output.push_str(&i.to_string()),
                 Literal::Float(f) => self.output.push_str(&f.to_string()),
                 Literal::Bool(b) => self.output.push_str(if *b { "1" } else { "0" }),
+                Literal::Nil => self.output.push_str("NULL"),
                 Literal::Char(c) => {
                     // Escape special chars for valid C char literal
                     let esc: Option<&str> = match *c {
@@ -732,35 +1442,56 @@ impl CCodeGenerator {
                     self.output.push_str("\"");
                 }
             },
-            Expression::Variable(name) => self.output.push_str(name),
+            Expression::Variable(name) => {
+                // In a method, a bare field name that is no variable stands for `self.field`
+                if self.implicit_self_field(name).is_some() {
+                    self.output.push_str("self.");
+                }
+                if self.is_ref_param(name) {
+                    self.output.push_str(&format!("(*{})", name));
+                } else {
+                    self.output.push_str(name);
+                }
+            }
             Expression::Binary { left, operator, right } => {
                 // Special case: string concatenation
-                if *operator == BinaryOp::Add && (self.contains_string_literal(left) || self.contains_string_literal(right)) {
+                if self.is_string_concatenation(left, operator, right) {
                     // If either operand contains a string literal, treat this as string concatenation
                     self.generate_string_concatenation(left, right)?;
-                } else {
-                    self.output.push_str("(");
+                } else if let Some((method, type_name)) = operator.method_name().and_then(|method| Some((method, self.impl_method_owner(left, method)?))) {
+                    // Operator overloading: `a + b` -> Vec2_add(a, b), `a != b` -> !Vec2_eq(a, b)
+                    if *operator == BinaryOp::NotEqual {
+                        self.output.push('!');
+                    }
+                    self.output.push_str(&Impl::mangled_name(&type_name, method));
+                    self.generate_call_arguments(Some(left), std::slice::from_ref(&**right))?;
+                } else if let (BinaryOp::Equal | BinaryOp::NotEqual, Some(generic_ty)) = (operator, self.generic_operand_type(left, right)) {
+                    // Option/Result values are structs in C: compare through the generated helper
+                    self.require_eq(&generic_ty);
+                    if *operator == BinaryOp::NotEqual {
+                        self.output.push('!');
+                    }
+                    self.output.push_str(&format!("{}_eq(", self.type_to_c(&generic_ty)));
                     self.generate_expression(left)?;
-                    self.output.push_str(" ");
-                    let op_str = match operator {
-                        BinaryOp::Add => "+",
-                        BinaryOp::Subtract => "-",
-                        BinaryOp::Multiply => "*",
-                        BinaryOp::Divide => "/",
-                        BinaryOp::Modulo => "%",
-                        BinaryOp::Equal => "==",
-                        BinaryOp::NotEqual => "!=",
-                        BinaryOp::Less => "<",
-                        BinaryOp::LessEqual => "<=",
-                        BinaryOp::Greater => ">",
-                        BinaryOp::GreaterEqual => ">=",
-                        BinaryOp::And => "&&",
-                        BinaryOp::Or => "||",
-                    };
-                    self.output.push_str(op_str);
-                    self.output.push_str(" ");
+                    self.output.push_str(", ");
                     self.generate_expression(right)?;
-                    self.output.push_str(")");
+                    self.output.push(')');
+                } else if let (BinaryOp::Equal | BinaryOp::NotEqual, Some(array_ty)) = (operator, self.dynamic_array_operand_type(left, right)) {
+                    // So are dynamic arrays: compare the sizes and then each element in a helper
+                    self.require_eq(&array_ty);
+                    if *operator == BinaryOp::NotEqual {
+                        self.output.push('!');
+                    }
+                    self.output.push_str(&format!("{}_eq(", self.type_to_mangled_name(&array_ty)));
+                    self.generate_expression(left)?;
+                    self.output.push_str(", ");
+                    self.generate_expression(right)?;
+                    self.output.push(')');
+                } else {
+                    // Parenthesize the whole operation once; nested operands only as precedence requires
+                    self.output.push('(');
+                    self.generate_binary_operands(left, operator, right)?;
+                    self.output.push(')');
                 }
             }
             Expression::Unary { operator, operand } => {
@@ -776,24 +1507,20 @@ impl CCodeGenerator {
             Expression::Call { callee, arguments } => {
                 if let Expression::Variable(name) = &**callee {
                     if name == "print" {
-                        if arguments.len() == 1 && self.is_array_expression(&arguments[0]) {
+                        if arguments.is_empty() {
+                            // print() prints nothing
+                            self.output.push_str("((void)0)");
+                        } else if self.is_array_expression(&arguments[0]) {
                             // Special handling for arrays - print each element
                             self.generate_array_print(&arguments[0], false)?;
                         } else {
                             // print(arg) -> printf("%d", arg) or similar based on type
-                            self.output.push_str("printf(");
-                            if arguments.len() == 1 {
-                                // Try to infer the format based on the argument type
-                                let format_spec = self.infer_printf_format(&arguments[0]);
-                                self.output.push_str("\"");
-                                self.output.push_str(&format_spec);
-                                self.output.push_str("\"");
-                                if arguments.len() > 0 {
-                                    self.output.push_str(", ");
-                                    self.generate_expression(&arguments[0])?;
-                                }
-                            }
-                            self.output.push_str(")");
+                            let format_spec = self.infer_printf_format(&arguments[0]);
+                            self.output.push_str("printf(\"");
+                            self.output.push_str(&format_spec);
+                            self.output.push_str("\", ");
+                            self.generate_printf_argument(&arguments[0], &format_spec)?;
+                            self.output.push(')');
                         }
                     } else if name == "println" {
                         if arguments.len() == 1 && self.is_array_expression(&arguments[0]) {
@@ -809,7 +1536,7 @@ impl CCodeGenerator {
                                 self.output.push_str("\\n\"");
                                 if arguments.len() > 0 {
                                     self.output.push_str(", ");
-                                    self.generate_expression(&arguments[0])?;
+                                    self.generate_printf_argument(&arguments[0], &format_spec)?;
                                 }
                             } else {
                                 // Just print newline
@@ -818,22 +1545,84 @@ impl CCodeGenerator {
                             self.output.push_str(")");
                         }
                     } else if name == "len" {
-                        // len(str) -> strlen(str) - built-in string length function
-                        self.output.push_str("strlen(");
+                        // len(str) -> (int)strlen(str) - built-in string length function; Rapter's int
+                        // result keeps comparisons with int bounds signed
                         if arguments.len() == 1 {
-                            self.generate_expression(&arguments[0])?;
+                            self.generate_string_length(&arguments[0], "(int)")?;
                         } else {
-                            self.output.push_str("\"\""); // Default empty string if no arguments
+                            self.output.push_str("(int)strlen(\"\")"); // Default empty string if no arguments
                         }
-                        self.output.push_str(")");
+                    } else if name == "env" {
+                        // env(name) -> Some(value) when the variable is set, None otherwise
+                        let option_c = self.type_to_c(&builtin_function_type(name).unwrap_or(Type::Void));
+                        let temp_var = format!("__env_{}", self.temp_counter);
+                        self.temp_counter += 1;
+                        self.output.push_str(&format!("({{ char* {} = getenv(", temp_var));
+                        self.generate_expression(&arguments[0])?;
+                        self.output.push_str(&format!(
+                            "); {1} ? ({0}){{ .tag = {0}_Some, .data = {{ .some_value = {1} }} }} : ({0}){{ .tag = {0}_None }}; }})",
+                            option_c, temp_var
+                        ));
+                    } else if name == "assert" {
+                        // assert(cond[, message]) -> rapter_assert(cond, message)
+                        self.output.push_str("rapter_assert(");
+                        self.generate_expression(&arguments[0])?;
+                        self.output.push_str(", ");
+                        match arguments.get(1) {
+                            Some(message) => self.generate_expression(message)?,
+                            None => self.output.push_str("\"assertion failed\""),
+                        }
+                        self.output.push(')');
+                    } else if name == "in_range" {
+                        // in_range(x, lo, hi) -> (x >= lo && x <= hi), with x evaluated once
+                        let temp_var = format!("__in_range_{}", self.temp_counter);
+                        self.temp_counter += 1;
+                        self.output.push_str(&format!("({{ {} {} = ", self.infer_c_type(&arguments[0]), temp_var));
+                        self.generate_expression(&arguments[0])?;
+                        self.output.push_str(&format!("; ({} >= ", temp_var));
+                        self.generate_expression(&arguments[1])?;
+                        self.output.push_str(&format!(" && {} <= ", temp_var));
+                        self.generate_expression(&arguments[2])?;
+                        self.output.push_str("); })");
+                    } else if name == "hex" {
+                        // hex(n) -> rapter_hex(n), a malloc'd string of lowercase hex digits
+                        self.output.push_str("rapter_hex(");
+                        self.generate_expression(&arguments[0])?;
+                        self.output.push(')');
+                    } else if name == "read_lines" {
+                        // read_lines(path) -> rapter_read_lines(path), the file's lines without their endings
+                        self.output.push_str("rapter_read_lines(");
+                        self.generate_expression(&arguments[0])?;
+                        self.output.push(')');
+                    } else if name == "pad" {
+                        // pad(s, width) -> rapter_pad(s, width), a malloc'd copy padded with spaces
+                        self.output.push_str("rapter_pad(");
+                        self.generate_expression(&arguments[0])?;
+                        self.output.push_str(", ");
+                        self.generate_expression(&arguments[1])?;
+                        self.output.push(')');
                     } else {
-                        // Regular function call
-                        self.output.push_str(name);
+                        // Regular function call; a const-generic callee is called through its
+                        // specialization for the argument lengths
+                        if let Some(func) = self.const_generic_functions.get(name).cloned() {
+                            let specialization = (name.clone(), self.const_param_values(&func, arguments)?);
+                            self.output.push_str(&func.specialized_name(&specialization.1));
+                            if !self.specializations.contains(&specialization) {
+                                self.specializations.push(specialization);
+                            }
+                        } else {
+                            self.output.push_str(name);
+                        }
                         self.output.push_str("(");
+                        let by_ref = self.ref_functions.get(name).cloned().unwrap_or_default();
                         for (i, arg) in arguments.iter().enumerate() {
                             if i > 0 {
                                 self.output.push_str(", ");
                             }
+                            // Semantic analysis made sure `ref` arguments are places
+                            if by_ref.get(i) == Some(&true) {
+                                self.output.push('&');
+                            }
                             self.generate_expression(arg)?;
                         }
                         self.output.push_str(")");
@@ -841,6 +1630,9 @@ impl CCodeGenerator {
                 } else if let Expression::StructAccess { object, field } = &**callee {
                     // Distinguish between module-qualified calls (module.func) and methods (obj.method)
                     if let Expression::Variable(obj_name) = &**object {
+                        // In a method, a bare field name is `self.field`, even if a module has the same name
+                        let field_receiver = self.implicit_self_field(obj_name).map(|_| format!("self.{}", obj_name));
+                        let obj_name = field_receiver.as_deref().unwrap_or(obj_name);
                         let mut obj_type = self.expr_type(object).unwrap_or(Type::Int);
                         
                         // Normalize str to String type
@@ -853,9 +1645,7 @@ impl CCodeGenerator {
                         match (&obj_type, field.as_str()) {
                             // String methods
                             (&Type::String, "length") => {
-                                self.output.push_str("strlen(");
-                                self.generate_expression(object)?;
-                                self.output.push_str(")");
+                                self.generate_string_length(object, "")?;
                             }
                             (&Type::String, "substring") => {
                                 self.output.push_str("rapter_substring(");
@@ -873,8 +1663,8 @@ impl CCodeGenerator {
                                 self.generate_expression(&arguments[0])?;
                                 self.output.push_str(") != NULL ? 1 : 0)");
                             }
-                            (&Type::String, "trim") => {
-                                self.output.push_str("rapter_trim(");
+                            (&Type::String, "trim" | "trim_start" | "trim_end" | "to_upper" | "to_lower") => {
+                                self.output.push_str(&format!("rapter_{}(", field));
                                 self.generate_expression(object)?;
                                 self.output.push_str(")");
                             }
@@ -931,15 +1721,36 @@ impl CCodeGenerator {
                                     self.output.push_str("/* pop expects no arguments */");
                                 }
                             }
-                            (&Type::DynamicArray(_), "length") => {
+                            (Type::Int | Type::Long | Type::Float | Type::Bool | Type::Char, "to_string") if self.expr_type(object).is_some() => {
+                                self.generate_to_string(object, &obj_type)?;
+                            }
+                            (Type::DynamicArray(elem_ty), "to_string") if **elem_ty == Type::Char => {
+                                self.generate_chars_to_string(object)?;
+                            }
+                            (Type::DynamicArray(elem_ty), "map" | "filter") if arguments.len() == 1 => {
+                                self.generate_map_filter(field, object, elem_ty, &arguments[0])?;
+                            }
+                            (&Type::DynamicArray(_), "length" | "len") => {
                                 if arguments.is_empty() {
-                                    self.output.push_str("(");
+                                    self.output.push_str("((int)");
                                     self.output.push_str(obj_name);
                                     self.output.push_str(".size)");
                                 } else {
                                     self.output.push_str("/* length expects no arguments */");
                                 }
                             }
+                            // The element storage, e.g. to hand the array to C: qsort(v.data(), v.len(), ...)
+                            (&Type::DynamicArray(_), "data") => {
+                                self.output.push_str(obj_name);
+                                self.output.push_str(".data");
+                            }
+                            // Method from an impl block: p.sum() -> Point_sum(p)
+                            (&Type::Struct(ref type_name), _) | (&Type::Enum(ref type_name), _)
+                                if self.get_impl_function(type_name, field).is_some() =>
+                            {
+                                self.output.push_str(&Impl::mangled_name(type_name, field));
+                                self.generate_call_arguments(Some(object), arguments)?;
+                            }
                             // Assume module-qualified function call like module.func
                             _ => {
                                 self.output.push_str(field);
@@ -951,9 +1762,29 @@ impl CCodeGenerator {
                                 self.output.push_str(")");
                             }
                         }
+                    } else if let Some(type_name) = self.impl_method_owner(object, field) {
+                        self.output.push_str(&Impl::mangled_name(&type_name, field));
+                        self.generate_call_arguments(Some(object), arguments)?;
+                    } else if let (Some(ty @ (Type::Int | Type::Long | Type::Float | Type::Bool | Type::Char)), "to_string") = (self.expr_type(object), field.as_str()) {
+                        self.generate_to_string(object, &ty)?;
+                    } else if matches!(
+                        (self.expr_type(object), field.as_str()),
+                        (Some(Type::String), _) | (Some(Type::DynamicArray(_)), "data")
+                    ) {
+                        // String methods on any string expression: "abc".length(), s.trim().to_upper(),
+                        // and the storage of any array expression: make_vec().data()
+                        self.generate_expression(&Expression::MethodCall {
+                            object: object.clone(),
+                            method: field.clone(),
+                            arguments: arguments.clone(),
+                        })?;
                     } else {
                         self.output.push_str("/* method calls on non-variables not supported */");
                     }
+                } else if let Some(mangled) = self.associated_function(callee) {
+                    // Associated function call: Point::new(1, 2) -> Point_new(1, 2)
+                    self.output.push_str(&mangled);
+                    self.generate_call_arguments(None, arguments)?;
                 } else if let Expression::EnumAccess { enum_name, variant } = &**callee {
                     // Enum variant constructor call: Option::Some(42), Result::Ok(value)
                     // Generate C code: (Option_int){ .tag = Option_int_Some, .data = { .some_value = 42 } }
@@ -1018,8 +1849,22 @@ impl CCodeGenerator {
                     )));
                 }
             }
+            Expression::Tuple(elements) => {
+                // (a, b) -> ((Tuple_int_int){ a, b })
+                let tuple_type = self.tuple_type(elements);
+                self.track_generic_type(&tuple_type);
+                self.output.push_str(&format!("(({}){{ ", self.type_to_c(&tuple_type)));
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.generate_expression(element)?;
+                }
+                self.output.push_str(" })");
+            }
             Expression::ArrayLiteral(elements) => {
-                self.output.push_str("(int[]){");
+                let elem_ty = self.array_literal_element_type(elements);
+                self.output.push_str(&format!("({}[]){{", self.type_to_c(&elem_ty)));
                 for (i, elem) in elements.iter().enumerate() {
                     if i > 0 {
                         self.output.push_str(", ");
@@ -1092,8 +1937,44 @@ impl CCodeGenerator {
                 self.output.push_str(")");
             }
             Expression::ArrayAccess { array, index } => {
+                if let Expression::Range { start, end } = &**index {
+                    return self.generate_slice(array, start, end);
+                }
                 // If the array is a dynamic array, index its .data field; otherwise use [] directly
-                let is_dyn = match self.expr_type(array) { Some(Type::DynamicArray(_)) => true, _ => false };
+                let array_ty = self.expr_type(array);
+                let is_dyn = match array_ty { Some(Type::DynamicArray(_)) => true, _ => false };
+                if let Some(n) = crate::semantic::negative_constant_index(index) {
+                    // Any other receiver is evaluated once, into a temporary; the element is
+                    // reached through a pointer so it can still be assigned to
+                    if (is_dyn || array_ty == Some(Type::String)) && !matches!(**array, Expression::Variable(_)) {
+                        let temp = format!("__indexed_{}", self.temp_counter);
+                        self.temp_counter += 1;
+                        let c_type = self.type_to_c(array_ty.as_ref().unwrap());
+                        self.output.push_str(&format!("(*({{ {} {} = ", c_type, temp));
+                        self.generate_expression(array)?;
+                        if is_dyn {
+                            self.output.push_str(&format!("; &{0}.data[{0}.size - {1}]; }}))", temp, -n));
+                        } else {
+                            self.output.push_str(&format!("; &{0}[strlen({0}) - {1}]; }}))", temp, -n));
+                        }
+                        return Ok(());
+                    }
+                    // Negative index counts from the end: a[-n] -> a[len - n]
+                    if is_dyn {
+                        self.output.push('(');
+                        self.generate_expression(array)?;
+                        self.output.push_str(").data[(");
+                        self.generate_expression(array)?;
+                        self.output.push_str(&format!(").size - {}]", -n));
+                        return Ok(());
+                    } else if array_ty == Some(Type::String) {
+                        self.generate_expression(array)?;
+                        self.output.push_str("[strlen(");
+                        self.generate_expression(array)?;
+                        self.output.push_str(&format!(") - {}]", -n));
+                        return Ok(());
+                    }
+                }
                 if is_dyn {
                     self.output.push_str("(");
                     self.generate_expression(array)?;
@@ -1108,8 +1989,8 @@ impl CCodeGenerator {
                 }
             }
             Expression::StructAccess { object, field } => {
-                // Check if object needs parentheses (e.g., for dereference)
-                let needs_parens = matches!(&**object, Expression::Unary { operator: UnaryOp::Dereference, .. });
+                // Check if object needs parentheses (e.g., for dereference, or a call's result)
+                let needs_parens = matches!(&**object, Expression::Unary { operator: UnaryOp::Dereference, .. } | Expression::Call { .. } | Expression::MethodCall { .. });
                 
                 if needs_parens {
                     self.output.push_str("(");
@@ -1121,12 +2002,51 @@ impl CCodeGenerator {
                 self.output.push_str(".");
                 self.output.push_str(field);
             }
-            Expression::StructLiteral { name, fields } => {
-                // Generate: (Name){ .field = value, ... }
+            Expression::OptionalField { object, field } => {
+                // ({ Option_Point __chain = p; __chain.tag == Option_Point_Some ? Some(__chain.data.some_value.x) : None; })
+                // and for pointers ({ Point* __chain = p; __chain ? Some(__chain->x) : None; })
+                let object_ty = self.expr_type(object).unwrap_or(Type::Void);
+                let option_c = self.type_to_c(&self.expr_type(expr).unwrap_or(Type::Void));
+                let temp_var = format!("__chain_{}", self.temp_counter);
+                self.temp_counter += 1;
+                self.output.push_str(&format!("({{ {} {} = ", self.type_to_c(&object_ty), temp_var));
+                self.generate_expression(object)?;
+                let (present, value) = if let Type::Pointer(_) = object_ty {
+                    (temp_var.clone(), format!("{}->{}", temp_var, field))
+                } else {
+                    let object_c = self.type_to_c(&object_ty);
+                    (format!("{}.tag == {}_Some", temp_var, object_c), format!("{}.data.some_value.{}", temp_var, field))
+                };
+                self.output.push_str(&format!(
+                    "; {1} ? ({0}){{ .tag = {0}_Some, .data = {{ .some_value = {2} }} }} : ({0}){{ .tag = {0}_None }}; }})",
+                    option_c, present, value
+                ));
+            }
+            Expression::StructLiteral { name, fields, base: Some(base) } => {
+                // Spread: copy the base, then overwrite the explicit fields
+                // ({ Name __tmp = base; __tmp.field = value; __tmp; })
+                let tmp = format!("__struct_tmp_{}", self.temp_counter);
+                self.temp_counter += 1;
+                self.output.push_str(&format!("({{ {} {} = ", name, tmp));
+                self.generate_expression(base)?;
+                self.output.push_str("; ");
+                for (fname, fexpr) in fields {
+                    self.output.push_str(&format!("{}.{} = ", tmp, fname));
+                    self.generate_expression(fexpr)?;
+                    self.output.push_str("; ");
+                }
+                self.output.push_str(&format!("{}; }})", tmp));
+            }
+            Expression::StructLiteral { name, fields, base: None } => {
+                // Generate: (Name){ .field = value, ... }, filling omitted fields from their defaults
+                let defaults: Vec<(String, Expression)> = self.struct_defaults.get(name).into_iter().flatten()
+                    .filter(|(fname, _)| !fields.iter().any(|(provided, _)| provided == fname))
+                    .cloned()
+                    .collect();
                 self.output.push_str("(");
                 self.output.push_str(name);
                 self.output.push_str("){ ");
-                for (i, (fname, fexpr)) in fields.iter().enumerate() {
+                for (i, (fname, fexpr)) in fields.iter().chain(&defaults).enumerate() {
                     if i > 0 { self.output.push_str(", "); }
                     self.output.push_str(".");
                     self.output.push_str(fname);
@@ -1135,7 +2055,18 @@ impl CCodeGenerator {
                 }
                 self.output.push_str(" }");
             }
+            // Checked by the semantic pass; nothing of the assertion is left at runtime
+            Expression::AssertType { expression, .. } => self.generate_expression(expression)?,
             Expression::Cast { expression, target_type } => {
+                match (self.expr_type(expression), target_type) {
+                    (Some(Type::String), Type::DynamicArray(elem)) if **elem == Type::Char => {
+                        return self.generate_string_to_chars(expression);
+                    }
+                    (Some(Type::DynamicArray(elem)), Type::String) if *elem == Type::Char => {
+                        return self.generate_chars_to_string(expression);
+                    }
+                    _ => {}
+                }
                 // Generate C cast: (target_type)expression
                 self.output.push_str("(");
                 self.output.push_str(&self.type_to_c(target_type));
@@ -1158,218 +2089,10 @@ impl CCodeGenerator {
                 self.output.push_str(&format!("{}_{}", enum_name.to_uppercase(), variant.to_uppercase()));
             }
             Expression::Match { scrutinee, arms } => {
-                use crate::ast::Pattern;
-                
-                // For match expressions, we need to use a GCC statement expression ({ ... })
-                // or generate a temporary function. For simplicity, we'll use statement expressions.
-                self.output.push_str("({\n");
-                self.indent_level += 1;
-                
-                // Generate a temporary variable for the scrutinee
-                let temp_var = format!("__match_temp_{}", self.temp_counter);
-                self.temp_counter += 1;
-                
-                self.indent();
-                let scrutinee_type = self.expr_type(scrutinee).unwrap_or(Type::Int);
-                self.output.push_str(&self.type_to_c(&scrutinee_type));
-                self.output.push_str(&format!(" {} = ", temp_var));
-                self.generate_expression(scrutinee)?;
-                self.output.push_str(";\n");
-                
-                // Determine result type from arms - try all arms until we find one with an inferable type
-                let result_type = arms.iter()
-                    .filter_map(|arm| self.expr_type(&arm.expression))
-                    .next()
-                    .unwrap_or(Type::Int); // Default to int if no arm has inferable type
-                let result_var = format!("__match_result_{}", self.temp_counter);
-                self.temp_counter += 1;
-                
-                self.indent();
-                self.output.push_str(&self.type_to_c(&result_type));
-                self.output.push_str(&format!(" {};\n", result_var));
-                
-                // Check if we can use a switch statement (int/enum/char types)
-                // Note: Due to parser limitations, enums might be typed as Struct, so we check both
-                // Also handle Generic types (Option, Result, etc.)
-                let use_switch = matches!(scrutinee_type, Type::Int | Type::Enum(_) | Type::Struct(_) | Type::Char | Type::Generic { .. });
-                
-                if use_switch {
-                    // Generate switch statement
-                    self.indent();
-                    // For generic types, switch on the tag field
-                    if matches!(scrutinee_type, Type::Generic { .. }) {
-                        self.output.push_str(&format!("switch ({}.tag) {{\n", temp_var));
-                    } else {
-                        self.output.push_str(&format!("switch ({}) {{\n", temp_var));
-                    }
-                    self.indent_level += 1;
-                    
-                    for arm in arms {
-                        match &arm.pattern {
-                            Pattern::Wildcard => {
-                                self.indent();
-                                self.output.push_str("default:\n");
-                                self.indent_level += 1;
-                                self.indent();
-                                self.output.push_str(&format!("{} = ", result_var));
-                                self.generate_expression(&arm.expression)?;
-                                self.output.push_str(";\n");
-                                self.indent();
-                                self.output.push_str("break;\n");
-                                self.indent_level -= 1;
-                            }
-                            Pattern::Literal(lit) => {
-                                self.indent();
-                                self.output.push_str("case ");
-                                match lit {
-                                    crate::ast::Literal::Integer(val) => self.output.push_str(&val.to_string()),
-                                    crate::ast::Literal::Char(ch) => {
-                                        // Properly escape special chars in case labels
-                                        let esc: Option<&str> = match *ch {
-                                            '\\' => Some("\\\\"),
-                                            '\'' => Some("\\'"),
-                                            '\n' => Some("\\n"),
-                                            '\t' => Some("\\t"),
-                                            '\r' => Some("\\r"),
-                                            '\0' => Some("\\0"),
-                                            _ => None,
-                                        };
-                                        self.output.push_str("'");
-                                        if let Some(e) = esc {
-                                            self.output.push_str(e);
-                                        } else {
-                                            self.output.push(*ch);
-                                        }
-                                        self.output.push_str("'");
-                                    },
-                                    _ => self.output.push_str("/* unsupported literal */"),
-                                }
-                                self.output.push_str(":\n");
-                                self.indent_level += 1;
-                                self.indent();
-                                self.output.push_str(&format!("{} = ", result_var));
-                                self.generate_expression(&arm.expression)?;
-                                self.output.push_str(";\n");
-                                self.indent();
-                                self.output.push_str("break;\n");
-                                self.indent_level -= 1;
-                            }
-                            Pattern::EnumVariant { enum_name, variant, binding } => {
-                                self.indent();
-                                self.output.push_str("case ");
-                                // For generic types, generate mangled enum variant names
-                                if matches!(scrutinee_type, Type::Generic { ref name, .. } if name == enum_name) {
-                                    // Generate: Option_int_Some
-                                    self.output.push_str(&self.type_to_c(&scrutinee_type));
-                                    self.output.push_str("_");
-                                    self.output.push_str(variant);
-                                } else {
-                                    // Regular enum: OPTION_SOME (uppercase)
-                                    self.output.push_str(&format!("{}_{}", enum_name.to_uppercase(), variant.to_uppercase()));
-                                }
-                                self.output.push_str(": {\n");
-                                self.indent_level += 1;
-                                
-                                // If there's a binding (and it's not a wildcard), extract the value from the union
-                                if let Some(binding_name) = binding {
-                                    if binding_name != "_" {
-                                        self.indent();
-                                        // Get the type of the bound value
-                                        if let Type::Generic { ref type_params, .. } = scrutinee_type {
-                                            if !type_params.is_empty() {
-                                                let value_type = &type_params[0];
-                                                self.output.push_str(&self.type_to_c(value_type));
-                                                self.output.push_str(" ");
-                                                self.output.push_str(binding_name);
-                                                self.output.push_str(" = ");
-                                                self.output.push_str(&temp_var);
-                                                self.output.push_str(".data.");
-                                                self.output.push_str(&format!("{}_value", variant.to_lowercase()));
-                                                self.output.push_str(";\n");
-                                            }
-                                        }
-                                    }
-                                }
-                                
-                                self.indent();
-                                self.output.push_str(&format!("{} = ", result_var));
-                                self.generate_expression(&arm.expression)?;
-                                self.output.push_str(";\n");
-                                self.indent();
-                                self.output.push_str("break;\n");
-                                self.indent_level -= 1;
-                                self.indent();
-                                self.output.push_str("}\n");
-                            }
-                        }
-                    }
-                    
-                    self.indent_level -= 1;
-                    self.indent();
-                    self.output.push_str("}\n");
-                } else {
-                    // Generate if-else chain for other types
-                    let mut first = true;
-                    for arm in arms {
-                        match &arm.pattern {
-                            Pattern::Wildcard => {
-                                // Default case
-                                if !first {
-                                    self.output.push_str(" else ");
-                                }
-                                self.output.push_str("{\n");
-                                self.indent_level += 1;
-                                self.indent();
-                                self.output.push_str(&format!("{} = ", result_var));
-                                self.generate_expression(&arm.expression)?;
-                                self.output.push_str(";\n");
-                                self.indent_level -= 1;
-                                self.indent();
-                                self.output.push_str("}\n");
-                            }
-                            Pattern::Literal(lit) => {
-                                self.indent();
-                                if !first {
-                                    self.output.push_str("else ");
-                                }
-                                self.output.push_str(&format!("if ({} == ", temp_var));
-                                match lit {
-                                    crate::ast::Literal::String(s) => {
-                                        self.output.push_str(&format!("strcmp({}, \"{}\") == 0", temp_var, s));
-                                    }
-                                    crate::ast::Literal::Float(f) => {
-                                        self.output.push_str(&f.to_string());
-                                    }
-                                    crate::ast::Literal::Bool(b) => {
-                                        self.output.push_str(if *b { "1" } else { "0" });
-                                    }
-                                    _ => self.output.push_str("/* unsupported */"),
-                                }
-                                self.output.push_str(") {\n");
-                                self.indent_level += 1;
-                                self.indent();
-                                self.output.push_str(&format!("{} = ", result_var));
-                                self.generate_expression(&arm.expression)?;
-                                self.output.push_str(";\n");
-                                self.indent_level -= 1;
-                                self.indent();
-                                self.output.push_str("}\n");
-                                first = false;
-                            }
-                            Pattern::EnumVariant { .. } => {
-                                // Should not happen for non-int/enum types
-                            }
-                        }
-                    }
-                }
-                
-                // Return the result
-                self.indent();
-                self.output.push_str(&format!("{};\n", result_var));
-                
-                self.indent_level -= 1;
-                self.indent();
-                self.output.push_str("})");
+                self.generate_match(scrutinee, arms, false)?;
+            }
+            Expression::Loop { body } => {
+                self.generate_loop_value(body)?;
             }
             Expression::InterpolatedString { parts } => {
                 // Generate sprintf code for string interpolation
@@ -1392,6 +2115,7 @@ impl CCodeGenerator {
                             let expr_type = self.expr_type(expr).unwrap_or(Type::Int);
                             let format_spec = match expr_type {
                                 Type::Int => "%d",
+                                Type::Long => "%lld",
                                 Type::Float => "%f",
                                 Type::Char => "%c",
                                 Type::String => "%s",
@@ -1602,9 +2326,7 @@ impl CCodeGenerator {
                     // String methods
                     (&Type::String, "length") => {
                         // string.length() -> strlen(string)
-                        self.output.push_str("strlen(");
-                        self.generate_expression(object)?;
-                        self.output.push_str(")");
+                        self.generate_string_length(object, "")?;
                     }
                     (&Type::String, "substring") => {
                         // string.substring(start, end) -> rapter_substring(string, start, end)
@@ -1624,9 +2346,9 @@ impl CCodeGenerator {
                         self.generate_expression(&arguments[0])?;
                         self.output.push_str(") != NULL ? 1 : 0)");
                     }
-                    (&Type::String, "trim") => {
-                        // string.trim() -> rapter_trim(string)
-                        self.output.push_str("rapter_trim(");
+                    (&Type::String, "trim" | "trim_start" | "trim_end" | "to_upper" | "to_lower") => {
+                        // string.trim() -> rapter_trim(string), likewise trim_start/trim_end and the case conversions
+                        self.output.push_str(&format!("rapter_{}(", method));
                         self.generate_expression(object)?;
                         self.output.push_str(")");
                     }
@@ -1692,10 +2414,23 @@ impl CCodeGenerator {
                             self.output.push_str("/* method calls on non-variables not supported */");
                         }
                     }
-                    (&Type::DynamicArray(_), "length") => {
+                    (Type::Int | Type::Long | Type::Float | Type::Bool | Type::Char, "to_string") => {
+                        self.generate_to_string(object, &obj_type)?;
+                    }
+                    (Type::DynamicArray(elem_ty), "to_string") if **elem_ty == Type::Char => {
+                        if let Expression::Variable(_) = &**object {
+                            self.generate_chars_to_string(object)?;
+                        } else {
+                            self.output.push_str("/* method calls on non-variables not supported */");
+                        }
+                    }
+                    (Type::DynamicArray(elem_ty), "map" | "filter") if arguments.len() == 1 => {
+                        self.generate_map_filter(method, object, elem_ty, &arguments[0])?;
+                    }
+                    (&Type::DynamicArray(_), "length" | "len") => {
                         if let Expression::Variable(obj_name) = &**object {
                             if arguments.is_empty() {
-                                self.output.push_str("(");
+                                self.output.push_str("((int)");
                                 self.output.push_str(obj_name);
                                 self.output.push_str(".size)");
                             } else {
@@ -1705,6 +2440,16 @@ impl CCodeGenerator {
                             self.output.push_str("/* method calls on non-variables not supported */");
                         }
                     }
+                    (&Type::DynamicArray(_), "data") => {
+                        if let Expression::Variable(obj_name) = &**object {
+                            self.output.push_str(obj_name);
+                            self.output.push_str(".data");
+                        } else {
+                            self.output.push('(');
+                            self.generate_expression(object)?;
+                            self.output.push_str(").data");
+                        }
+                    }
                     _ => {
                         self.output.push_str("/* method not supported: ");
                         self.output.push_str(method);
@@ -1728,6 +2473,11 @@ impl CCodeGenerator {
         self.indent();
         self.output.push_str("__rapter_argc = argc; __rapter_argv = argv;\n");
         self.indent();
+        // Imported modules initialize themselves before main runs
+        for init in self.module_inits.clone() {
+            self.output.push_str(&format!("{}();\n", init));
+            self.indent();
+        }
         
         // Check if main returns void
         let main_return_type = self.func_types.get("main").cloned().unwrap_or(Type::Void);
@@ -1744,14 +2494,40 @@ impl CCodeGenerator {
         Ok(())
     }
     
+    // main for `--test`: runs each `@test` function, recovering from failed asserts through
+    // setjmp, then reports the counts and fails if any test did
+    fn generate_test_runner(&mut self, ast: &Program) -> Result<(), CompilerError> {
+        self.output.push_str("int main(int argc, char* argv[]) {\n");
+        self.output.push_str("    __rapter_argc = argc; __rapter_argv = argv;\n");
+        for init in &self.module_inits {
+            self.output.push_str(&format!("    {}();\n", init));
+        }
+        // Modified between setjmp and longjmp, so they must be volatile
+        self.output.push_str("    volatile int passed = 0, failed = 0;\n");
+        self.output.push_str("    jmp_buf env;\n");
+        self.output.push_str("    __rapter_test_jmp = &env;\n");
+        for func in ast.functions.iter().filter(|f| f.has_attribute("test")) {
+            self.output.push_str(&format!(
+                "    if (setjmp(env) == 0) {{ {0}(); printf(\"test {0} ... ok\\n\"); passed++; }} else {{ printf(\"test {0} ... FAILED\\n\"); failed++; }}\n",
+                func.name
+            ));
+        }
+        self.output.push_str("    __rapter_test_jmp = NULL;\n");
+        self.output.push_str("    printf(\"\\ntest result: %s. %d passed; %d failed\\n\", failed ? \"FAILED\" : \"ok\", passed, failed);\n");
+        self.output.push_str("    return failed ? 1 : 0;\n");
+        self.output.push_str("}\n");
+        Ok(())
+    }
+    
     fn type_to_c(&self, ty: &Type) -> String {
         match ty {
             Type::Int => "int".to_string(),
+            Type::Long => "long long".to_string(),
             Type::Float => "double".to_string(),
             Type::Bool => "int".to_string(), // C doesn't have bool, use int
             Type::Char => "char".to_string(),
             Type::String => "char*".to_string(),
-            Type::Array(elem_ty) => format!("{}*", self.type_to_c(elem_ty)),
+            Type::Array(elem_ty, _) => format!("{}*", self.type_to_c(elem_ty)),
             Type::DynamicArray(elem_ty) => match &**elem_ty {
                 Type::Int => "DynamicArray_int".to_string(),
                 Type::Float => "DynamicArray_double".to_string(),
@@ -1771,7 +2547,10 @@ impl CCodeGenerator {
                 }
             },
             Type::Enum(_) => "int".to_string(), // Enums are represented as ints in C
+            // Function pointers go through a typedef emitted with the generic type definitions
+            Type::Function(..) | Type::Tuple(_) => self.type_to_mangled_name(ty),
             Type::Void => "void".to_string(),
+            Type::Nil => "void*".to_string(),
             // Generic types are monomorphized: Option<int> -> Option_int
             Type::Generic { name, type_params } => {
                 let param_names: Vec<String> = type_params.iter()
@@ -1790,6 +2569,7 @@ impl CCodeGenerator {
     fn type_to_mangled_name(&self, ty: &Type) -> String {
         match ty {
             Type::Int => "int".to_string(),
+            Type::Long => "long".to_string(),
             Type::Float => "float".to_string(),
             Type::Bool => "bool".to_string(),
             Type::Char => "char".to_string(),
@@ -1804,9 +2584,22 @@ impl CCodeGenerator {
                 }
             },
             Type::Enum(name) => name.clone(),
-            Type::Array(elem) => format!("arr_{}", self.type_to_mangled_name(elem)),
+            Type::Array(elem, _) => format!("arr_{}", self.type_to_mangled_name(elem)),
             Type::DynamicArray(elem) => format!("vec_{}", self.type_to_mangled_name(elem)),
+            Type::Function(params, ret) => {
+                let params: Vec<String> = params.iter()
+                    .map(|t| self.type_to_mangled_name(t))
+                    .collect();
+                format!("fn_{}_to_{}", params.join("_"), self.type_to_mangled_name(ret))
+            },
+            Type::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter()
+                    .map(|t| self.type_to_mangled_name(t))
+                    .collect();
+                format!("Tuple_{}", elements.join("_"))
+            },
             Type::Void => "void".to_string(),
+            Type::Nil => "nil".to_string(),
             Type::Generic { name, type_params } => {
                 let params: Vec<String> = type_params.iter()
                     .map(|t| self.type_to_mangled_name(t))
@@ -1825,7 +2618,10 @@ impl CCodeGenerator {
             Expression::Literal(Literal::Bool(_)) => "int".to_string(),
             Expression::Literal(Literal::Char(_)) => "char".to_string(),
             Expression::Literal(Literal::String(_)) => "char*".to_string(),
-            Expression::ArrayLiteral(_) => "int*".to_string(), // arrays decay to pointers
+            Expression::ArrayLiteral(elements) => {
+                // arrays decay to pointers
+                format!("{}*", self.type_to_c(&self.array_literal_element_type(elements)))
+            }
             Expression::DynamicArrayLiteral { element_type, .. } => {
                 // Return the typedef name for dynamic arrays
                 match &**element_type {
@@ -1841,18 +2637,16 @@ impl CCodeGenerator {
             Expression::New(inner) => format!("{}*", self.infer_c_type(inner)), // new returns a pointer to inner
             Expression::Call { callee, .. } => {
                 // Try to resolve function return type
-                if let Expression::Variable(name) = &**callee {
-                    return self.type_to_c(self.func_types.get(name).unwrap_or(&Type::Int));
-                } else if let Expression::StructAccess { field, .. } = &**callee {
-                    return self.type_to_c(self.func_types.get(field).unwrap_or(&Type::Int));
+                if let Expression::Variable(_) | Expression::StructAccess { .. } | Expression::EnumAccess { .. } = &**callee {
+                    return self.type_to_c(&self.expr_type(expr).unwrap_or(Type::Int));
                 }
                 "int".to_string()
             }
-            Expression::ArrayAccess { array, .. } => {
+            Expression::ArrayAccess { array, index } if !matches!(**index, Expression::Range { .. }) => {
                 // Infer element C type for arrays and dynamic arrays
                 if let Some(ty) = self.expr_type(array) {
                     match ty {
-                        Type::Array(elem) | Type::DynamicArray(elem) | Type::Pointer(elem) => {
+                        Type::Array(elem, _) | Type::DynamicArray(elem) | Type::Pointer(elem) => {
                             return self.type_to_c(&elem);
                         }
                         _ => {}
@@ -1875,6 +2669,14 @@ impl CCodeGenerator {
         }
     }
     
+    // Element type of an array literal: the first element whose type is known (consts,
+    // enum variants, struct literals, ...), or int
+    fn array_literal_element_type(&self, elements: &[Expression]) -> Type {
+        elements.iter()
+            .find_map(|elem| self.expr_type(elem))
+            .unwrap_or(Type::Int)
+    }
+    
     fn infer_printf_format(&self, expr: &Expression) -> String {
         match expr {
             Expression::Literal(Literal::Integer(_)) => "%d".to_string(),
@@ -1883,13 +2685,15 @@ impl CCodeGenerator {
             Expression::Literal(Literal::Char(_)) => "%c".to_string(),
             Expression::Literal(Literal::String(_)) => "%s".to_string(),
             Expression::Variable(name) => {
-                if let Some(ty) = self.get_var_type(name) {
+                if let Some(ty) = self.get_var_type(name).or_else(|| self.implicit_self_field(name)) {
                     return match ty {
-                        Type::Int | Type::Bool | Type::Enum(_) | Type::Pointer(_) => "%d".to_string(),
+                        Type::Int | Type::Bool | Type::Enum(_) => "%d".to_string(),
+                        Type::Long => "%lld".to_string(),
+                        Type::Pointer(_) | Type::Function(..) | Type::Nil => "%p".to_string(),
                         Type::Float => "%f".to_string(),
                         Type::Char => "%c".to_string(),
                         Type::String => "%s".to_string(),
-                        Type::Array(_) | Type::DynamicArray(_) | Type::Struct(_) | Type::Void => "%d".to_string(),
+                        Type::Array(_, _) | Type::DynamicArray(_) | Type::Struct(_) | Type::Tuple(_) | Type::Void => "%d".to_string(),
                         Type::Generic { .. } => "%d".to_string(), // Generic types default to %d for now
                         Type::TypeParam(_) => "%d".to_string(),   // Type params default to %d for now
                     };
@@ -1898,18 +2702,27 @@ impl CCodeGenerator {
             }
             Expression::Binary { left, operator, right } => {
                 // Special case: if this is string concatenation, the result is a string
-                if *operator == BinaryOp::Add && (self.contains_string_literal(left) || self.contains_string_literal(right)) {
+                if self.is_string_concatenation(left, operator, right) {
                     "%s".to_string()
                 } else {
-                    "%d".to_string() // Default to int for other binary operations
+                    match self.expr_type(expr) {
+                        Some(Type::Long) => "%lld".to_string(),
+                        Some(Type::Float) => "%f".to_string(),
+                        _ => "%d".to_string(), // Default to int for other binary operations
+                    }
                 }
             }
-            Expression::ArrayAccess { array, .. } => {
-                // If we can infer element type, use it
+            Expression::ArrayAccess { array, index } if !matches!(**index, Expression::Range { .. }) => {
+                // If we can infer element type, use it; a string's elements are chars
                 if let Some(ty) = self.expr_type(array) {
-                    if let Type::DynamicArray(elem) | Type::Array(elem) = ty {
+                    if matches!(ty, Type::String) {
+                        return "%c".to_string();
+                    }
+                    if let Type::DynamicArray(elem) | Type::Array(elem, _) = ty {
                         return match *elem {
-                            Type::Int | Type::Bool | Type::Pointer(_) => "%d".to_string(),
+                            Type::Int | Type::Bool => "%d".to_string(),
+                            Type::Long => "%lld".to_string(),
+                            Type::Pointer(_) => "%p".to_string(),
                             Type::Float => "%f".to_string(),
                             Type::Char => "%c".to_string(),
                             Type::String => "%s".to_string(),
@@ -1919,7 +2732,26 @@ impl CCodeGenerator {
                 }
                 "%d".to_string()
             }
-            _ => "%d".to_string(), // Default fallback
+            _ => match self.expr_type(expr) {
+                Some(Type::Pointer(_)) => "%p".to_string(),
+                Some(Type::String) => "%s".to_string(),
+                Some(Type::Float) => "%f".to_string(),
+                Some(Type::Char) => "%c".to_string(),
+                Some(Type::Long) => "%lld".to_string(),
+                _ => "%d".to_string(), // Default fallback
+            },
+        }
+    }
+
+    // Emit a printf argument; `%p` requires a `void*`
+    fn generate_printf_argument(&mut self, expr: &Expression, format_spec: &str) -> Result<(), CompilerError> {
+        if format_spec == "%p" {
+            self.output.push_str("(void*)(");
+            self.generate_expression(expr)?;
+            self.output.push(')');
+            Ok(())
+        } else {
+            self.generate_expression(expr)
         }
     }
     
@@ -1930,7 +2762,7 @@ impl CCodeGenerator {
             Expression::Variable(var_name) => {
                 // Use type info when available
                 if let Some(ty) = self.get_var_type(var_name) {
-                    matches!(ty, Type::Array(_) | Type::DynamicArray(_))
+                    matches!(ty, Type::Array(_, _) | Type::DynamicArray(_))
                 } else { false }
             }
             _ => false,
@@ -1947,42 +2779,491 @@ impl CCodeGenerator {
         }
     }
     
-    fn generate_array_print(&mut self, expr: &Expression, add_newline: bool) -> Result<(), CompilerError> {
-        match expr {
-            Expression::ArrayLiteral(elements) => {
-                // For array literals, we know the size at compile time
-                self.output.push_str("printf(\"[\");\n");
-                self.indent();
-                for (i, elem) in elements.iter().enumerate() {
-                    if i > 0 {
-                        self.output.push_str("printf(\", \");\n");
-                        self.indent();
-                    }
-                    let format_spec = self.infer_printf_format(elem);
-                    self.output.push_str("printf(\"");
-                    self.output.push_str(&format_spec);
-                    self.output.push_str("\"");
-                    self.output.push_str(", ");
-                    self.generate_expression(elem)?;
-                    self.output.push_str(");\n");
+    // `s[a..b]` -> rapter_substring(s, a, b); `arr[a..b]` -> a new array holding a copy of
+    // the range. Out-of-range bounds are clamped, as rapter_substring does for strings.
+    fn generate_slice(&mut self, array: &Expression, start: &Expression, end: &Expression) -> Result<(), CompilerError> {
+        let elem = match self.expr_type(array) {
+            Some(Type::DynamicArray(elem)) => elem,
+            _ => {
+                self.output.push_str("rapter_substring(");
+                self.generate_expression(array)?;
+                self.output.push_str(", ");
+                self.generate_expression(start)?;
+                self.output.push_str(", ");
+                self.generate_expression(end)?;
+                self.output.push(')');
+                return Ok(());
+            }
+        };
+        let n = self.temp_counter;
+        self.temp_counter += 1;
+        let array_c = self.type_to_c(&Type::DynamicArray(elem.clone()));
+        let elem_c = self.type_to_c(&elem);
+        self.output.push_str(&format!("({{ {} __slice_src_{} = ", array_c, n));
+        self.generate_expression(array)?;
+        self.output.push_str(&format!("; int __slice_start_{} = ", n));
+        self.generate_expression(start)?;
+        self.output.push_str(&format!("; int __slice_end_{} = ", n));
+        self.generate_expression(end)?;
+        self.output.push_str(&format!(
+            "; if (__slice_start_{n} < 0) __slice_start_{n} = 0; \
+             if (__slice_end_{n} > (int)__slice_src_{n}.size) __slice_end_{n} = (int)__slice_src_{n}.size; \
+             if (__slice_end_{n} < __slice_start_{n}) __slice_end_{n} = __slice_start_{n}; \
+             {array_c} __slice_{n}; __slice_{n}.size = __slice_end_{n} - __slice_start_{n}; \
+             __slice_{n}.capacity = __slice_{n}.size ? __slice_{n}.size : 4; \
+             __slice_{n}.data = ({elem_c}*)malloc(__slice_{n}.capacity * sizeof({elem_c})); \
+             memcpy(__slice_{n}.data, __slice_src_{n}.data + __slice_start_{n}, __slice_{n}.size * sizeof({elem_c})); \
+             __slice_{n}; }})",
+            n = n, array_c = array_c, elem_c = elem_c
+        ));
+        Ok(())
+    }
+    
+    // Generate a match; as a statement its arms may be blocks and no value is produced
+    fn generate_match(&mut self, scrutinee: &Expression, arms: &[MatchArm], as_statement: bool) -> Result<(), CompilerError> {
+        // For match expressions, we need to use a GCC statement expression ({ ... })
+        // or generate a temporary function. For simplicity, we'll use statement expressions.
+        // A match used as a statement produces no value and is emitted as a plain block.
+        self.output.push_str(if as_statement { "{\n" } else { "({\n" });
+        self.indent_level += 1;
+        
+        // Generate a temporary variable for the scrutinee
+        let temp_var = format!("__match_temp_{}", self.temp_counter);
+        self.temp_counter += 1;
+        
+        self.indent();
+        let scrutinee_type = self.expr_type(scrutinee).unwrap_or(Type::Int);
+        self.output.push_str(&self.type_to_c(&scrutinee_type));
+        self.output.push_str(&format!(" {} = ", temp_var));
+        self.generate_expression(scrutinee)?;
+        self.output.push_str(";\n");
+        
+        let result_var = if as_statement {
+            None
+        } else {
+            let result_type = self.match_arms_type(arms).unwrap_or(Type::Int); // Default to int if no arm has inferable type
+            let result_var = format!("__match_result_{}", self.temp_counter);
+            self.temp_counter += 1;
+            
+            self.indent();
+            self.output.push_str(&self.type_to_c(&result_type));
+            self.output.push_str(&format!(" {};\n", result_var));
+            Some(result_var)
+        };
+        
+        self.enter_scope();
+        
+        // Check if we can use a switch statement (int/enum/char types)
+        // Note: Due to parser limitations, enums might be typed as Struct, so we check both
+        // Also handle Generic types (Option, Result, etc.), unless an arm nests a pattern inside
+        // a variant: its cases would repeat the outer tag, so those matches test each arm in turn
+        let nested = arms.iter()
+            .flat_map(|arm| arm.pattern.alternatives())
+            .any(|pattern| matches!(pattern, Pattern::EnumVariant { nested: Some(_), .. }));
+        let use_switch = !nested && matches!(scrutinee_type, Type::Int | Type::Enum(_) | Type::Struct(_) | Type::Char | Type::Generic { .. });
+        
+        // Inside the switch a `break` from a block arm would only leave the switch, so loop
+        // breaks set a flag instead and are re-issued once the switch is done
+        let break_flag = if use_switch && arms.iter().any(|arm| matches!(&arm.body, ArmBody::Block(body) if contains_loop_break(body))) {
+            let flag = format!("__match_break_{}", self.temp_counter);
+            self.temp_counter += 1;
+            self.indent();
+            self.output.push_str(&format!("int {} = 0;\n", flag));
+            Some(flag)
+        } else {
+            None
+        };
+        let outer_break_flag = self.match_break_flag.clone();
+        if break_flag.is_some() {
+            self.match_break_flag = break_flag.clone();
+        }
+        
+        if use_switch {
+            // Generate switch statement. The whole switch stays inside the ({ ... })
+            // statement-expression, so a `continue` after the match still targets the
+            // enclosing loop; each `break` below only leaves the switch.
+            self.indent();
+            // For generic types, switch on the tag field
+            if matches!(scrutinee_type, Type::Generic { .. }) {
+                self.output.push_str(&format!("switch ({}.tag) {{\n", temp_var));
+            } else {
+                self.output.push_str(&format!("switch ({}) {{\n", temp_var));
+            }
+            self.indent_level += 1;
+            
+            // The values that earlier literal and range arms already have labels for
+            let mut covered: Vec<(i64, i64)> = Vec::new();
+            for arm in arms {
+                // Labels for each alternative, falling through to the arm's block. Every case
+                // gets its own block, so declarations in one arm never clash with a sibling arm's
+                let mut labels = Vec::new();
+                for alternative in arm.pattern.alternatives() {
+                    labels.extend(self.switch_labels(alternative, &scrutinee_type, &mut covered).ok_or_else(empty_range_error)?);
+                }
+                for (i, label) in labels.iter().enumerate() {
                     self.indent();
+                    self.output.push_str(label);
+                    self.output.push_str(if i + 1 == labels.len() { ": {\n" } else { ":\n" });
                 }
-                self.output.push_str("printf(\"");
-                self.output.push_str("]");
-                if add_newline {
-                    self.output.push_str("\\n");
+                if labels.is_empty() {
+                    // Earlier arms match every value of this one; only unanalyzed modules get here
+                    self.indent();
+                    self.output.push_str("{\n");
                 }
-                self.output.push_str("\");");
+                self.indent_level += 1;
+                // The binding is only visible inside this arm
+                self.enter_scope();
+                self.generate_outer_bindings(&arm.pattern, &temp_var, &scrutinee_type);
+                
+                // If there's a binding (and it's not a wildcard), extract the value from the union
+                self.generate_pattern_bindings(arm.pattern.without_binding(), &temp_var, &scrutinee_type);
+                
+                self.generate_arm_body(&arm.body, result_var.as_deref())?;
+                self.exit_scope();
+                self.indent();
+                self.output.push_str("break;\n");
+                self.indent_level -= 1;
+                self.indent();
+                self.output.push_str("}\n");
             }
-            Expression::Variable(var_name) => {
-                // Attempt to print a dynamic array variable by iterating over its size
-                if let Some(Type::DynamicArray(elem_ty)) = self.get_var_type(var_name) {
-                    let elem_format = match &*elem_ty {
-                        Type::Int => "%d",
-                        Type::Float => "%f",
-                        Type::Bool => "%d",
-                        Type::Char => "%c",
-                        Type::String => "%s",
+            // Without a wildcard arm, a value no arm matches would leave the result unset:
+            // a value match stops the program instead, a statement match does nothing
+            let has_wildcard = arms.iter().any(|arm| arm.pattern.alternatives().iter().any(|p| matches!(p, Pattern::Wildcard)));
+            if !has_wildcard {
+                self.indent();
+                self.output.push_str(if result_var.is_some() { "default: abort();\n" } else { "default: break;\n" });
+            }
+            
+            self.indent_level -= 1;
+            self.indent();
+            self.output.push_str("}\n");
+        } else {
+            // Generate if-else chain for other types and nested patterns; each arm is its own block
+            let mut first = true;
+            let mut exhaustive = false;
+            for arm in arms {
+                // The conditions of this arm's alternatives; `None` when it matches anything
+                let mut conditions = Some(Vec::new());
+                for alternative in arm.pattern.alternatives() {
+                    match self.pattern_condition(alternative, &temp_var, &scrutinee_type)? {
+                        Some(condition) => {
+                            if let Some(conditions) = conditions.as_mut() {
+                                conditions.push(condition);
+                            }
+                        }
+                        None => conditions = None,
+                    }
+                }
+                if first {
+                    self.indent();
+                } else {
+                    self.output.push_str(" else ");
+                }
+                if let Some(conditions) = &conditions {
+                    let parenthesize = conditions.len() > 1;
+                    let conditions: Vec<String> = conditions.iter()
+                        .map(|condition| if parenthesize && condition.contains(" && ") { format!("({})", condition) } else { condition.clone() })
+                        .collect();
+                    self.output.push_str(&format!("if ({}) ", conditions.join(" || ")));
+                }
+                self.output.push_str("{\n");
+                self.indent_level += 1;
+                self.enter_scope();
+                self.generate_outer_bindings(&arm.pattern, &temp_var, &scrutinee_type);
+                self.generate_pattern_bindings(arm.pattern.without_binding(), &temp_var, &scrutinee_type);
+                self.generate_arm_body(&arm.body, result_var.as_deref())?;
+                self.exit_scope();
+                self.indent_level -= 1;
+                self.indent();
+                self.output.push('}');
+                first = false;
+                if conditions.is_none() {
+                    // Later arms are unreachable after the default
+                    exhaustive = true;
+                    break;
+                }
+            }
+            if !exhaustive && result_var.is_some() {
+                // As in the switch: a value no arm matches would leave the result unset
+                self.output.push_str(" else {\n");
+                self.indent_level += 1;
+                self.indent();
+                self.output.push_str("abort();\n");
+                self.indent_level -= 1;
+                self.indent();
+                self.output.push('}');
+            }
+            if !first {
+                self.output.push('\n');
+            }
+        }
+        
+        self.exit_scope();
+        self.match_break_flag = outer_break_flag;
+        if let Some(flag) = break_flag {
+            self.indent();
+            self.output.push_str(&format!("if ({}) ", flag));
+            self.generate_break();
+        }
+        
+        // Return the result
+        if let Some(result_var) = result_var {
+            self.indent();
+            self.output.push_str(&format!("{};\n", result_var));
+        }
+        
+        self.indent_level -= 1;
+        self.indent();
+        self.output.push_str(if as_statement { "}" } else { "})" });
+        Ok(())
+    }
+    
+    // `case ...` or `default` labels for one switch pattern; outer bindings and alternatives are
+    // handled by the caller. A literal or range only gets labels for the values not in `covered`,
+    // which an earlier arm matches first, and adds its own. `None` for a range that matches nothing.
+    fn switch_labels(&self, pattern: &Pattern, scrutinee_type: &Type, covered: &mut Vec<(i64, i64)>) -> Option<Vec<String>> {
+        if let (Some(left), Pattern::Literal(like) | Pattern::Range { start: like, .. }) = (pattern.unmatched_values(covered), pattern) {
+            // GCC case ranges are inclusive at both ends
+            let labels = left.iter().map(|&(first, last)| if first == last {
+                format!("case {}", value_label(first, like))
+            } else {
+                format!("case {} ... {}", value_label(first, like), value_label(last, like))
+            }).collect();
+            covered.extend(left);
+            return Some(labels);
+        }
+        Some(vec![match pattern {
+            Pattern::Wildcard => "default".to_string(),
+            Pattern::Literal(lit) => format!("case {}", case_label(lit)),
+            Pattern::Range { .. } => return None,
+            Pattern::EnumVariant { enum_name, variant, .. } => {
+                if matches!(scrutinee_type, Type::Generic { name, .. } if name == enum_name) {
+                    // For generic types, generate mangled enum variant names: Option_int_Some
+                    format!("case {}_{}", self.type_to_c(scrutinee_type), variant)
+                } else {
+                    // Regular enum: OPTION_SOME (uppercase)
+                    format!("case {}_{}", enum_name.to_uppercase(), variant.to_uppercase())
+                }
+            }
+            Pattern::Binding { .. } | Pattern::Or(_) => unreachable!("outer bindings and alternatives are expanded before the dispatch"),
+            Pattern::Tuple(_) => unreachable!("tuples are matched with an if-else chain"),
+        }])
+    }
+    
+    // The C condition under which `value`, of type `ty`, matches one alternative of a pattern;
+    // `None` when it matches anything
+    fn pattern_condition(&mut self, pattern: &Pattern, value: &str, ty: &Type) -> Result<Option<String>, CompilerError> {
+        Ok(match pattern {
+            Pattern::Wildcard => None,
+            // Only nested patterns still carry their `name @`
+            Pattern::Binding { pattern, .. } => self.pattern_condition(pattern, value, ty)?,
+            Pattern::Or(_) => unreachable!("alternatives are expanded before the dispatch"),
+            Pattern::Literal(lit) => {
+                let start = self.output.len();
+                self.generate_expression(&Expression::Literal(lit.clone()))?;
+                let literal = self.output.split_off(start);
+                if let crate::ast::Literal::String(_) = lit {
+                    Some(format!("strcmp({}, {}) == 0", value, literal))
+                } else {
+                    Some(format!("{} == {}", value, literal))
+                }
+            }
+            Pattern::Range { .. } => {
+                let (first, last) = range_labels(pattern).ok_or_else(empty_range_error)?;
+                Some(format!("({} >= {} && {} <= {})", value, first, value, last))
+            }
+            Pattern::EnumVariant { enum_name, variant, nested, .. } => match ty {
+                Type::Generic { name, type_params } if name == enum_name => {
+                    // Test the tag, then whatever the nested pattern requires of the value
+                    let mut condition = format!("{}.tag == {}_{}", value, self.type_to_c(ty), variant);
+                    if let Some(nested) = nested {
+                        let value_type = self.builtins.get_generic(name)
+                            .and_then(|builtin| builtin.variant_value_type(variant, type_params))
+                            .unwrap_or(Type::Int);
+                        let payload = format!("{}.data.{}_value", value, variant.to_lowercase());
+                        if let Some(inner) = self.pattern_condition(nested, &payload, &value_type)? {
+                            condition = format!("{} && {}", condition, inner);
+                        }
+                    }
+                    Some(condition)
+                }
+                _ => Some(format!("{} == {}_{}", value, enum_name.to_uppercase(), variant.to_uppercase())),
+            },
+            Pattern::Tuple(elements) => {
+                // Every element must match its own pattern
+                let element_types = match ty {
+                    Type::Tuple(element_types) => element_types.clone(),
+                    _ => Vec::new(),
+                };
+                let mut conditions = Vec::new();
+                for (i, element) in elements.iter().enumerate() {
+                    let element_type = element_types.get(i).cloned().unwrap_or(Type::Int);
+                    if let Some(condition) = self.pattern_condition(element, &format!("{}._{}", value, i), &element_type)? {
+                        conditions.push(condition);
+                    }
+                }
+                (!conditions.is_empty()).then(|| conditions.join(" && "))
+            }
+        })
+    }
+    
+    // Declare the names `name @ pattern` binds to the whole of `value`, of type `ty`
+    fn generate_outer_bindings(&mut self, pattern: &Pattern, value: &str, ty: &Type) {
+        for name in pattern.outer_bindings() {
+            self.indent();
+            self.output.push_str(&format!("{} {} = {};\n", self.type_to_c(ty), name, value));
+            self.set_var_type(name, ty.clone());
+        }
+    }
+    
+    // Declare the variables a pattern binds inside `value`, of type `ty`: the value of
+    // `Variant(name)`, the names bound by a nested pattern within the variant's value,
+    // and those bound inside the elements of a tuple
+    fn generate_pattern_bindings(&mut self, pattern: &Pattern, value: &str, ty: &Type) {
+        if let (Pattern::Tuple(elements), Type::Tuple(element_types)) = (pattern, ty) {
+            // Each element binds inside its own field; `name @` binds the whole element
+            for (i, (element, element_type)) in elements.iter().zip(element_types).enumerate() {
+                let field = format!("{}._{}", value, i);
+                self.generate_outer_bindings(element, &field, element_type);
+                self.generate_pattern_bindings(element.without_binding(), &field, element_type);
+            }
+            return;
+        }
+        let Pattern::EnumVariant { variant, binding, nested, .. } = pattern else {
+            return;
+        };
+        // The type of the variant's value: its own type parameter
+        let Type::Generic { name, type_params } = ty else {
+            return;
+        };
+        let Some(value_type) = self.builtins.get_generic(name)
+            .and_then(|builtin| builtin.variant_value_type(variant, type_params)) else {
+            return;
+        };
+        let payload = format!("{}.data.{}_value", value, variant.to_lowercase());
+        if let Some(binding_name) = binding.as_ref().filter(|name| *name != "_") {
+            self.indent();
+            self.output.push_str(&format!("{} {} = {};\n", self.type_to_c(&value_type), binding_name, payload));
+            self.set_var_type(binding_name, value_type.clone());
+        }
+        if let Some(nested) = nested {
+            self.generate_outer_bindings(nested, &payload, &value_type);
+            self.generate_pattern_bindings(nested.without_binding(), &payload, &value_type);
+        }
+    }
+    
+    // Emit one match arm: assign its value to `result_var`, or run its statements
+    fn generate_arm_body(&mut self, body: &ArmBody, result_var: Option<&str>) -> Result<(), CompilerError> {
+        match body {
+            ArmBody::Expression(expression) => {
+                self.indent();
+                if let Some(result_var) = result_var {
+                    self.output.push_str(&format!("{} = ", result_var));
+                }
+                self.generate_expression(expression)?;
+                self.output.push_str(";\n");
+            }
+            ArmBody::Block(stmts) => {
+                self.enter_scope();
+                for stmt in stmts {
+                    self.generate_statement(stmt)?;
+                }
+                self.generate_scope_drops(stmts);
+                self.exit_scope();
+            }
+        }
+        Ok(())
+    }
+    
+    // `{ ... }` of a while loop or a `loop` statement, through the closing line
+    fn generate_loop_body(&mut self, body: &[Statement]) -> Result<(), CompilerError> {
+        self.output.push_str("{\n");
+        self.indent_level += 1;
+        self.enter_scope();
+        let outer_break_flag = self.match_break_flag.take();
+        let outer_loop_depth = std::mem::replace(&mut self.loop_depth, self.var_types.len());
+        for stmt in body {
+            self.generate_statement(stmt)?;
+        }
+        self.generate_scope_drops(body);
+        self.loop_depth = outer_loop_depth;
+        self.match_break_flag = outer_break_flag;
+        self.exit_scope();
+        self.indent_level -= 1;
+        self.indent();
+        self.output.push_str("}\n");
+        Ok(())
+    }
+    
+    // A `loop` used as a value: a statement expression whose `break`s store into a result variable
+    fn generate_loop_value(&mut self, body: &[Statement]) -> Result<(), CompilerError> {
+        let value_type = self.loop_value_type(body, &mut HashMap::new()).unwrap_or(Type::Int);
+        let result_var = format!("__loop_result_{}", self.temp_counter);
+        self.temp_counter += 1;
+        
+        self.output.push_str("({\n");
+        self.indent_level += 1;
+        self.indent();
+        self.output.push_str(&format!("{} {};\n", self.type_to_c(&value_type), result_var));
+        self.indent();
+        self.output.push_str("while (1) ");
+        let outer_result = self.loop_result.replace(result_var.clone());
+        self.generate_loop_body(body)?;
+        self.loop_result = outer_result;
+        self.indent();
+        self.output.push_str(&format!("{};\n", result_var));
+        self.indent_level -= 1;
+        self.indent();
+        self.output.push_str("})");
+        Ok(())
+    }
+    
+    // `break;`, or through the enclosing match's flag when inside its switch
+    fn generate_break(&mut self) {
+        match &self.match_break_flag {
+            Some(flag) => self.output.push_str(&format!("{{ {} = 1; break; }}\n", flag)),
+            None => self.output.push_str("break;\n"),
+        }
+    }
+    
+    fn generate_array_print(&mut self, expr: &Expression, add_newline: bool) -> Result<(), CompilerError> {
+        match expr {
+            Expression::ArrayLiteral(elements) => {
+                // For array literals, we know the size at compile time
+                self.output.push_str("printf(\"[\");\n");
+                self.indent();
+                for (i, elem) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str("printf(\", \");\n");
+                        self.indent();
+                    }
+                    let format_spec = self.infer_printf_format(elem);
+                    self.output.push_str("printf(\"");
+                    self.output.push_str(&format_spec);
+                    self.output.push_str("\"");
+                    self.output.push_str(", ");
+                    self.generate_printf_argument(elem, &format_spec)?;
+                    self.output.push_str(");\n");
+                    self.indent();
+                }
+                self.output.push_str("printf(\"");
+                self.output.push_str("]");
+                if add_newline {
+                    self.output.push_str("\\n");
+                }
+                self.output.push_str("\");");
+            }
+            Expression::Variable(var_name) => {
+                // Attempt to print a dynamic array variable by iterating over its size
+                if let Some(Type::DynamicArray(elem_ty)) = self.get_var_type(var_name) {
+                    let elem_format = match &*elem_ty {
+                        Type::Int => "%d",
+                        Type::Float => "%f",
+                        Type::Bool => "%d",
+                        Type::Char => "%c",
+                        // Strings are quoted so element boundaries stay visible: ["a", "b"]
+                        Type::String => "\\\"%s\\\"",
+                        Type::Struct(name) if name == "str" => "\\\"%s\\\"",
                         _ => "%d",
                     };
                     self.output.push_str("printf(\"[\");\n");
@@ -2077,7 +3358,207 @@ impl CCodeGenerator {
         Ok(())
     }
     
+    fn is_string_concatenation(&self, left: &Expression, operator: &BinaryOp, right: &Expression) -> bool {
+        // If either operand contains a string literal, treat this as string concatenation
+        *operator == BinaryOp::Add && (self.contains_string_literal(left) || self.contains_string_literal(right)
+            || self.expr_type(left) == Some(Type::String) || self.expr_type(right) == Some(Type::String))
+    }
+    
+    // Binary operations emitted as a helper call or statement-expression are atomic operands
+    fn is_lowered_to_call(&self, left: &Expression, operator: &BinaryOp, right: &Expression) -> bool {
+        self.is_string_concatenation(left, operator, right)
+            || (matches!(operator, BinaryOp::Equal | BinaryOp::NotEqual)
+                && (self.generic_operand_type(left, right).is_some() || self.dynamic_array_operand_type(left, right).is_some()))
+            || operator.method_name().is_some_and(|method| self.impl_method_owner(left, method).is_some())
+    }
+    
+    // Emit `left op right`, wrapping a nested binary operand in parens only when C precedence
+    // would otherwise regroup it (or, for readability, `&&` directly under `||`)
+    fn generate_binary_operands(&mut self, left: &Expression, operator: &BinaryOp, right: &Expression) -> Result<(), CompilerError> {
+        for (operand, is_right) in [(left, false), (right, true)] {
+            if is_right {
+                self.output.push_str(&format!(" {} ", binary_op_str(operator)));
+            }
+            match operand {
+                Expression::Binary { left: l, operator: op, right: r }
+                    if !self.is_lowered_to_call(l, op, r) =>
+                {
+                    let (parent, child) = (binary_precedence(operator), binary_precedence(op));
+                    let needs_parens = child < parent
+                        || (child == parent && is_right)
+                        || (*operator == BinaryOp::Or && *op == BinaryOp::And);
+                    if needs_parens { self.output.push('('); }
+                    self.generate_binary_operands(l, op, r)?;
+                    if needs_parens { self.output.push(')'); }
+                }
+                _ => self.generate_expression(operand)?,
+            }
+        }
+        Ok(())
+    }
+    
+    // Value of a fixed array length; semantic analysis has already checked that it evaluates
+    fn array_length(&self, length: &ArrayLength) -> Result<usize, CompilerError> {
+        match length {
+            ArrayLength::Literal(n) => Ok(*n),
+            ArrayLength::ConstCall { function, arguments } => {
+                ConstEvaluator::new(&self.const_functions)
+                    .call(function, arguments)
+                    .map(|n| n.max(0) as usize)
+                    .map_err(|message| CompilerError::new(
+                        ErrorKind::InvalidOperation,
+                        format!("cannot evaluate array length `{}(..)`: {}", function, message),
+                        SourceLocation::new(PathBuf::from("input.rap"), 0, 0),
+                    ))
+            }
+            ArrayLength::Param(name) => self.const_args.get(name).copied().ok_or_else(|| CompilerError::new(
+                ErrorKind::InvalidOperation,
+                format!("array length `{}` is not known outside of its function", name),
+                SourceLocation::new(PathBuf::from("input.rap"), 0, 0),
+            )),
+        }
+    }
+    
+    // Values of a const-generic callee's parameters, from the fixed lengths of the arguments
+    fn const_param_values(&self, func: &Function, arguments: &[Expression]) -> Result<Vec<usize>, CompilerError> {
+        let lengths: Vec<Option<usize>> = arguments.iter()
+            .map(|arg| match self.expr_type(arg) {
+                Some(Type::Array(_, Some(length))) => self.array_length(&length).ok(),
+                _ => None,
+            })
+            .collect();
+        func.const_param_values(&lengths).map_err(|message| CompilerError::new(
+            ErrorKind::TypeMismatch,
+            message,
+            SourceLocation::new(PathBuf::from("input.rap"), 0, 0),
+        ))
+    }
+    
+    // A const-generic function as the plain function `name_3`, each `N` a local constant;
+    // `[T; N]` types in it resolve through `const_args`
+    fn specialize(&self, func: &Function, values: &[usize]) -> Function {
+        let mut body: Vec<Statement> = func.const_params.iter().zip(values)
+            .map(|(name, value)| Statement::Const {
+                name: name.clone(),
+                var_type: Some(Type::Int),
+                initializer: Expression::Literal(Literal::Integer(*value as i64)),
+            })
+            .collect();
+        body.extend(func.body.iter().cloned());
+        Function {
+            name: func.specialized_name(values),
+            const_params: Vec::new(),
+            body,
+            ..func.clone()
+        }
+    }
+    
+    // strlen(value), after `cast`; a literal's length is known at compile time
+    fn generate_string_length(&mut self, value: &Expression, cast: &str) -> Result<(), CompilerError> {
+        if let Some(text) = folded_string_literal(value) {
+            // strlen stops at the first NUL
+            self.output.push_str(&text.find('\0').unwrap_or(text.len()).to_string());
+            return Ok(());
+        }
+        self.output.push_str(cast);
+        self.output.push_str("strlen(");
+        self.generate_expression(value)?;
+        self.output.push(')');
+        Ok(())
+    }
+    
+    // x.to_string() on a primitive -> a malloc'd string, sized by a first snprintf; bools
+    // become "true" or "false"
+    fn generate_to_string(&mut self, value: &Expression, ty: &Type) -> Result<(), CompilerError> {
+        if *ty == Type::Bool {
+            self.output.push_str("strdup((");
+            self.generate_expression(value)?;
+            self.output.push_str(") ? \"true\" : \"false\")");
+            return Ok(());
+        }
+        let format = match ty {
+            Type::Long => "%lld",
+            Type::Float => "%f",
+            Type::Char => "%c",
+            _ => "%d",
+        };
+        let (temp, len, buf) = (
+            format!("__tostr_{}", self.temp_counter),
+            format!("__tostr_len_{}", self.temp_counter),
+            format!("__tostr_buf_{}", self.temp_counter),
+        );
+        self.temp_counter += 1;
+        self.output.push_str(&format!("({{ {} {} = ", self.type_to_c(ty), temp));
+        self.generate_expression(value)?;
+        self.output.push_str(&format!(
+            "; int {1} = snprintf(NULL, 0, \"{3}\", {0}); char* {2} = (char*)malloc({1} + 1); snprintf({2}, {1} + 1, \"{3}\", {0}); {2}; }})",
+            temp, len, buf, format
+        ));
+        Ok(())
+    }
+    
+    // chars.to_string() / chars as string -> a malloc'd, null-terminated copy of the characters
+    fn generate_chars_to_string(&mut self, chars: &Expression) -> Result<(), CompilerError> {
+        let (src, dst) = (format!("__chars_{}", self.temp_counter), format!("__str_{}", self.temp_counter));
+        self.temp_counter += 1;
+        self.output.push_str(&format!("({{ DynamicArray_char {} = ", src));
+        self.generate_expression(chars)?;
+        self.output.push_str(&format!(
+            "; char* {1} = (char*)malloc({0}.size + 1); memcpy({1}, {0}.data, {0}.size); {1}[{0}.size] = 0; {1}; }})",
+            src, dst
+        ));
+        Ok(())
+    }
+    
+    // v.map(f) / v.filter(f) -> a loop over a copy of `v` filling a new dynamic array
+    fn generate_map_filter(&mut self, method: &str, array: &Expression, elem_ty: &Type, function: &Expression) -> Result<(), CompilerError> {
+        let result_elem = match (method, self.expr_type(function)) {
+            ("map", Some(Type::Function(_, ret))) => *ret,
+            _ => elem_ty.clone(),
+        };
+        let array_c = self.type_to_c(&Type::DynamicArray(Box::new(elem_ty.clone())));
+        let result_c = self.type_to_c(&Type::DynamicArray(Box::new(result_elem.clone())));
+        let result_elem_c = self.type_to_c(&result_elem);
+        let n = self.temp_counter;
+        self.temp_counter += 1;
+        let (src, dst, i) = (format!("__src_{}", n), format!("__{}_{}", method, n), format!("__i_{}", n));
+        self.output.push_str(&format!("({{ {} {} = ", array_c, src));
+        self.generate_expression(array)?;
+        self.output.push_str(&format!(
+            "; {0} {1}; {1}.size = 0; {1}.capacity = {2}.size > 0 ? {2}.size : 1; {1}.data = ({3}*)malloc({1}.capacity * sizeof({3})); for (size_t {4} = 0; {4} < {2}.size; {4}++) {{ ",
+            result_c, dst, src, result_elem_c, i
+        ));
+        if method == "map" {
+            self.output.push_str(&format!("{}.data[{}.size++] = ", dst, dst));
+            self.generate_expression(function)?;
+            self.output.push_str(&format!("({}.data[{}]); ", src, i));
+        } else {
+            self.output.push_str("if (");
+            self.generate_expression(function)?;
+            self.output.push_str(&format!("({0}.data[{1}])) {2}.data[{2}.size++] = {0}.data[{1}]; ", src, i, dst));
+        }
+        self.output.push_str(&format!("}} {}; }})", dst));
+        Ok(())
+    }
+    
+    // s as DynamicArray[char] -> a char vector holding a copy of the string's bytes (without the terminator)
+    fn generate_string_to_chars(&mut self, string: &Expression) -> Result<(), CompilerError> {
+        let (src, dst) = (format!("__chars_src_{}", self.temp_counter), format!("__chars_{}", self.temp_counter));
+        self.temp_counter += 1;
+        self.output.push_str(&format!("({{ char* {} = ", src));
+        self.generate_expression(string)?;
+        self.output.push_str(&format!(
+            "; DynamicArray_char {1}; {1}.size = strlen({0}); {1}.capacity = {1}.size > 0 ? {1}.size : 1; {1}.data = (char*)malloc({1}.capacity); memcpy({1}.data, {0}, {1}.size); {1}; }})",
+            src, dst
+        ));
+        Ok(())
+    }
+    
     fn generate_string_concatenation(&mut self, left: &Expression, right: &Expression) -> Result<(), CompilerError> {
+        // Literal-only concatenations are folded into a single C string literal
+        if let (Some(l), Some(r)) = (folded_string_literal(left), folded_string_literal(right)) {
+            return self.generate_expression(&Expression::Literal(Literal::String(l + &r)));
+        }
         // Generate: ({ char* result = malloc(strlen(left) + strlen(right) + 1); strcpy(result, left); strcat(result, right); result; })
         self.output.push_str("({");
         self.output.push_str("char* result = malloc(strlen(");
@@ -2113,8 +3594,11 @@ impl CCodeGenerator {
     }
 }
 
-pub fn generate(ast: &Program, resolver: &mut ModuleResolver, output_file: Option<&str>) -> Result<(), CompilerError> {
+pub fn generate(ast: &Program, resolver: &mut ModuleResolver, output_file: Option<&str>, test_runner: bool) -> Result<(), CompilerError> {
     let mut generator = CCodeGenerator::new();
+    if test_runner {
+        generator = generator.with_test_runner();
+    }
     generator.generate(ast, resolver, &PathBuf::from("input.rap"))?;
     
     let output_path = output_file.unwrap_or("output.c");
@@ -2129,10 +3613,81 @@ pub fn generate(ast: &Program, resolver: &mut ModuleResolver, output_file: Optio
     Ok(())
 }
 
+/// Write the header for `ast`'s exports to `header_file`, guarded by a macro named after the file
+pub fn generate_header(ast: &Program, header_file: &str) -> Result<(), CompilerError> {
+    let stem = Path::new(header_file).file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let guard: String = stem.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    let mut generator = CCodeGenerator::new();
+    generator.generate_header(ast, &format!("{}_H", guard))?;
+    generator.write_to_file(header_file)
+}
+
 // Helper methods for type-aware codegen
 impl CCodeGenerator {
     fn enter_scope(&mut self) { self.var_types.push(HashMap::new()); }
-    fn exit_scope(&mut self) { self.var_types.pop(); }
+    fn exit_scope(&mut self) {
+        let depth = self.var_types.len();
+        self.drop_locals.retain(|(local_depth, ..)| *local_depth < depth);
+        self.var_types.pop();
+    }
+    // C name of the `drop` method of a struct type, if it has one
+    fn drop_function(&self, ty: &Type) -> Option<String> {
+        match ty {
+            Type::Struct(name) if self.impl_functions.get(name).is_some_and(|functions| functions.contains_key("drop")) => {
+                Some(Impl::mangled_name(name, "drop"))
+            }
+            _ => None,
+        }
+    }
+    // Drop calls for the locals in scopes at least `depth` deep, innermost first
+    fn drop_calls(&self, depth: usize, except: Option<&str>) -> Vec<String> {
+        self.drop_locals.iter().rev()
+            .filter(|(local_depth, name, ..)| *local_depth >= depth && Some(name.as_str()) != except)
+            .map(|(_, name, drop_fn, flag)| match flag {
+                Some(flag) => format!("if (!{}) {}({});", flag, drop_fn, name),
+                None => format!("{}({});", drop_fn, name),
+            })
+            .collect()
+    }
+    // Emit drop calls where a statement starts, leaving the line indented for the next one
+    fn generate_drops(&mut self, drops: &[String]) {
+        for drop in drops {
+            self.output.push_str(drop);
+            self.output.push('\n');
+            self.indent();
+        }
+    }
+    // At the end of a block, drop its own locals unless control already left it
+    fn generate_scope_drops(&mut self, body: &[Statement]) {
+        if matches!(body.last(), Some(Statement::Return(_) | Statement::Break(_) | Statement::Continue)) {
+            return;
+        }
+        for drop in self.drop_calls(self.var_types.len(), None) {
+            self.indent();
+            self.output.push_str(&drop);
+            self.output.push('\n');
+        }
+    }
+    // A local with a `drop` method that is copied into another variable now belongs to it. In a
+    // block nested below the local's own, which may not run, the move sets the local's flag instead
+    fn move_drop_local(&mut self, value: &Expression) {
+        if let Expression::Variable(name) = value {
+            if let Some(position) = self.drop_locals.iter().rposition(|(_, local, ..)| local == name) {
+                match &self.drop_locals[position] {
+                    (depth, _, _, Some(flag)) if *depth < self.var_types.len() => {
+                        let flag = flag.clone();
+                        self.indent();
+                        self.output.push_str(&format!("{} = 1;\n", flag));
+                    }
+                    _ => {
+                        self.drop_locals.remove(position);
+                    }
+                }
+            }
+        }
+    }
     fn set_var_type(&mut self, name: &str, ty: Type) {
         if let Some(scope) = self.var_types.last_mut() {
             scope.insert(name.to_string(), ty);
@@ -2144,6 +3699,119 @@ impl CCodeGenerator {
         }
         None
     }
+    fn collect_ref_functions(&mut self, ast: &Program) {
+        for func in ast.functions.iter().filter(|f| f.parameters.iter().any(|p| p.by_ref)) {
+            self.ref_functions.insert(func.name.clone(), func.parameters.iter().map(|p| p.by_ref).collect());
+        }
+    }
+    // Whether a name refers to a `ref` parameter rather than a local shadowing it
+    fn is_ref_param(&self, name: &str) -> bool {
+        self.ref_params.contains(name)
+            && self.var_types.iter().rposition(|scope| scope.contains_key(name)) == Some(self.ref_param_scope)
+    }
+    // Type of the field of `self` that a bare name refers to in a method; variables come first
+    fn implicit_self_field(&self, name: &str) -> Option<Type> {
+        if self.get_var_type(name).is_some() || self.func_types.contains_key(name) {
+            return None;
+        }
+        let Some(Type::Struct(struct_name)) = self.get_var_type("self") else { return None };
+        self.field_type(&struct_name, name)
+    }
+    // Concrete Option/Result type of either comparison operand (`Option::None` alone has none)
+    fn generic_operand_type(&self, left: &Expression, right: &Expression) -> Option<Type> {
+        [left, right].iter()
+            .filter_map(|e| self.expr_type(e))
+            .find(|t| matches!(t, Type::Generic { name, .. } if self.builtins.is_generic_builtin(name)))
+    }
+    fn dynamic_array_operand_type(&self, left: &Expression, right: &Expression) -> Option<Type> {
+        [left, right].iter()
+            .filter_map(|e| self.expr_type(e))
+            .find(|t| matches!(t, Type::DynamicArray(_)))
+    }
+    fn get_impl_function(&self, type_name: &str, func_name: &str) -> Option<&Function> {
+        self.impl_functions.get(type_name).and_then(|m| m.get(func_name))
+    }
+    // `Type::name` naming an impl function -> its mangled C name
+    fn associated_function(&self, callee: &Expression) -> Option<String> {
+        if let Expression::EnumAccess { enum_name, variant } = callee {
+            if self.get_impl_function(enum_name, variant).is_some() {
+                return Some(Impl::mangled_name(enum_name, variant));
+            }
+        }
+        None
+    }
+    // Type whose impl block defines method `name` for this object, if any
+    fn impl_method_owner(&self, object: &Expression, name: &str) -> Option<String> {
+        match self.expr_type(object) {
+            Some(Type::Struct(type_name)) | Some(Type::Enum(type_name))
+                if self.get_impl_function(&type_name, name).is_some() => Some(type_name),
+            _ => None,
+        }
+    }
+    // Emit `(receiver, args...)` for a lowered impl call
+    fn generate_call_arguments(&mut self, receiver: Option<&Expression>, arguments: &[Expression]) -> Result<(), CompilerError> {
+        self.output.push('(');
+        let mut first = true;
+        for arg in receiver.into_iter().chain(arguments) {
+            if !first { self.output.push_str(", "); }
+            first = false;
+            self.generate_expression(arg)?;
+        }
+        self.output.push(')');
+        Ok(())
+    }
+    // Type of a `loop` used as a value: that of its first `break` value with a known type.
+    // Variables declared in the loop are not in scope yet, so `lets` tracks them.
+    fn loop_value_type(&self, body: &[Statement], lets: &mut HashMap<String, Type>) -> Option<Type> {
+        for stmt in body {
+            match stmt {
+                Statement::Let { name, var_type, initializer, .. } => {
+                    let ty = var_type.clone().or_else(|| initializer.as_ref().and_then(|e| self.expr_type(e)));
+                    if let Some(ty) = ty {
+                        lets.insert(name.clone(), ty);
+                    }
+                }
+                Statement::Break(Some(Expression::Variable(name))) if lets.contains_key(name) => {
+                    return lets.get(name).cloned();
+                }
+                Statement::Break(Some(value)) => {
+                    if let Some(ty) = self.expr_type(value) {
+                        return Some(ty);
+                    }
+                }
+                Statement::If { then_branch, else_branch, .. } => {
+                    let ty = self.loop_value_type(then_branch, lets)
+                        .or_else(|| else_branch.as_deref().and_then(|b| self.loop_value_type(b, lets)));
+                    if ty.is_some() {
+                        return ty;
+                    }
+                }
+                Statement::Expression(Expression::Match { arms, .. }) => {
+                    for arm in arms {
+                        if let ArmBody::Block(arm_body) = &arm.body {
+                            if let Some(ty) = self.loop_value_type(arm_body, lets) {
+                                return Some(ty);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+    
+    // Type of a match's value: the arm values whose types are known, reconciled. An arm
+    // reading a binding of its own pattern has no known type here, so one arm is not enough
+    fn match_arms_type(&self, arms: &[MatchArm]) -> Option<Type> {
+        arms.iter()
+            .filter_map(|arm| match &arm.body {
+                ArmBody::Expression(expression) => self.expr_type(expression),
+                ArmBody::Block(_) => None,
+            })
+            .reduce(common_type)
+    }
+    
     fn expr_type(&self, expr: &Expression) -> Option<Type> {
         match expr {
             Expression::Literal(Literal::Integer(_)) => Some(Type::Int),
@@ -2151,7 +3819,9 @@ impl CCodeGenerator {
             Expression::Literal(Literal::Bool(_)) => Some(Type::Bool),
             Expression::Literal(Literal::Char(_)) => Some(Type::Char),
             Expression::Literal(Literal::String(_)) => Some(Type::String),
-            Expression::Variable(name) => self.get_var_type(name),
+            Expression::Literal(Literal::Nil) => Some(Type::Nil),
+            Expression::Variable(name) => self.get_var_type(name).or_else(|| self.implicit_self_field(name))
+                .or_else(|| self.func_pointer_types.get(name).cloned()),
             Expression::Unary { operator, operand } => match operator {
                 UnaryOp::Dereference => {
                     if let Some(Type::Pointer(inner)) = self.expr_type(operand) { Some(*inner) } else { None }
@@ -2161,37 +3831,98 @@ impl CCodeGenerator {
                 }
                 _ => self.expr_type(operand),
             },
+            Expression::ArrayAccess { array, index } if matches!(**index, Expression::Range { .. }) => {
+                // A slice has the type of what it slices
+                match self.expr_type(array) {
+                    Some(ty @ (Type::DynamicArray(_) | Type::String)) => Some(ty),
+                    _ => None,
+                }
+            }
             Expression::ArrayAccess { array, .. } => {
                 match self.expr_type(array) {
-                    Some(Type::Array(elem)) | Some(Type::DynamicArray(elem)) | Some(Type::Pointer(elem)) => Some(*elem),
+                    Some(Type::Array(elem, _)) | Some(Type::DynamicArray(elem)) | Some(Type::Pointer(elem)) => Some(*elem),
+                    // Indexing a string reads one of its characters
+                    Some(Type::String) => Some(Type::Char),
+                    Some(Type::Struct(name)) if name == "str" => Some(Type::Char),
                     _ => None,
                 }
             }
             Expression::DynamicArrayLiteral { element_type, .. } => Some(Type::DynamicArray(element_type.clone())),
-            Expression::ArrayLiteral(_) => None,
-            Expression::StructAccess { .. } => None,
+            Expression::Tuple(elements) => Some(self.tuple_type(elements)),
+            Expression::ArrayLiteral(elements) => Some(Type::Array(
+                Box::new(self.array_literal_element_type(elements)),
+                Some(ArrayLength::Literal(elements.len())),
+            )),
+            Expression::OptionalField { object, field } => {
+                let struct_name = match self.expr_type(object)? {
+                    Type::Generic { type_params, .. } => match type_params.first()? {
+                        Type::Struct(name) => name.clone(),
+                        _ => return None,
+                    },
+                    Type::Pointer(pointee) => match *pointee {
+                        Type::Struct(name) => name,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                let field_ty = self.field_type(&struct_name, field)?;
+                Some(Type::Generic { name: "Option".to_string(), type_params: vec![field_ty] })
+            }
+            Expression::StructAccess { object, field } => match self.expr_type(object)? {
+                Type::Struct(name) => self.field_type(&name, field),
+                _ => None,
+            },
             Expression::StructLiteral { name, .. } => Some(Type::Struct(name.clone())),
-            Expression::Binary { .. } => None,
-            Expression::Call { callee, .. } => {
+            Expression::Binary { left, operator, right } if self.is_string_concatenation(left, operator, right) => Some(Type::String),
+            Expression::Binary {
+                operator: BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual
+                    | BinaryOp::Greater | BinaryOp::GreaterEqual | BinaryOp::And | BinaryOp::Or,
+                ..
+            } => Some(Type::Bool),
+            // Arithmetic follows C's promotions: int to long, and either to double
+            // An overloaded `+` or `-` has its method's return type
+            Expression::Binary { left, operator, .. } if operator.method_name().and_then(|method| self.impl_method_owner(left, method)).is_some() => {
+                let method = operator.method_name()?;
+                self.func_types.get(&Impl::mangled_name(&self.impl_method_owner(left, method)?, method)).cloned()
+            }
+            Expression::Binary { left, right, .. } => match (self.expr_type(left)?, self.expr_type(right)?) {
+                (Type::Float, Type::Int | Type::Long | Type::Float) | (Type::Int | Type::Long, Type::Float) => Some(Type::Float),
+                (left, right) => match (left.integer_rank()?, right.integer_rank()?) {
+                    (l, r) if l >= r => Some(left),
+                    _ => Some(right),
+                },
+            },
+            Expression::Call { callee, arguments } => {
                 if let Expression::Variable(name) = &**callee {
-                    self.func_types.get(name).cloned()
-                } else if let Expression::StructAccess { field, .. } = &**callee {
-                    self.func_types.get(field).cloned()
+                    // A call through a function pointer variable or parameter
+                    if let Some(Type::Function(_, ret)) = self.get_var_type(name) {
+                        return Some(*ret);
+                    }
+                    self.func_types.get(name).cloned().or_else(|| builtin_function_type(name))
+                } else if let Some(mangled) = self.associated_function(callee) {
+                    self.func_types.get(&mangled).cloned()
+                } else if let Expression::StructAccess { object, field } = &**callee {
+                    match self.impl_method_owner(object, field) {
+                        Some(type_name) => self.func_types.get(&Impl::mangled_name(&type_name, field)).cloned(),
+                        // Built-in methods such as `s.trim()` are typed like method calls
+                        None => self.func_types.get(field).cloned().or_else(|| self.expr_type(&Expression::MethodCall {
+                            object: object.clone(),
+                            method: field.clone(),
+                            arguments: arguments.clone(),
+                        })),
+                    }
                 } else { None }
             }
             Expression::New(inner) => self.expr_type(inner).map(|t| Type::Pointer(Box::new(t))),
             Expression::Delete(_) => Some(Type::Void),
             Expression::Cast { target_type, .. } => Some(target_type.clone()),
-            Expression::Ternary { true_expr, .. } => self.expr_type(true_expr),
-            Expression::EnumAccess { enum_name, .. } => Some(Type::Enum(enum_name.clone())),
-            Expression::Match { arms, .. } => {
-                // Return type of first arm (all arms have compatible types)
-                if !arms.is_empty() {
-                    self.expr_type(&arms[0].expression)
-                } else {
-                    None
-                }
+            Expression::AssertType { expression, .. } => self.expr_type(expression),
+            Expression::Ternary { true_expr, false_expr, .. } => {
+                [true_expr, false_expr].into_iter().filter_map(|branch| self.expr_type(branch)).reduce(common_type)
             }
+            Expression::EnumAccess { enum_name, .. } => Some(Type::Enum(enum_name.clone())),
+            Expression::Match { arms, .. } => self.match_arms_type(arms),
+            Expression::Loop { body } => self.loop_value_type(body, &mut HashMap::new()),
             Expression::InterpolatedString { .. } => {
                 // Interpolated strings always produce String type (char*)
                 Some(Type::String)
@@ -2208,7 +3939,7 @@ impl CCodeGenerator {
                     None
                 }
             }
-            Expression::MethodCall { object, method, .. } => {
+            Expression::MethodCall { object, method, arguments } => {
                 // Return type based on method
                 let mut obj_type = self.expr_type(object)?;
                 
@@ -2224,11 +3955,18 @@ impl CCodeGenerator {
                     (&Type::String, "length") => Some(Type::Int),
                     (&Type::String, "substring") => Some(Type::String),
                     (&Type::String, "contains") => Some(Type::Bool),
-                    (&Type::String, "trim") => Some(Type::String),
+                    (&Type::String, "trim" | "trim_start" | "trim_end" | "to_upper" | "to_lower") => Some(Type::String),
                     (&Type::String, "split") => Some(Type::DynamicArray(Box::new(Type::String))),
                     // Dynamic array methods
-                    (&Type::DynamicArray(_), "length") => Some(Type::Int),
-                    (&Type::DynamicArray(ref elem_ty), "pop") => Some(*elem_ty.clone()),
+                    (Type::Int | Type::Long | Type::Float | Type::Bool | Type::Char, "to_string") => Some(Type::String),
+                    (Type::DynamicArray(elem_ty), "to_string") if **elem_ty == Type::Char => Some(Type::String),
+                    (&Type::DynamicArray(_), "length" | "len") => Some(Type::Int),
+                    (Type::DynamicArray(elem_ty), "data") => Some(Type::Pointer(elem_ty.clone())),
+                    (Type::DynamicArray(elem_ty), "map" | "filter") => match (method.as_str(), arguments.first().and_then(|f| self.expr_type(f))) {
+                        ("map", Some(Type::Function(_, ret))) => Some(Type::DynamicArray(ret)),
+                        _ => Some(Type::DynamicArray(elem_ty.clone())),
+                    },
+                    (Type::DynamicArray(elem_ty), "pop") => Some(*elem_ty.clone()),
                     (&Type::DynamicArray(_), "push") => Some(Type::Void),
                     _ => None,
                 }
@@ -2236,4 +3974,733 @@ impl CCodeGenerator {
             Expression::Range { .. } => Some(Type::Void),
         }
     }
-}
\ No newline at end of file
+}
+
+// Return types of the built-in functions that codegen lowers itself
+fn builtin_function_type(name: &str) -> Option<Type> {
+    match name {
+        "len" => Some(Type::Int),
+        "hex" | "pad" => Some(Type::String),
+        "in_range" => Some(Type::Bool),
+        "read_lines" => Some(Type::DynamicArray(Box::new(Type::String))),
+        "exit" | "assert" => Some(Type::Void),
+        "env" => Some(Type::Generic { name: "Option".to_string(), type_params: vec![Type::String] }),
+        _ => None,
+    }
+}
+
+// The value of a string literal, or of a `+` chain made only of string literals
+fn folded_string_literal(expr: &Expression) -> Option<String> {
+    match expr {
+        Expression::Literal(Literal::String(s)) => Some(s.clone()),
+        Expression::Binary { left, operator: BinaryOp::Add, right } => {
+            Some(folded_string_literal(left)? + &folded_string_literal(right)?)
+        }
+        _ => None,
+    }
+}
+
+// Type holding values of both branch types: numbers widen as in arithmetic, `nil` takes
+// the other side's type, and otherwise the first known type wins
+fn common_type(first: Type, second: Type) -> Type {
+    match (first.integer_rank(), second.integer_rank()) {
+        (Some(l), Some(r)) => return if l >= r { first } else { second },
+        (Some(_), None) if second == Type::Float => return second,
+        _ => {}
+    }
+    if first == Type::Nil { second } else { first }
+}
+
+// The first and last value a range pattern matches, as C literals of the kind it was written
+// with; `None` for a range that matches nothing
+fn range_labels(pattern: &Pattern) -> Option<(String, String)> {
+    let Pattern::Range { start, .. } = pattern else { unreachable!("not a range pattern") };
+    let (low, high) = pattern.matched_values()?;
+    Some((value_label(low, start), value_label(high, start)))
+}
+
+// A value a pattern matches as a C literal of the same kind as `like`
+fn value_label(value: i64, like: &Literal) -> String {
+    match like {
+        Literal::Char(_) => char::from_u32(value as u32).map_or_else(|| value.to_string(), |c| case_label(&Literal::Char(c))),
+        _ => value.to_string(),
+    }
+}
+
+// Semantic analysis rejects empty ranges, but imported modules are not analyzed
+fn empty_range_error() -> CompilerError {
+    CompilerError::new(
+        ErrorKind::InvalidOperation,
+        "range pattern matches nothing".to_string(),
+        SourceLocation::new(PathBuf::from("input.rap"), 0, 0),
+    ).with_suggestion(Suggestion::simple(
+        "write the smaller end first; `1..10` stops before its end, `1..=10` includes it"
+    ))
+}
+
+// A switch `case` label for an integer or char pattern literal
+fn case_label(lit: &Literal) -> String {
+    match lit {
+        Literal::Integer(val) => val.to_string(),
+        Literal::Char(ch) => {
+            // Properly escape special chars in case labels
+            let esc: Option<&str> = match *ch {
+                '\\' => Some("\\\\"),
+                '\'' => Some("\\'"),
+                '\n' => Some("\\n"),
+                '\t' => Some("\\t"),
+                '\r' => Some("\\r"),
+                '\0' => Some("\\0"),
+                _ => None,
+            };
+            match esc {
+                Some(e) => format!("'{}'", e),
+                None => format!("'{}'", ch),
+            }
+        }
+        _ => "/* unsupported literal */".to_string(),
+    }
+}
+
+// Collect the locals that a block nested below their declaration moves out (`let k = a;` or
+// `k = a;`); `outer` holds the locals declared in the enclosing blocks
+fn conditional_moves(body: &[Statement], outer: &HashSet<String>, moves: &mut HashSet<String>) {
+    let mut own: HashSet<String> = HashSet::new();
+    for stmt in body {
+        let visible: HashSet<String> = outer.union(&own).cloned().collect();
+        match stmt {
+            Statement::Let { initializer: Some(Expression::Variable(name)), .. } | Statement::Assignment { value: Expression::Variable(name), .. }
+                if outer.contains(name) && !own.contains(name) =>
+            {
+                moves.insert(name.clone());
+            }
+            Statement::If { then_branch, else_branch, .. } => {
+                conditional_moves(then_branch, &visible, moves);
+                if let Some(else_branch) = else_branch {
+                    conditional_moves(else_branch, &visible, moves);
+                }
+            }
+            Statement::While { body, .. } | Statement::For { body, .. } | Statement::Expression(Expression::Loop { body }) => {
+                conditional_moves(body, &visible, moves);
+            }
+            Statement::Expression(Expression::Match { arms, .. }) => {
+                for arm in arms {
+                    if let ArmBody::Block(body) = &arm.body {
+                        conditional_moves(body, &visible, moves);
+                    }
+                }
+            }
+            _ => {}
+        }
+        if let Statement::Let { name, .. } = stmt {
+            own.insert(name.clone());
+        }
+    }
+}
+
+// Collect what a header must define for `ty`: the structs and enums it names, including those
+// named by the fields of such structs, and its dynamic array types, elements first
+fn header_types(ast: &Program, ty: &Type, names: &mut HashSet<String>, dynamic_arrays: &mut Vec<Type>) {
+    match ty {
+        Type::Struct(name) | Type::Enum(name) => {
+            if !names.insert(name.clone()) {
+                return;
+            }
+            if let Some(st) = ast.structs.iter().find(|s| &s.name == name) {
+                for field in &st.fields {
+                    header_types(ast, &field.field_type, names, dynamic_arrays);
+                }
+            }
+        }
+        Type::DynamicArray(inner) => {
+            header_types(ast, inner, names, dynamic_arrays);
+            if !dynamic_arrays.contains(ty) {
+                dynamic_arrays.push(ty.clone());
+            }
+        }
+        Type::Pointer(inner) | Type::Array(inner, _) => header_types(ast, inner, names, dynamic_arrays),
+        Type::Generic { type_params: types, .. } | Type::Tuple(types) => {
+            for inner in types {
+                header_types(ast, inner, names, dynamic_arrays);
+            }
+        }
+        Type::Function(params, ret) => {
+            for inner in params.iter().chain(std::iter::once(&**ret)) {
+                header_types(ast, inner, names, dynamic_arrays);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Whether `body` has a `break` that targets an enclosing loop (not one of its own loops)
+fn contains_loop_break(body: &[Statement]) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Statement::Break(_) => true,
+        Statement::If { then_branch, else_branch, .. } => {
+            contains_loop_break(then_branch) || else_branch.as_deref().is_some_and(contains_loop_break)
+        }
+        Statement::Expression(Expression::Match { arms, .. }) => arms.iter().any(|arm| {
+            matches!(&arm.body, ArmBody::Block(body) if contains_loop_break(body))
+        }),
+        _ => false,
+    })
+}
+
+fn binary_op_str(operator: &BinaryOp) -> &'static str {
+    match operator {
+        BinaryOp::Add => "+",
+        BinaryOp::Subtract => "-",
+        BinaryOp::Multiply => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Modulo => "%",
+        BinaryOp::Equal => "==",
+        BinaryOp::NotEqual => "!=",
+        BinaryOp::Less => "<",
+        BinaryOp::LessEqual => "<=",
+        BinaryOp::Greater => ">",
+        BinaryOp::GreaterEqual => ">=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+    }
+}
+
+// C precedence of binary operators; higher binds tighter
+fn binary_precedence(operator: &BinaryOp) -> u8 {
+    match operator {
+        BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => 6,
+        BinaryOp::Add | BinaryOp::Subtract => 5,
+        BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => 4,
+        BinaryOp::Equal | BinaryOp::NotEqual => 3,
+        BinaryOp::And => 2,
+        BinaryOp::Or => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser};
+
+    fn emit(source: &str) -> String {
+        emit_with_modules(source, ".")
+    }
+
+    fn emit_with_modules(source: &str, base_path: &str) -> String {
+        let path = PathBuf::from("test.rapt");
+        let tokens = lexer::tokenize(source, &path).unwrap();
+        let mut ast = parser::parse(tokens, path.clone()).unwrap();
+        let mut resolver = ModuleResolver::new(base_path);
+        let imported_symbols = resolver.resolve_imports(&ast).unwrap();
+        crate::semantic::infer_return_types(&mut ast, &imported_symbols, &path).unwrap();
+        crate::semantic::analyze_with_imports(&ast, &imported_symbols, &path).unwrap();
+        let mut generator = CCodeGenerator::new();
+        generator.generate(&ast, &mut resolver, &path).unwrap();
+        generator.get_output().to_string()
+    }
+
+    #[test]
+    fn test_inline_attribute() {
+        let c = emit("@inline\nfn square(x: int) -> int { return x * x; }\nfn main() -> int { return square(3); }");
+        assert!(c.contains("static inline int square(int x)"), "{}", c);
+        assert!(!c.contains("static inline int rapter_main"));
+    }
+
+    #[test]
+    fn test_negative_index() {
+        let c = emit("fn main() -> int { let mut a = new [int](); a.push(1); a.push(2); return a[-1]; }");
+        assert!(c.contains("(a).data[(a).size - 1]"), "{}", c);
+        let c = emit("fn main() -> int { let s = \"abc\"; let ch = s[-2]; return 0; }");
+        assert!(c.contains("s[strlen(s) - 2]"), "{}", c);
+    }
+
+    #[test]
+    fn test_print_without_arguments() {
+        let c = emit("fn main() { print(); println(); }");
+        assert!(!c.contains("printf()"), "{}", c);
+        assert!(c.contains("((void)0);"), "{}", c);
+        assert!(c.contains("printf(\"\\n\");"), "{}", c);
+    }
+
+    #[test]
+    fn test_opaque_extern_struct() {
+        let c = emit("extern struct FILE;\nextern fn fopen(path: string, mode: string) -> *FILE;\nextern fn fclose(f: *FILE) -> int;\nfn main() -> int { let f = fopen(\"data.txt\", \"r\"); return fclose(f); }");
+        assert!(!c.contains("typedef struct FILE"), "{}", c);
+        assert!(c.contains("FILE* f = fopen("), "{}", c);
+    }
+
+    #[test]
+    fn test_extern_header_include() {
+        let c = emit("extern \"math.h\" fn hypot(x: float, y: float) -> float;\nextern fn my_helper(x: int) -> int;\nfn main() -> int { let h = hypot(3.0, 4.0); return my_helper(1); }");
+        assert!(c.contains("#include <math.h>\n"), "{}", c);
+        assert!(!c.contains("double hypot("), "{}", c);
+        assert!(c.contains("int my_helper(int x);"), "{}", c);
+    }
+
+    #[test]
+    fn test_associated_function() {
+        let c = emit("struct Point { x: int, y: int }\nimpl Point {\n    fn new(x: int, y: int) -> Self { return Point { x: x, y: y }; }\n    fn sum(self) -> int { return self.x + self.y; }\n}\nfn main() -> int { let p = Point::new(1, 2); return p.sum(); }");
+        assert!(c.contains("Point Point_new(int, int);"), "{}", c);
+        assert!(c.contains("Point p = Point_new(1, 2);"), "{}", c);
+        assert!(c.contains("return Point_sum(p);"), "{}", c);
+    }
+
+    #[test]
+    fn test_enum_method() {
+        let c = emit("enum Color { Red, Green, Blue }\nimpl Color {\n    fn name(self) -> string {\n        return match self {\n            Color::Red => \"red\",\n            Color::Green => \"green\",\n            Color::Blue => \"blue\",\n        };\n    }\n}\nfn main() -> int {\n    let c = Color::Green;\n    println(c.name());\n    println(Color::Blue.name());\n    return 0;\n}");
+        assert!(c.contains("char* Color_name(Color self) {"), "{}", c);
+        assert!(c.contains("printf(\"%s\\n\", Color_name(c));"), "{}", c);
+        assert!(c.contains("printf(\"%s\\n\", Color_name(COLOR_BLUE));"), "{}", c);
+    }
+
+    #[test]
+    fn test_generic_defs_deduped_by_c_name() {
+        let c = emit("fn a() -> Option<str> { return Option::Some(\"a\"); }\nfn b() -> Option<string> { return Option::Some(\"b\"); }\nfn main() -> int { return 0; }");
+        assert_eq!(c.matches("} Option_string;").count(), 1, "{}", c);
+    }
+
+    #[test]
+    fn test_option_equality() {
+        let c = emit("fn main() -> int {\n    let a: Option<int> = Option::Some(1);\n    let b: Option<int> = Option::Some(1);\n    let n: Option<int> = Option::None;\n    if a == b && a != n { return 0; }\n    return 1;\n}");
+        assert!(c.contains("static inline int Option_int_eq(Option_int a, Option_int b)"), "{}", c);
+        assert!(c.contains("case Option_int_Some: return a.data.some_value == b.data.some_value;"), "{}", c);
+        assert!(c.contains("Option_int_eq(a, b)"), "{}", c);
+        assert!(c.contains("!Option_int_eq(a, n)"), "{}", c);
+    }
+
+    #[test]
+    fn test_minimal_parentheses() {
+        let c = emit("fn f(a: int, b: int, c: int) -> int { let x = a + b * c; let y = (a + b) * c; let z = a - (b - c); return x + y + z; }");
+        assert!(c.contains("int x = (a + b * c);"), "{}", c);
+        assert!(c.contains("int y = ((a + b) * c);"), "{}", c);
+        assert!(c.contains("int z = (a - (b - c));"), "{}", c);
+        assert!(c.contains("return (x + y + z);"), "{}", c);
+    }
+
+    #[test]
+    fn test_continue_after_match_in_loop() {
+        let c = emit("fn main() -> int {\n    let mut total = 0;\n    for i: 0..10 {\n        let skip = match i % 3 { 0 => true, _ => false };\n        if skip { continue; }\n        total = total + i;\n    }\n    return total;\n}");
+        let loop_start = c.find("for (int i = 0; i < 10; i++) {").expect(&c);
+        let match_end = loop_start + c[loop_start..].find("});").expect(&c);
+        let cont = loop_start + c[loop_start..].find("continue;").expect(&c);
+        // The match's switch is closed before the continue, which therefore belongs to the for-loop
+        assert!(match_end < cont, "{}", c);
+        assert!(c[cont..].contains("total = (total + i);"), "{}", c);
+    }
+    #[test]
+    fn test_print_pointer_uses_p_format() {
+        let c = emit("fn main() -> int {\n    let x = 5;\n    let p: *int = &x;\n    println(p);\n    print(&x);\n    return 0;\n}");
+        assert!(c.contains("printf(\"%p\\n\", (void*)(p))"), "{}", c);
+        assert!(c.contains("printf(\"%p\", (void*)(&x))"), "{}", c);
+    }
+    #[test]
+    fn test_slicing() {
+        let c = emit("fn main() -> int {\n    let s = \"hello\";\n    let t = s[1..3];\n    let mut a = new [int]();\n    a.push(1);\n    let b = a[0..1];\n    return b[0];\n}");
+        assert!(c.contains("char* t = rapter_substring(s, 1, 3);"), "{}", c);
+        assert!(c.contains("DynamicArray_int b = ({ DynamicArray_int __slice_src_"), "{}", c);
+        assert!(c.contains("memcpy(__slice_"), "{}", c);
+    }
+
+    #[test]
+    fn test_hex_builtin() {
+        let c = emit("fn main() -> int {\n    let h = hex(255);\n    println(h);\n    return 0;\n}");
+        assert!(c.contains("char* h = rapter_hex(255);"), "{}", c);
+        assert!(c.contains("snprintf(buf, 2 * sizeof(int) + 1, \"%x\", (unsigned int)n)"), "{}", c);
+        assert!(c.contains("printf(\"%s\\n\", h)"), "{}", c);
+    }
+
+    #[test]
+    fn test_array_literal_element_types() {
+        let c = emit("enum Color { Red, Green }\nfn main() -> int {\n    const HALF: float = 0.5;\n    const TWO: float = 2.0;\n    let colors = [Color::Red, Color::Green];\n    let scales = [HALF, TWO];\n    let ints = [1, 2];\n    return 0;\n}");
+        assert!(c.contains("int* colors = (int[]){COLOR_RED, COLOR_GREEN};"), "{}", c);
+        assert!(c.contains("double* scales = (double[]){HALF, TWO};"), "{}", c);
+        assert!(c.contains("int* ints = (int[]){1, 2};"), "{}", c);
+        let c = emit("fn main() -> int {\n    const HALF: float = 0.5;\n    let scales = [HALF, 1.0];\n    println(scales[0]);\n    return 0;\n}");
+        assert!(c.contains("printf(\"%f\\n\", scales[0])"), "{}", c);
+    }
+
+    #[test]
+    fn test_env_builtin() {
+        let c = emit("fn main() -> int {\n    let home = env(\"HOME\");\n    match home {\n        Option::Some(path) => println(path),\n        Option::None => println(\"unset\"),\n    }\n    return 0;\n}");
+        assert!(c.contains("} Option_string;"), "{}", c);
+        assert!(c.contains("Option_string home = ({ char* __env_0 = getenv(\"HOME\"); __env_0 ? (Option_string){ .tag = Option_string_Some"), "{}", c);
+        assert!(c.contains(": (Option_string){ .tag = Option_string_None }; })"), "{}", c);
+        // The bound value is known to be a string
+        assert!(c.contains("printf(\"%s\\n\", path)"), "{}", c);
+    }
+
+    #[test]
+    fn test_match_outer_binding() {
+        let c = emit("fn main() -> int {\n    let n = 7;\n    let doubled = match n {\n        0 => 0,\n        k @ _ => k * 2,\n    };\n    return doubled;\n}");
+        // Declared inside its own arm, so the other arms still see any outer `k`
+        let binding = c.find("int k = __match_temp_0;").expect(&c);
+        assert!(c.find("default:").unwrap() < binding, "{}", c);
+        assert_eq!(c.matches("int k = ").count(), 1, "{}", c);
+        assert!(c.contains("__match_result_1 = (k * 2);"), "{}", c);
+    }
+
+    #[test]
+    fn test_nested_variant_pattern() {
+        let c = emit("fn ok(n: int) -> Result<int, string> { return Result::Ok(n); }\nfn some(r: Result<int, string>) -> Option<Result<int, string>> { return Option::Some(r); }\nfn main() -> int {\n    return match some(ok(5)) {\n        Option::Some(Result::Ok(x)) => x,\n        _ => 0,\n    };\n}");
+        assert!(c.contains("if (__match_temp_0.tag == Option_Result_int_string_Some && __match_temp_0.data.some_value.tag == Result_int_string_Ok) {"), "{}", c);
+        assert!(c.contains("int x = __match_temp_0.data.some_value.data.ok_value;"), "{}", c);
+        assert!(!c.contains("switch (__match_temp_0"), "{}", c);
+        // The inner type is defined before the type that contains it
+        assert!(c.find("} Result_int_string;").unwrap() < c.find("} Option_Result_int_string;").unwrap(), "{}", c);
+    }
+
+    #[test]
+    fn test_match_without_wildcard_has_default() {
+        let c = emit("enum Color { Red, Green }\nfn main() -> int {\n    let c = Color::Green;\n    let n = match c {\n        Color::Red => 1,\n        Color::Green => 2,\n    };\n    match n {\n        1 => println(\"one\"),\n    }\n    return n;\n}");
+        // The value match always assigns its result or stops; the statement match just leaves
+        assert!(c.contains("default: abort();"), "{}", c);
+        assert!(c.contains("default: break;"), "{}", c);
+        let c = emit("fn main() -> int {\n    let n = 3;\n    let x = match n {\n        0 => 1,\n        _ => 2,\n    };\n    return x;\n}");
+        assert!(!c.contains("abort();") && c.contains("default: {"), "{}", c);
+    }
+
+    #[test]
+    fn test_method_reads_field_without_self() {
+        let c = emit("struct Counter { count: int, step: int }\nimpl Counter {\n    fn next(self) -> int { return count + step; }\n    fn shadowed(self, step: int) -> int { let count = 100; return count + step; }\n}\nfn main() -> int { let c = Counter { count: 3, step: 2 }; return c.next(); }");
+        assert!(c.contains("return (self.count + self.step);"), "{}", c);
+        // Parameters and locals come before fields
+        assert!(c.contains("return (count + step);"), "{}", c);
+        // A field as a method receiver
+        let c = emit("struct Bag { items: DynamicArray[int] }\nimpl Bag {\n    fn size(self) -> int { return items.length(); }\n}\nfn main() -> int { let b = Bag { items: new [int]() }; return b.size(); }");
+        assert!(c.contains("return ((int)self.items.size);"), "{}", c);
+    }
+
+    #[test]
+    fn test_assert_type_emits_only_its_expression() {
+        let c = emit("fn main() -> int { let x = assert_type(1.5, float); return 0; }");
+        assert!(c.contains("double x = 1.5;"), "{}", c);
+        assert!(!c.contains("assert_type"), "{}", c);
+    }
+
+    #[test]
+    fn test_field_of_call_result() {
+        let c = emit("struct Point { x: int, y: float }\nfn get_point() -> Point { return Point { x: 3, y: 1.5 }; }\nfn main() -> int {\n    let y = get_point().y;\n    println(get_point().y);\n    return get_point().x;\n}");
+        assert!(c.contains("double y = (get_point()).y;"), "{}", c);
+        assert!(c.contains("printf(\"%f\\n\", (get_point()).y);"), "{}", c);
+        assert!(c.contains("return (get_point()).x;"), "{}", c);
+    }
+
+    #[test]
+    fn test_long_arithmetic() {
+        let c = emit("fn main() -> int {\n    let big: long = 3000000000;\n    let n = 2;\n    let total = big * n;\n    println(n + big);\n    return 0;\n}");
+        assert!(c.contains("long long total = (big * n);"), "{}", c);
+        assert!(c.contains("printf(\"%lld\\n\", (n + big));"), "{}", c);
+    }
+
+    #[test]
+    fn test_drop_on_early_return() {
+        let c = emit("struct Guard { id: int }\nimpl Guard {\n    fn drop(self) { println(id); }\n}\nfn f(flag: bool) -> int {\n    let a = Guard { id: 1 };\n    if flag {\n        let b = Guard { id: 2 };\n        return a.id + b.id;\n    }\n    let kept = a;\n    return kept.id;\n}\nfn main() -> int { return f(true); }");
+        // The returned value is computed before the locals are dropped, innermost first
+        assert!(c.contains("int __return_0 = (a.id + b.id);\n        Guard_drop(b);\n        Guard_drop(a);\n        return __return_0;"), "{}", c);
+        // `a` moved into `kept`, so only `kept` is dropped at the end
+        assert!(c.contains("Guard_drop(kept);\n    return __return_1;"), "{}", c);
+        assert_eq!(c.matches("Guard_drop(a);").count(), 1, "{}", c);
+    }
+
+    #[test]
+    fn test_literal_string_length_is_folded() {
+        let c = emit("fn main() -> int {\n    let n = \"abc\".length();\n    let m = len(\"hello\" + \" world\");\n    let s = \"xy\";\n    return n + m + s.length() + len(s);\n}");
+        assert!(c.contains("int n = 3;"), "{}", c);
+        assert!(c.contains("int m = 11;"), "{}", c);
+        // Other operands are measured at run time
+        assert!(c.contains("strlen(s) + (int)strlen(s)"), "{}", c);
+    }
+
+    #[test]
+    fn test_packed_struct() {
+        let c = emit("@repr(C)\n@packed\nstruct Header { tag: char, size: int }\nfn main() -> int { let h = Header { tag: 'a', size: 4 }; return h.size; }");
+        assert!(c.contains("typedef struct __attribute__((packed)) Header {"), "{}", c);
+        let plain = emit("@repr(C)\nstruct Header { tag: char, size: int }\nfn main() -> int { return 0; }");
+        assert!(plain.contains("typedef struct Header {"), "{}", plain);
+    }
+
+    #[test]
+    fn test_let_match_type_from_any_arm() {
+        let c = emit("struct P { x: float }\nfn main() -> int {\n    let o: Option<P> = Option::None;\n    let v = match o { Option::Some(p) => p.x, Option::None => 0.0 };\n    return 0;\n}");
+        assert!(c.contains("double v = ({"), "{}", c);
+        assert!(c.contains("double __match_result_"), "{}", c);
+    }
+
+    #[test]
+    fn test_noreturn_attribute() {
+        let c = emit("@noreturn\nfn fail() { exit(1); }\nfn main() -> int { fail(); }");
+        assert!(c.contains("__attribute__((noreturn)) void fail() {"), "{}", c);
+    }
+
+    #[test]
+    fn test_chained_string_methods() {
+        let c = emit("fn main() -> int { let s = \" hi \"; let n = s.trim().to_upper().length(); let t = s.trim_start().to_lower(); return n; }");
+        assert!(c.contains("int n = strlen(rapter_to_upper(rapter_trim(s)));"), "{}", c);
+        assert!(c.contains("char* t = rapter_to_lower(rapter_trim_start(s));"), "{}", c);
+    }
+
+    #[test]
+    fn test_match_arm_block_returns_early() {
+        let c = emit("fn sign(n: int) -> int {\n    match n {\n        0 => { return 0; }\n        _ => { println(n); }\n    }\n    return 1;\n}\nfn main() -> int { return sign(2); }");
+        let case = c.find("case 0: {").expect(&c);
+        assert!(c[case..].trim_start_matches("case 0: {").trim_start().starts_with("return 0;"), "{}", c);
+        // A statement match yields no value
+        assert!(!c.contains("__match_result"), "{}", c);
+    }
+
+    #[test]
+    fn test_string_literal_concatenation_is_folded() {
+        let c = emit("fn main() -> int {\n    let s = \"foo\" + \"bar\";\n    println(s);\n    return 0;\n}");
+        assert!(c.contains("char* s = \"foobar\";"), "{}", c);
+        assert!(!c.contains("strcat("), "{}", c);
+        // A non-literal operand keeps the runtime path
+        let c = emit("fn main() -> int {\n    let a = \"x\";\n    let s = \"foo\" + \"bar\" + a;\n    println(s);\n    return 0;\n}");
+        assert!(c.contains("strcpy(result, \"foobar\")"), "{}", c);
+    }
+
+    #[test]
+    fn test_one_sided_trim() {
+        let c = emit("fn main() -> int {\n    let s = \"  lead\";\n    let t = \"trail  \";\n    let a = s.trim_start();\n    let b = t.trim_end();\n    println(a);\n    println(b);\n    return 0;\n}");
+        assert!(c.contains("char* a = rapter_trim_start(s);"), "{}", c);
+        assert!(c.contains("char* b = rapter_trim_end(t);"), "{}", c);
+        assert!(c.contains("char* rapter_trim_start(char* str)") && c.contains("char* rapter_trim_end(char* str)"), "{}", c);
+    }
+
+    #[test]
+    fn test_struct_field_defaults() {
+        let c = emit("struct Config { retries: int = 3, verbose: int }\nfn main() -> int {\n    let a = Config { verbose: 1 };\n    let b = Config { retries: 5, verbose: 0 };\n    return a.retries + b.retries;\n}");
+        assert!(c.contains("Config a = (Config){ .verbose = 1, .retries = 3 };"), "{}", c);
+        assert!(c.contains("Config b = (Config){ .retries = 5, .verbose = 0 };"), "{}", c);
+    }
+
+    #[test]
+    fn test_char_array_to_string() {
+        let c = emit("fn main() -> int {\n    let chars = new [char]();\n    chars.push('a');\n    let s = chars.to_string();\n    return len(s);\n}");
+        assert!(c.contains("char* s = ({ DynamicArray_char __chars_0 = chars; char* __str_0 = (char*)malloc(__chars_0.size + 1); memcpy(__str_0, __chars_0.data, __chars_0.size); __str_0[__chars_0.size] = 0; __str_0; });"), "{}", c);
+    }
+
+    #[test]
+    fn test_exit_builtin() {
+        let c = emit("fn check(n: int) -> int {\n    if n < 0 {\n        exit(2);\n    }\n    return n;\n}\nfn main() -> int { return check(1); }");
+        assert!(c.contains("        exit(2);\n"), "{}", c);
+    }
+
+    #[test]
+    fn test_string_char_vector_round_trip() {
+        let c = emit("fn main() -> int {\n    let s = \"hi\";\n    let chars = s as DynamicArray[char];\n    let back = chars as string;\n    return len(back);\n}");
+        assert!(c.contains("DynamicArray_char chars = ({ char* __chars_src_0 = s; DynamicArray_char __chars_0; __chars_0.size = strlen(__chars_src_0);"), "{}", c);
+        assert!(c.contains("memcpy(__chars_0.data, __chars_src_0, __chars_0.size); __chars_0; });"), "{}", c);
+        assert!(c.contains("char* back = ({ DynamicArray_char __chars_1 = chars; char* __str_1 = (char*)malloc(__chars_1.size + 1);"), "{}", c);
+        assert!(c.contains("__str_1[__chars_1.size] = 0; __str_1; });"), "{}", c);
+    }
+
+    #[test]
+    fn test_print_string_array() {
+        let c = emit("fn main() -> int {\n    let csv = \"a,b\";\n    let words = csv.split(\",\");\n    println(words);\n    return 0;\n}");
+        assert!(c.contains("printf(\"\\\"%s\\\"\", words.data[i]);"), "{}", c);
+    }
+
+    #[test]
+    fn test_const_fn_array_length() {
+        let c = emit("const fn square(n: int) -> int {\n    return n * n;\n}\nfn main() -> int {\n    let a: [int; square(3)];\n    a[0] = square(2);\n    return a[0];\n}");
+        assert!(c.contains("    int a[9];\n"), "{}", c);
+        // Runtime calls stay ordinary C calls
+        assert!(c.contains("a[0] = square(2);"), "{}", c);
+    }
+
+    #[test]
+    fn test_const_generic_function_specialized_per_length() {
+        let c = emit("fn sum<const N: int>(a: [int; N]) -> int {\n    let mut total = 0;\n    for i : 0..N {\n        total = total + a[i];\n    }\n    return total;\n}\nfn main() -> int {\n    let small = [1, 2, 3];\n    let big: [int; 5] = [1, 2, 3, 4, 5];\n    return sum(small) + sum(big) + sum([4, 5, 6]);\n}");
+        assert!(c.contains("int sum_3(int*);\nint sum_5(int*);\n"), "{}", c);
+        assert!(c.contains("return (sum_3(small) + sum_5(big) + sum_3((int[]){4, 5, 6}));"), "{}", c);
+        assert!(c.contains("int sum_3(int* a) {\n    int N = 3;\n"), "{}", c);
+        assert!(c.contains("int sum_5(int* a) {\n    int N = 5;\n"), "{}", c);
+        // Only the specializations exist in C
+        assert!(!c.contains("sum(int*"), "{}", c);
+    }
+
+    #[test]
+    fn test_function_pointer_parameter() {
+        let c = emit("fn descending(a: int, b: int) -> int {\n    return b - a;\n}\nfn first_of(a: int, b: int, cmp: fn(int, int) -> int) -> int {\n    if cmp(a, b) <= 0 {\n        return a;\n    }\n    return b;\n}\nfn main() -> int {\n    return first_of(3, 7, descending);\n}");
+        assert!(c.contains("typedef int (*fn_int_int_to_int)(int, int);"), "{}", c);
+        assert!(c.contains("int first_of(int a, int b, fn_int_int_to_int cmp) {"), "{}", c);
+        assert!(c.contains("if ((cmp(a, b) <= 0)) {"), "{}", c);
+        assert!(c.contains("return first_of(3, 7, descending);"), "{}", c);
+    }
+
+    #[test]
+    fn test_map_and_filter() {
+        let c = emit("fn square(n: int) -> int {\n    return n * n;\n}\nfn is_even(n: int) -> bool {\n    return n % 2 == 0;\n}\nfn main() -> int {\n    let nums = new [int]();\n    nums.push(3);\n    let squares = nums.map(square);\n    let evens = nums.filter(is_even);\n    return squares.length() + evens.length();\n}");
+        assert!(c.contains("DynamicArray_int squares = ({ DynamicArray_int __src_0 = nums; DynamicArray_int __map_0;"), "{}", c);
+        assert!(c.contains("__map_0.data[__map_0.size++] = square(__src_0.data[__i_0]);"), "{}", c);
+        assert!(c.contains("DynamicArray_int evens = ({ DynamicArray_int __src_1 = nums; DynamicArray_int __filter_1;"), "{}", c);
+        assert!(c.contains("if (is_even(__src_1.data[__i_1])) __filter_1.data[__filter_1.size++] = __src_1.data[__i_1];"), "{}", c);
+    }
+
+    #[test]
+    fn test_else_if_chain_is_flattened() {
+        let c = emit("fn sign(n: int) -> int {\n    if n < 0 {\n        return -1;\n    } else if n == 0 {\n        return 0;\n    } else {\n        return 1;\n    }\n}\nfn main() -> int { return sign(2); }");
+        assert!(c.contains("    if ((n < 0)) {\n        return -1;\n    } else if ((n == 0)) {\n        return 0;\n    } else {\n        return 1;\n    }\n"), "{}", c);
+    }
+
+    #[test]
+    fn test_module_init_runs_before_main() {
+        let dir = std::env::temp_dir().join(format!("rapter_init_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("config.rapt"),
+            "let mut answer: int = 0;\nfn init() {\n    answer = 42;\n}\nexport fn get_answer() -> int {\n    return answer;\n}\n",
+        ).unwrap();
+        let c = emit_with_modules(
+            "import config\nfn main() -> int {\n    return config.get_answer();\n}",
+            dir.to_str().unwrap(),
+        );
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(c.contains("static int answer = 0;"), "{}", c);
+        assert!(c.contains("void __config_init() {\n    answer = 42;\n}"), "{}", c);
+        let init = c.find("    __config_init();\n").expect(&c);
+        let main = c.find("return rapter_main(argc, argv);").expect(&c);
+        assert!(init < main, "{}", c);
+    }
+
+    #[test]
+    fn test_test_functions_only_in_test_builds() {
+        let source = "@test\nfn it_works() {\n    assert(1 + 1 == 2);\n}\nfn main() -> int { return 0; }";
+        let c = emit(source);
+        assert!(!c.contains("it_works"), "{}", c);
+        assert!(c.contains("return rapter_main(argc, argv);"), "{}", c);
+
+        let path = PathBuf::from("test.rapt");
+        let ast = parser::parse(lexer::tokenize(source, &path).unwrap(), path.clone()).unwrap();
+        let mut resolver = ModuleResolver::new(".");
+        let mut generator = CCodeGenerator::new().with_test_runner();
+        generator.generate(&ast, &mut resolver, &path).unwrap();
+        let c = generator.get_output();
+        assert!(c.contains("void it_works() {\n    rapter_assert((1 + 1 == 2), \"assertion failed\");\n}"), "{}", c);
+        assert!(c.contains("if (setjmp(env) == 0) { it_works(); printf(\"test it_works ... ok\\n\"); passed++; }"), "{}", c);
+        assert!(!c.contains("return rapter_main(argc, argv);"), "{}", c);
+    }
+
+    #[test]
+    fn test_match_arm_bindings_stay_in_their_arm() {
+        let c = emit("fn parse(n: int) -> Result<int, string> {\n    if n > 0 {\n        return Result::Ok(n);\n    }\n    return Result::Err(\"negative\");\n}\nfn main() -> int {\n    let r = parse(1);\n    let size = match r {\n        Result::Ok(v) => v,\n        Result::Err(v) => len(v),\n    };\n    return size;\n}");
+        assert!(c.contains("case Result_int_string_Ok: {\n                int v = __match_temp_0.data.ok_value;"), "{}", c);
+        assert!(c.contains("case Result_int_string_Err: {\n                char* v = __match_temp_0.data.err_value;"), "{}", c);
+
+        let c = emit("fn main() -> int {\n    let n = 2;\n    match n {\n        1 => {\n            let w = 1;\n        }\n        _ => {\n            let w = \"two\";\n        }\n    }\n    let s = \"b\";\n    match s {\n        \"a\" => {\n            let w = 1;\n        }\n        _ => {\n            let w = \"other\";\n        }\n    }\n    return 0;\n}");
+        assert!(c.contains("case 1: {\n                int w = 1;\n                break;\n            }\n            default: {\n                char* w = \"two\";\n                break;\n            }"), "{}", c);
+        assert!(c.contains("if (strcmp(__match_temp_1, \"a\") == 0) {\n            int w = 1;\n        } else {\n            char* w = \"other\";\n        }\n"), "{}", c);
+    }
+
+    #[test]
+    fn test_inferred_return_type() {
+        let c = emit("fn greeting() {\n    return \"hello\";\n}\nfn main() -> int {\n    println(greeting());\n    return 0;\n}");
+        assert!(c.contains("char* greeting() {"), "{}", c);
+        assert!(c.contains("printf(\"%s\\n\", greeting());"), "{}", c);
+    }
+
+    #[test]
+    fn test_size_based_loops_use_size_t() {
+        let c = emit("fn main() -> int {\n    let nums = new [int]();\n    nums.push(4);\n    println(nums);\n    for i: 0..nums.length() {\n        println(nums[i]);\n    }\n    let s = \"abc\";\n    return len(s);\n}");
+        assert!(c.contains("for (size_t i = 0; i < nums.size; i++) {"), "{}", c);
+        // Sizes become Rapter ints before they meet signed bounds
+        assert!(c.contains("for (int i = 0; i < ((int)nums.size); i++) {"), "{}", c);
+        assert!(c.contains("return (int)strlen(s);"), "{}", c);
+    }
+
+    #[test]
+    fn test_loop_breaks_with_found_element() {
+        let c = emit("fn main() -> int {\n    let values = [3, 8, 15];\n    let mut i = 0;\n    let found = loop {\n        if values[i] > 5 {\n            break values[i];\n        }\n        i = i + 1;\n    };\n    return found;\n}");
+        assert!(c.contains("int found = ({\n        int __loop_result_0;\n        while (1) {\n            if ((values[i] > 5)) {\n                __loop_result_0 = values[i];\n                break;\n            }"), "{}", c);
+        assert!(c.contains("        __loop_result_0;\n    });"), "{}", c);
+    }
+
+    #[test]
+    fn test_char_range_pattern() {
+        let c = emit("fn is_lower(c: char) -> bool {\n    return match c {\n        'a'..='z' => true,\n        _ => false,\n    };\n}\nfn main() -> int {\n    return 0;\n}");
+        assert!(c.contains("case 'a' ... 'z': {\n                __match_result_1 = 1;\n                break;\n            }"), "{}", c);
+    }
+
+    #[test]
+    fn test_integer_range_patterns() {
+        let c = emit("fn size(n: int) -> string {\n    return match n {\n        0..10 => \"small\",\n        10..100 => \"medium\",\n        _ => \"large\",\n    };\n}\nfn main() -> int {\n    return 0;\n}");
+        assert!(c.contains("case 0 ... 9: {"), "{}", c);
+        assert!(c.contains("case 10 ... 99: {"), "{}", c);
+        let c = emit("fn size(n: int) -> string {\n    return match n {\n        0..=9 => \"small\",\n        _ => \"large\",\n    };\n}\nfn main() -> int {\n    return 0;\n}");
+        assert!(c.contains("case 0 ... 9: {"), "{}", c);
+        assert!(c.contains("default: {\n                __match_result_1 = \"large\";"), "{}", c);
+        // A value an earlier arm matches gets no second label
+        let c = emit("fn size(n: int) -> string {\n    return match n {\n        5 => \"five\",\n        0..10 => \"small\",\n        8..=12 | 20 => \"teen\",\n        _ => \"large\",\n    };\n}\nfn main() -> int {\n    return 0;\n}");
+        assert!(c.contains("case 5: {"), "{}", c);
+        assert!(c.contains("case 0 ... 4:\n            case 6 ... 9: {"), "{}", c);
+        assert!(c.contains("case 10 ... 12:\n            case 20: {"), "{}", c);
+    }
+
+    #[test]
+    fn test_empty_range_in_module_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("rapter_empty_range_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("sizes.rapt"), "export fn size(n: int) -> int {\n    return match n {\n        5..5 => 1,\n        _ => 0,\n    };\n}\n").unwrap();
+        let path = PathBuf::from("test.rapt");
+        let source = "import sizes\nfn main() -> int {\n    return sizes.size(5);\n}";
+        let ast = parser::parse(lexer::tokenize(source, &path).unwrap(), path.clone()).unwrap();
+        let mut resolver = ModuleResolver::new(dir.to_str().unwrap());
+        let result = CCodeGenerator::new().generate(&ast, &mut resolver, &path);
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(result.unwrap_err().message, "range pattern matches nothing");
+    }
+
+    #[test]
+    fn test_or_pattern_shares_an_arm() {
+        let c = emit("enum Color { Red, Green, Blue }\nfn warm(c: Color) -> bool {\n    return match c {\n        Color::Red | Color::Green => true,\n        Color::Blue => false,\n    };\n}\nfn main() -> int {\n    return 0;\n}");
+        assert!(c.contains("case COLOR_RED:\n            case COLOR_GREEN: {\n                __match_result_1 = 1;\n                break;\n            }"), "{}", c);
+    }
+
+    #[test]
+    fn test_header_declares_exports_only() {
+        let path = PathBuf::from("lib.rapt");
+        let source = "export struct Point {\n    x: int,\n    y: int\n}\nstruct Hidden {\n    v: int\n}\nexport fn add(a: int, b: int) -> int {\n    return a + b;\n}\nfn helper() -> int {\n    return 1;\n}";
+        let ast = parser::parse(lexer::tokenize(source, &path).unwrap(), path.clone()).unwrap();
+        let mut generator = CCodeGenerator::new();
+        generator.generate_header(&ast, "LIB_H").unwrap();
+        let h = generator.get_output();
+        assert!(h.starts_with("#ifndef LIB_H\n#define LIB_H\n"), "{}", h);
+        assert!(h.contains("typedef struct Point {"), "{}", h);
+        assert!(h.contains("int add(int a, int b);"), "{}", h);
+        assert!(!h.contains("Hidden") && !h.contains("helper"), "{}", h);
+        assert!(h.ends_with("#endif // LIB_H\n"), "{}", h);
+    }
+
+    #[test]
+    fn test_enum_int_casts() {
+        let c = emit("enum Color { Red, Green }\nfn main() -> int {\n    let n = Color::Green as int;\n    let c = n as Color;\n    return n;\n}");
+        assert!(c.contains("int n = (int)COLOR_GREEN;"), "{}", c);
+        assert!(c.contains("Color c = (Color)n;"), "{}", c);
+    }
+
+    #[test]
+    fn test_raw_identifiers() {
+        let c = emit("extern fn r#new(size: int) -> int;\nfn twice(r#new: int) -> int {\n    let r#match = r#new * 2;\n    return r#match;\n}\nfn main() -> int {\n    return twice(r#new(4));\n}");
+        assert!(c.contains("int new(int size);"), "{}", c);
+        assert!(c.contains("int twice(int new) {\n    int match = (new * 2);"), "{}", c);
+        assert!(c.contains("return twice(new(4));"), "{}", c);
+        // A C keyword stays unusable, raw or not
+        assert!(lexer::tokenize("let r#int = 1;", &PathBuf::from("test.rapt")).is_err());
+    }
+
+    #[test]
+    fn test_pad_builtin() {
+        let c = emit("fn main() -> int {\n    let row = pad(\"id\", -6);\n    println(row);\n    return 0;\n}");
+        assert!(c.contains("char* row = rapter_pad(\"id\", -6);"), "{}", c);
+        assert!(c.contains("char* rapter_pad(char* str, int width) {"), "{}", c);
+    }
+
+    #[test]
+    fn test_tuple_return_destructured() {
+        let c = emit("fn divmod(a: int, b: int) -> (int, int) {\n    return (a / b, a % b);\n}\nfn main() -> int {\n    let (q, r) = divmod(17, 5);\n    return q * 10 + r;\n}");
+        assert!(c.contains("typedef struct { int _0; int _1; } Tuple_int_int;\n"), "{}", c);
+        assert!(c.contains("Tuple_int_int divmod(int, int);"), "{}", c);
+        assert!(c.contains("return ((Tuple_int_int){ (a / b), (a % b) });"), "{}", c);
+        assert!(c.contains("    Tuple_int_int __tuple_0 = divmod(17, 5);\n    int q = __tuple_0._0;\n    int r = __tuple_0._1;\n"), "{}", c);
+    }
+}
@@ -0,0 +1,247 @@
+// Compile-time evaluation of `const fn` calls, used for array lengths such as `[int; square(3)]`
+// Only integer (and bool/char, as integers) arithmetic and simple control flow is supported
+
+use crate::ast::*;
+use std::collections::HashMap;
+
+// Guards against const fns that loop or recurse forever
+const MAX_STEPS: usize = 1_000_000;
+const MAX_DEPTH: usize = 256;
+
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(i64),
+}
+
+pub struct ConstEvaluator<'a> {
+    functions: &'a HashMap<String, Function>,
+    steps: usize,
+    depth: usize,
+}
+
+impl<'a> ConstEvaluator<'a> {
+    /// `functions` holds the `const fn`s that may be called, by name
+    pub fn new(functions: &'a HashMap<String, Function>) -> Self {
+        ConstEvaluator { functions, steps: 0, depth: 0 }
+    }
+
+    pub fn call(&mut self, name: &str, arguments: &[i64]) -> Result<i64, String> {
+        let func = self.functions.get(name)
+            .ok_or_else(|| format!("`{}` is not a const fn", name))?;
+        if func.parameters.len() != arguments.len() {
+            return Err(format!("`{}` expects {} argument(s), got {}", name, func.parameters.len(), arguments.len()));
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err(format!("recursion in `{}` is too deep to evaluate at compile time", name));
+        }
+        let mut locals: HashMap<String, i64> = func.parameters.iter()
+            .map(|p| p.name.clone())
+            .zip(arguments.iter().copied())
+            .collect();
+        self.depth += 1;
+        let flow = self.block(&func.body, &mut locals);
+        self.depth -= 1;
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Err(format!("`{}` finished without returning a value", name)),
+        }
+    }
+
+    /// Value of an expression that uses no variables, such as `1 == 1` or `square(2) > 3`
+    pub fn evaluate(&mut self, expr: &Expression) -> Result<i64, String> {
+        self.expression(expr, &HashMap::new())
+    }
+
+    fn block(&mut self, body: &[Statement], locals: &mut HashMap<String, i64>) -> Result<Flow, String> {
+        for stmt in body {
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                return Err("evaluation takes too many steps; does a loop never end?".to_string());
+            }
+            match stmt {
+                Statement::Let { name, initializer: Some(init), .. } | Statement::Const { name, initializer: init, .. } => {
+                    let value = self.expression(init, locals)?;
+                    locals.insert(name.clone(), value);
+                }
+                Statement::Assignment { target: Expression::Variable(name), value } => {
+                    let value = self.expression(value, locals)?;
+                    locals.insert(name.clone(), value);
+                }
+                Statement::Return(Some(value)) => return Ok(Flow::Return(self.expression(value, locals)?)),
+                Statement::If { condition, then_branch, else_branch } => {
+                    let flow = if self.expression(condition, locals)? != 0 {
+                        self.block(then_branch, locals)?
+                    } else if let Some(else_branch) = else_branch {
+                        self.block(else_branch, locals)?
+                    } else {
+                        Flow::Normal
+                    };
+                    if !matches!(flow, Flow::Normal) {
+                        return Ok(flow);
+                    }
+                }
+                Statement::While { condition, body } => {
+                    while self.expression(condition, locals)? != 0 {
+                        match self.block(body, locals)? {
+                            Flow::Break => break,
+                            Flow::Return(value) => return Ok(Flow::Return(value)),
+                            Flow::Normal | Flow::Continue => {}
+                        }
+                    }
+                }
+                Statement::For { variable, iterable: Expression::Range { start, end }, body } => {
+                    let (start, end) = (self.expression(start, locals)?, self.expression(end, locals)?);
+                    for i in start..end {
+                        locals.insert(variable.clone(), i);
+                        match self.block(body, locals)? {
+                            Flow::Break => break,
+                            Flow::Return(value) => return Ok(Flow::Return(value)),
+                            Flow::Normal | Flow::Continue => {}
+                        }
+                    }
+                }
+                Statement::Break(None) => return Ok(Flow::Break),
+                Statement::Continue => return Ok(Flow::Continue),
+                _ => return Err(unsupported_statement(stmt).to_string()),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn expression(&mut self, expr: &Expression, locals: &HashMap<String, i64>) -> Result<i64, String> {
+        match expr {
+            Expression::Literal(Literal::Integer(n)) => Ok(*n),
+            Expression::Literal(Literal::Bool(b)) => Ok(*b as i64),
+            Expression::Literal(Literal::Char(c)) => Ok(*c as i64),
+            Expression::Variable(name) => locals.get(name).copied()
+                .ok_or_else(|| format!("`{}` is not known at compile time", name)),
+            Expression::Unary { operator: UnaryOp::Negate, operand } => {
+                self.expression(operand, locals)?.checked_neg().ok_or_else(overflow)
+            }
+            Expression::Unary { operator: UnaryOp::Not, operand } => Ok((self.expression(operand, locals)? == 0) as i64),
+            Expression::Binary { left, operator: BinaryOp::And, right } => {
+                Ok((self.expression(left, locals)? != 0 && self.expression(right, locals)? != 0) as i64)
+            }
+            Expression::Binary { left, operator: BinaryOp::Or, right } => {
+                Ok((self.expression(left, locals)? != 0 || self.expression(right, locals)? != 0) as i64)
+            }
+            Expression::Binary { left, operator, right } => {
+                let (l, r) = (self.expression(left, locals)?, self.expression(right, locals)?);
+                match operator {
+                    BinaryOp::Add => l.checked_add(r).ok_or_else(overflow),
+                    BinaryOp::Subtract => l.checked_sub(r).ok_or_else(overflow),
+                    BinaryOp::Multiply => l.checked_mul(r).ok_or_else(overflow),
+                    BinaryOp::Divide | BinaryOp::Modulo if r == 0 => Err("division by zero".to_string()),
+                    BinaryOp::Divide => l.checked_div(r).ok_or_else(overflow),
+                    BinaryOp::Modulo => l.checked_rem(r).ok_or_else(overflow),
+                    BinaryOp::Equal => Ok((l == r) as i64),
+                    BinaryOp::NotEqual => Ok((l != r) as i64),
+                    BinaryOp::Less => Ok((l < r) as i64),
+                    BinaryOp::LessEqual => Ok((l <= r) as i64),
+                    BinaryOp::Greater => Ok((l > r) as i64),
+                    BinaryOp::GreaterEqual => Ok((l >= r) as i64),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            }
+            Expression::Ternary { condition, true_expr, false_expr } => {
+                if self.expression(condition, locals)? != 0 {
+                    self.expression(true_expr, locals)
+                } else {
+                    self.expression(false_expr, locals)
+                }
+            }
+            Expression::Call { callee, arguments } => {
+                let Expression::Variable(name) = &**callee else {
+                    return Err("only calls to const fns can be evaluated at compile time".to_string());
+                };
+                let values = arguments.iter()
+                    .map(|arg| self.expression(arg, locals))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(name, &values)
+            }
+            _ => Err(unsupported_expression(expr).to_string()),
+        }
+    }
+}
+
+fn overflow() -> String {
+    "integer overflow".to_string()
+}
+
+/// Check that a `const fn` body only uses what the evaluator supports; `is_const_fn` tells
+/// which callees are themselves `const fn`s
+pub fn check_const_body(body: &[Statement], is_const_fn: &dyn Fn(&str) -> bool) -> Result<(), String> {
+    for stmt in body {
+        match stmt {
+            Statement::Let { initializer: Some(init), .. } | Statement::Const { initializer: init, .. } => {
+                check_const_expression(init, is_const_fn)?;
+            }
+            Statement::Assignment { target: Expression::Variable(_), value } => check_const_expression(value, is_const_fn)?,
+            Statement::Return(Some(value)) => check_const_expression(value, is_const_fn)?,
+            Statement::If { condition, then_branch, else_branch } => {
+                check_const_expression(condition, is_const_fn)?;
+                check_const_body(then_branch, is_const_fn)?;
+                if let Some(else_branch) = else_branch {
+                    check_const_body(else_branch, is_const_fn)?;
+                }
+            }
+            Statement::While { condition, body } => {
+                check_const_expression(condition, is_const_fn)?;
+                check_const_body(body, is_const_fn)?;
+            }
+            Statement::For { iterable: Expression::Range { start, end }, body, .. } => {
+                check_const_expression(start, is_const_fn)?;
+                check_const_expression(end, is_const_fn)?;
+                check_const_body(body, is_const_fn)?;
+            }
+            Statement::Break(None) | Statement::Continue => {}
+            _ => return Err(unsupported_statement(stmt).to_string()),
+        }
+    }
+    Ok(())
+}
+
+fn check_const_expression(expr: &Expression, is_const_fn: &dyn Fn(&str) -> bool) -> Result<(), String> {
+    match expr {
+        Expression::Literal(Literal::Integer(_) | Literal::Bool(_) | Literal::Char(_)) | Expression::Variable(_) => Ok(()),
+        Expression::Unary { operator: UnaryOp::Negate | UnaryOp::Not, operand } => check_const_expression(operand, is_const_fn),
+        Expression::Binary { left, right, .. } => {
+            check_const_expression(left, is_const_fn)?;
+            check_const_expression(right, is_const_fn)
+        }
+        Expression::Ternary { condition, true_expr, false_expr } => {
+            check_const_expression(condition, is_const_fn)?;
+            check_const_expression(true_expr, is_const_fn)?;
+            check_const_expression(false_expr, is_const_fn)
+        }
+        Expression::Call { callee, arguments } => match &**callee {
+            Expression::Variable(name) if is_const_fn(name) => {
+                arguments.iter().try_for_each(|arg| check_const_expression(arg, is_const_fn))
+            }
+            Expression::Variable(name) => Err(format!("calls `{}`, which is not a const fn", name)),
+            _ => Err("only calls to const fns are allowed".to_string()),
+        },
+        _ => Err(unsupported_expression(expr).to_string()),
+    }
+}
+
+fn unsupported_statement(stmt: &Statement) -> &'static str {
+    match stmt {
+        Statement::Let { initializer: None, .. } => "variables must be initialized",
+        Statement::Assignment { .. } => "only local variables can be assigned",
+        Statement::Return(None) => "must return a value",
+        Statement::For { .. } => "for loops must iterate over a range",
+        Statement::Break(Some(_)) => "loops cannot produce values at compile time",
+        _ => "expression statements have no effect at compile time",
+    }
+}
+
+fn unsupported_expression(expr: &Expression) -> &'static str {
+    match expr {
+        Expression::Literal(_) => "only integer, bool and char literals are supported",
+        Expression::Unary { .. } => "pointers are not available at compile time",
+        _ => "only arithmetic, comparisons and const fn calls are supported",
+    }
+}
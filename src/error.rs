@@ -41,6 +41,14 @@ pub enum ErrorKind {
     // Code generation errors
     UnsupportedFeature,
     InternalError,
+
+    // Warnings
+    UnreachableCode,
+    UnboundedRecursion,
+    PointerReinterpretCast,
+    ConstantCondition,
+    ContinueSkipsUpdate,
+    DeprecatedFunction,
 }
 
 impl ErrorKind {
@@ -73,6 +81,12 @@ impl ErrorKind {
             ErrorKind::ImportConflict => "E306",
             ErrorKind::UnsupportedFeature => "E401",
             ErrorKind::InternalError => "E500",
+            ErrorKind::UnreachableCode => "W001",
+            ErrorKind::UnboundedRecursion => "W002",
+            ErrorKind::PointerReinterpretCast => "W003",
+            ErrorKind::ConstantCondition => "W004",
+            ErrorKind::ContinueSkipsUpdate => "W005",
+            ErrorKind::DeprecatedFunction => "W006",
         }
     }
 
@@ -105,6 +119,12 @@ impl ErrorKind {
             ErrorKind::ImportConflict => "import conflict",
             ErrorKind::UnsupportedFeature => "unsupported feature",
             ErrorKind::InternalError => "internal compiler error",
+            ErrorKind::UnreachableCode => "unreachable code",
+            ErrorKind::UnboundedRecursion => "unbounded recursion",
+            ErrorKind::PointerReinterpretCast => "pointer reinterpret cast",
+            ErrorKind::ConstantCondition => "constant condition",
+            ErrorKind::ContinueSkipsUpdate => "continue skips the loop update",
+            ErrorKind::DeprecatedFunction => "use of deprecated function",
         }
     }
 }
@@ -170,9 +190,16 @@ impl Suggestion {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
 #[derive(Debug, Clone)]
 pub struct CompilerError {
     pub kind: ErrorKind,
+    pub severity: Severity,
     pub message: String,
     pub location: SourceLocation,
     pub context: Option<String>, // Additional context about the error
@@ -184,6 +211,7 @@ impl CompilerError {
     pub fn new(kind: ErrorKind, message: String, location: SourceLocation) -> Self {
         CompilerError {
             kind,
+            severity: Severity::Error,
             message,
             location,
             context: None,
@@ -192,6 +220,23 @@ impl CompilerError {
         }
     }
 
+    pub fn warning(kind: ErrorKind, message: String, location: SourceLocation) -> Self {
+        CompilerError {
+            severity: Severity::Warning,
+            ..CompilerError::new(kind, message, location)
+        }
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.severity == Severity::Warning
+    }
+
+    // Turn a warning into a hard error (`-W error`)
+    pub fn promoted(mut self) -> Self {
+        self.severity = Severity::Error;
+        self
+    }
+
     pub fn with_context(mut self, context: impl Into<String>) -> Self {
         self.context = Some(context.into());
         self
@@ -217,8 +262,12 @@ impl Error for CompilerError {}
 
 impl fmt::Display for CompilerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // Error header with code and title
-        writeln!(f, "\x1b[1;31merror[{}]\x1b[0m: {}", self.kind.code(), self.kind.title())?;
+        // Error header with code and title; warnings are yellow, errors red
+        let (label, color) = match self.severity {
+            Severity::Error => ("error", "1;31"),
+            Severity::Warning => ("warning", "1;33"),
+        };
+        writeln!(f, "\x1b[{}m{}[{}]\x1b[0m: {}", color, label, self.kind.code(), self.kind.title())?;
         writeln!(f, "  \x1b[1m{}\x1b[0m", self.message)?;
         writeln!(f, "  \x1b[36m-->\x1b[0m {}", self.location.span())?;
 
@@ -238,13 +287,13 @@ impl fmt::Display for CompilerError {
                 let caret_len = self.location.length.unwrap_or(1);
                 let spaces = " ".repeat(caret_position);
                 let carets = "^".repeat(caret_len);
-                writeln!(f, "  \x1b[36m{} |\x1b[0m     \x1b[1;31m{}{}\x1b[0m", " ".repeat(line_number.to_string().len()), spaces, carets)?;
+                writeln!(f, "  \x1b[36m{} |\x1b[0m     \x1b[{}m{}{}\x1b[0m", " ".repeat(line_number.to_string().len()), color, spaces, carets)?;
             }
         } else {
             // Fallback if we can't read the file
             writeln!(f, "  \x1b[36m|\x1b[0m")?;
             let caret_len = self.location.length.unwrap_or(1);
-            writeln!(f, "  \x1b[36m|\x1b[0m     \x1b[1;31m{}\x1b[0m", "^".repeat(caret_len))?;
+            writeln!(f, "  \x1b[36m|\x1b[0m     \x1b[{}m{}\x1b[0m", color, "^".repeat(caret_len))?;
         }
 
         // Context if available
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::fmt;
-use std::path::PathBuf;
+use std::path::Path;
 use crate::error::{CompilerError, ErrorKind, SourceLocation, Suggestion};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -13,6 +13,7 @@ pub enum TokenKind {
     If,
     Else,
     While,
+    Loop,
     For,
     Return,
     Break,
@@ -30,6 +31,7 @@ pub enum TokenKind {
     As,
     Export,
     Extern,
+    Impl,
     
     // Types
     Int,
@@ -44,6 +46,7 @@ pub enum TokenKind {
     StringLiteral(String),
     BoolLiteral(bool),
     CharLiteral(char),
+    Nil,
     
     // Identifiers
     Identifier(String),
@@ -80,10 +83,12 @@ pub enum TokenKind {
     Comma,
     Dot,
     DotDot, // ..
+    DotDotEq, // ..=
     DotDotDot, // ...
     Arrow, // ->
     FatArrow, // =>
     Question, // ?
+    At, // @ (attribute sigil)
     
     // Comments
     Comment(String),
@@ -102,6 +107,7 @@ impl fmt::Display for TokenKind {
             TokenKind::If => write!(f, "if"),
             TokenKind::Else => write!(f, "else"),
             TokenKind::While => write!(f, "while"),
+            TokenKind::Loop => write!(f, "loop"),
             TokenKind::For => write!(f, "for"),
             TokenKind::Return => write!(f, "return"),
             TokenKind::Break => write!(f, "break"),
@@ -119,6 +125,7 @@ impl fmt::Display for TokenKind {
             TokenKind::As => write!(f, "as"),
             TokenKind::Export => write!(f, "export"),
             TokenKind::Extern => write!(f, "extern"),
+            TokenKind::Impl => write!(f, "impl"),
             TokenKind::Int => write!(f, "int"),
             TokenKind::Float => write!(f, "float"),
             TokenKind::Bool => write!(f, "bool"),
@@ -129,6 +136,7 @@ impl fmt::Display for TokenKind {
             TokenKind::StringLiteral(_) => write!(f, "string literal"),
             TokenKind::BoolLiteral(_) => write!(f, "bool literal"),
             TokenKind::CharLiteral(_) => write!(f, "char literal"),
+            TokenKind::Nil => write!(f, "nil"),
             TokenKind::Identifier(_) => write!(f, "identifier"),
             TokenKind::Plus => write!(f, "+"),
             TokenKind::Minus => write!(f, "-"),
@@ -159,10 +167,12 @@ impl fmt::Display for TokenKind {
             TokenKind::Comma => write!(f, ","),
             TokenKind::Dot => write!(f, "."),
             TokenKind::DotDot => write!(f, ".."),
+            TokenKind::DotDotEq => write!(f, "..="),
             TokenKind::DotDotDot => write!(f, "..."),
             TokenKind::Arrow => write!(f, "->"),
             TokenKind::FatArrow => write!(f, "=>"),
             TokenKind::Question => write!(f, "?"),
+            TokenKind::At => write!(f, "@"),
             TokenKind::Comment(_) => write!(f, "comment"),
             TokenKind::Eof => write!(f, "end of file"),
         }
@@ -176,7 +186,15 @@ pub struct Token {
     pub column: usize,
 }
 
-pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, CompilerError> {
+// Names a raw identifier cannot take: they would still be keywords in the generated C
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while",
+];
+
+pub fn tokenize(source: &str, file_path: &Path) -> Result<Vec<Token>, CompilerError> {
     let mut tokens = Vec::new();
     let mut chars = source.chars().peekable();
     let mut line = 1;
@@ -190,6 +208,7 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
         ("if", TokenKind::If),
         ("else", TokenKind::Else),
         ("while", TokenKind::While),
+        ("loop", TokenKind::Loop),
         ("for", TokenKind::For),
         ("return", TokenKind::Return),
         ("break", TokenKind::Break),
@@ -207,6 +226,7 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
         ("as", TokenKind::As),
         ("export", TokenKind::Export),
         ("extern", TokenKind::Extern),
+        ("impl", TokenKind::Impl),
         ("int", TokenKind::Int),
         ("float", TokenKind::Float),
         ("bool", TokenKind::Bool),
@@ -214,6 +234,7 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
         ("string", TokenKind::String),
         ("true", TokenKind::BoolLiteral(true)),
         ("false", TokenKind::BoolLiteral(false)),
+        ("nil", TokenKind::Nil),
     ]);
     
     while let Some(&ch) = chars.peek() {
@@ -423,6 +444,11 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
                 tokens.push(Token { kind: TokenKind::Question, line, column });
                 column += 1;
             }
+            '@' => {
+                chars.next();
+                tokens.push(Token { kind: TokenKind::At, line, column });
+                column += 1;
+            }
             ',' => {
                 chars.next();
                 tokens.push(Token { kind: TokenKind::Comma, line, column });
@@ -439,6 +465,11 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
                         chars.next();
                         tokens.push(Token { kind: TokenKind::DotDotDot, line, column: column - 2 });
                         column += 1;
+                    } else if let Some(&'=') = chars.peek() {
+                        // Inclusive range: ..=
+                        chars.next();
+                        tokens.push(Token { kind: TokenKind::DotDotEq, line, column: column - 1 });
+                        column += 1;
                     } else {
                         // Two dots: ..
                         tokens.push(Token { kind: TokenKind::DotDot, line, column: column - 1 });
@@ -566,7 +597,7 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
                     if let Some(val) = ch_value {
                         tokens.push(Token { kind: TokenKind::CharLiteral(val), line, column: column - 2 });
                     } else {
-                        let location = SourceLocation::new(file_path.clone(), line, column);
+                        let location = SourceLocation::new(file_path.to_path_buf(), line, column);
                         return Err(CompilerError::new(
                             ErrorKind::UnterminatedString,
                             "unterminated char literal".to_string(),
@@ -576,7 +607,7 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
                         )));
                     }
                 } else {
-                    let location = SourceLocation::new(file_path.clone(), line, column);
+                    let location = SourceLocation::new(file_path.to_path_buf(), line, column);
                     return Err(CompilerError::new(
                         ErrorKind::UnterminatedString,
                         "unterminated char literal".to_string(),
@@ -620,7 +651,7 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
                     match number.parse::<f64>() {
                         Ok(f) => tokens.push(Token { kind: TokenKind::FloatLiteral(f), line, column: start_column }),
                         Err(_) => {
-                            let location = SourceLocation::new(file_path.clone(), line, start_column);
+                            let location = SourceLocation::new(file_path.to_path_buf(), line, start_column);
                             return Err(CompilerError::new(
                                 ErrorKind::InvalidNumber,
                                 format!("invalid float literal '{}'", number),
@@ -634,7 +665,7 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
                     match number.parse::<i64>() {
                         Ok(i) => tokens.push(Token { kind: TokenKind::Integer(i), line, column: start_column }),
                         Err(_) => {
-                            let location = SourceLocation::new(file_path.clone(), line, start_column);
+                            let location = SourceLocation::new(file_path.to_path_buf(), line, start_column);
                             return Err(CompilerError::new(
                                 ErrorKind::InvalidNumber,
                                 format!("invalid integer literal '{}'", number),
@@ -661,14 +692,39 @@ pub fn tokenize(source: &str, file_path: &PathBuf) -> Result<Vec<Token>, Compile
                         break;
                     }
                 }
-                if let Some(kind) = keywords.get(&ident.as_str()) {
+                // Raw identifier `r#new`: the name after `#` is never a keyword
+                if ident == "r" && chars.peek() == Some(&'#') {
+                    chars.next();
+                    column += 1;
+                    ident.clear();
+                    while let Some(&ch) = chars.peek() {
+                        if ch.is_alphanumeric() || ch == '_' {
+                            ident.push(ch);
+                            chars.next();
+                            column += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) || C_KEYWORDS.contains(&ident.as_str()) {
+                        let location = SourceLocation::new(file_path.to_path_buf(), line, start_column);
+                        return Err(CompilerError::new(
+                            ErrorKind::UnexpectedCharacter,
+                            format!("`r#{}` is not a valid raw identifier", ident),
+                            location,
+                        ).with_suggestion(Suggestion::simple(
+                            "a raw identifier is `r#` followed by a name that is not a C keyword, such as `r#new`"
+                        )));
+                    }
+                    tokens.push(Token { kind: TokenKind::Identifier(ident), line, column: start_column });
+                } else if let Some(kind) = keywords.get(&ident.as_str()) {
                     tokens.push(Token { kind: kind.clone(), line, column: start_column });
                 } else {
                     tokens.push(Token { kind: TokenKind::Identifier(ident), line, column: start_column });
                 }
             }
             _ => {
-                let location = SourceLocation::new(file_path.clone(), line, column);
+                let location = SourceLocation::new(file_path.to_path_buf(), line, column);
                 return Err(CompilerError::new(
                     ErrorKind::UnexpectedCharacter,
                     format!("unexpected character '{}'", ch),
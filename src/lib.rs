@@ -7,15 +7,34 @@ pub mod modules;
 pub mod error;
 pub mod intrinsics;
 pub mod builtins;
+pub mod consteval;
 
 use std::fs;
 use std::path::Path;
 
+/// Settings collected from the command line.
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// `-D NAME=value` defines, injected as immutable globals so the source
+    /// can refer to them like any constant.
+    pub defines: Vec<(String, String)>,
+    /// `-W error`: treat warnings as errors.
+    pub warnings_as_errors: bool,
+    /// `--test`: build a runner for the `@test` functions instead of the program.
+    pub test: bool,
+    /// `--emit-header`: also write a `.h` next to the `.c`, declaring the exports.
+    pub emit_header: bool,
+}
+
 pub fn compile(file_path: &Path, output_file: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    compile_with_options(file_path, output_file, &CompileOptions::default())
+}
+
+pub fn compile_with_options(file_path: &Path, output_file: Option<&str>, options: &CompileOptions) -> Result<(), Box<dyn std::error::Error>> {
     let source = fs::read_to_string(file_path)?;
     
     // Lexing
-    let tokens = match lexer::tokenize(&source, &file_path.to_path_buf()) {
+    let tokens = match lexer::tokenize(&source, file_path) {
         Ok(tokens) => tokens,
         Err(error) => {
             error::report_error(&error);
@@ -24,7 +43,7 @@ pub fn compile(file_path: &Path, output_file: Option<&str>) -> Result<(), Box<dy
     };
     
     // Parsing
-    let ast = match parser::parse(tokens, file_path.to_path_buf()) {
+    let mut ast = match parser::parse(tokens, file_path.to_path_buf()) {
         Ok(ast) => ast,
         Err(error) => {
             error::report_error(&error);
@@ -32,9 +51,12 @@ pub fn compile(file_path: &Path, output_file: Option<&str>) -> Result<(), Box<dy
         }
     };
     
+    apply_cfg(&mut ast, &options.defines);
+    inject_defines(&mut ast, &options.defines);
+    
     // Module resolution
     let cwd = std::env::current_dir().unwrap_or_else(|_| Path::new(".").to_path_buf());
-    let mut resolver = modules::ModuleResolver::new(cwd.to_str().unwrap());
+    let mut resolver = modules::ModuleResolver::new(cwd.to_str().unwrap()).with_defines(&options.defines);
     let imported_symbols = match resolver.resolve_imports(&ast) {
         Ok(symbols) => symbols,
         Err(error) => {
@@ -43,17 +65,185 @@ pub fn compile(file_path: &Path, output_file: Option<&str>) -> Result<(), Box<dy
         }
     };
     
-    // Semantic analysis (with imported symbols)
-    if let Err(error) = semantic::analyze_with_imports(&ast, &imported_symbols, &file_path.to_path_buf()) {
+    // Unannotated functions that return values get their return type from their returns
+    if let Err(error) = semantic::infer_return_types(&mut ast, &imported_symbols, &file_path.to_path_buf()) {
         error::report_error(&error);
         return Err(Box::new(error));
     }
     
+    // Semantic analysis (with imported symbols)
+    let warnings = match semantic::analyze_with_warnings(&ast, &imported_symbols, &file_path.to_path_buf()) {
+        Ok(warnings) => warnings,
+        Err(error) => {
+            error::report_error(&error);
+            return Err(Box::new(error));
+        }
+    };
+    if let Err(error) = check_warnings(warnings, options.warnings_as_errors) {
+        return Err(Box::new(error));
+    }
+    
     // Code generation
-    if let Err(error) = codegen::generate(&ast, &mut resolver, output_file) {
+    if let Err(error) = codegen::generate(&ast, &mut resolver, output_file, options.test) {
         error::report_error(&error);
         return Err(Box::new(error));
     }
+    if options.emit_header {
+        let header_file = Path::new(output_file.unwrap_or("output.c")).with_extension("h");
+        if let Err(error) = codegen::generate_header(&ast, &header_file.to_string_lossy()) {
+            error::report_error(&error);
+            return Err(Box::new(error));
+        }
+    }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Report warnings. Under `-W error` they are reported as errors and the
+/// first one fails the compilation.
+pub fn check_warnings(warnings: Vec<error::CompilerError>, warnings_as_errors: bool) -> Result<(), error::CompilerError> {
+    if !warnings_as_errors {
+        error::report_errors(&warnings);
+        return Ok(());
+    }
+    let errors: Vec<error::CompilerError> = warnings.into_iter().map(|w| w.promoted()).collect();
+    error::report_errors(&errors);
+    match errors.into_iter().next() {
+        Some(first) => Err(first),
+        None => Ok(()),
+    }
+}
+
+/// Drop the functions and methods whose `@cfg` conditions don't hold under the
+/// defines, so they are neither analyzed nor generated. `@cfg(NAME)` holds when
+/// NAME is defined, `@cfg(NAME = value)` when it is defined to that value.
+/// Imported modules are filtered as they are loaded, see `ModuleResolver::with_defines`.
+pub fn apply_cfg(ast: &mut ast::Program, defines: &[(String, String)]) {
+    let enabled = |func: &ast::Function| {
+        func.attributes.iter().filter(|a| a.name == "cfg").all(|a| {
+            let condition = a.argument.as_deref().unwrap_or_default();
+            match condition.split_once('=') {
+                Some((name, value)) => defines.iter().any(|(n, v)| n == name && v == value),
+                None => defines.iter().any(|(n, _)| n == condition),
+            }
+        })
+    };
+    ast.functions.retain(|f| enabled(f));
+    for imp in &mut ast.impls {
+        imp.functions.retain(|f| enabled(f));
+    }
+}
+
+/// Prepend each define as an immutable global. Values that look like integers,
+/// floats or booleans get that type; anything else becomes a string.
+pub fn inject_defines(ast: &mut ast::Program, defines: &[(String, String)]) {
+    let globals = defines.iter().map(|(name, value)| {
+        let literal = if let Ok(i) = value.parse::<i64>() {
+            ast::Literal::Integer(i)
+        } else if let Ok(f) = value.parse::<f64>() {
+            ast::Literal::Float(f)
+        } else if value == "true" || value == "false" {
+            ast::Literal::Bool(value == "true")
+        } else {
+            ast::Literal::String(value.clone())
+        };
+        ast::GlobalVariable {
+            name: name.clone(),
+            var_type: None,
+            mutable: false,
+            constant: false,
+            initializer: Some(ast::Expression::Literal(literal)),
+        }
+    });
+    let user_globals = std::mem::take(&mut ast.global_variables);
+    ast.global_variables = globals.chain(user_globals).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_define_injected_as_global() {
+        let path = PathBuf::from("test.rapt");
+        let src = "fn main() -> int { if DEBUG == 1 { return 1; } return 0; }";
+        let tokens = lexer::tokenize(src, &path).unwrap();
+        let mut ast = parser::parse(tokens, path.clone()).unwrap();
+        assert!(semantic::analyze_with_imports(&ast, &HashMap::new(), &path).is_err());
+
+        inject_defines(&mut ast, &[("DEBUG".to_string(), "1".to_string())]);
+        assert!(semantic::analyze_with_imports(&ast, &HashMap::new(), &path).is_ok());
+        assert_eq!(ast.global_variables[0].name, "DEBUG");
+        assert!(matches!(
+            ast.global_variables[0].initializer,
+            Some(ast::Expression::Literal(ast::Literal::Integer(1)))
+        ));
+    }
+
+    #[test]
+    fn test_cfg_excludes_functions() {
+        let path = PathBuf::from("test.rapt");
+        let src = r#"
+            @cfg(target = "linux")
+            fn platform() -> int { return 1; }
+            @cfg(target = "windows")
+            fn platform() -> int { return unknown_windows_call(); }
+            fn main() -> int { return platform(); }
+        "#;
+        let parse = || parser::parse(lexer::tokenize(src, &path).unwrap(), path.clone()).unwrap();
+
+        let mut linux = parse();
+        apply_cfg(&mut linux, &[("target".to_string(), "linux".to_string())]);
+        assert_eq!(linux.functions.len(), 2);
+        assert!(semantic::analyze_with_imports(&linux, &HashMap::new(), &path).is_ok());
+
+        let mut windows = parse();
+        apply_cfg(&mut windows, &[("target".to_string(), "windows".to_string())]);
+        assert_eq!(windows.functions.len(), 2);
+        assert!(semantic::analyze_with_imports(&windows, &HashMap::new(), &path).is_err());
+
+        let mut neither = parse();
+        apply_cfg(&mut neither, &[]);
+        assert_eq!(neither.functions.len(), 1);
+        assert_eq!(neither.functions[0].name, "main");
+    }
+
+    #[test]
+    fn test_cfg_applies_to_imported_modules() {
+        let dir = std::env::temp_dir().join(format!("rapter_cfg_module_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("platform.rapt"), r#"
+            @cfg(target = "linux")
+            fn platform() -> string { return "linux"; }
+            @cfg(target = "windows")
+            fn platform() -> string { return windows_name(); }
+            export fn name() -> string { return platform(); }
+        "#).unwrap();
+        let defines = [("target".to_string(), "linux".to_string())];
+        let mut resolver = modules::ModuleResolver::new(dir.to_str().unwrap()).with_defines(&defines);
+        let module = resolver.load_module("platform").map(|module| module.program.functions.clone());
+        std::fs::remove_dir_all(&dir).ok();
+        let functions = module.unwrap();
+        assert_eq!(functions.len(), 2);
+        assert!(matches!(
+            &functions[0].body[..],
+            [ast::Statement::Return(Some(ast::Expression::Literal(ast::Literal::String(name))))] if name == "linux"
+        ), "{:?}", functions[0].body);
+    }
+
+    #[test]
+    fn test_warnings_promoted_under_werror() {
+        let path = PathBuf::from("test.rapt");
+        let tokens = lexer::tokenize("fn main() -> int { return 0; return 1; }", &path).unwrap();
+        let ast = parser::parse(tokens, path.clone()).unwrap();
+        let warnings = semantic::analyze_with_warnings(&ast, &HashMap::new(), &path).unwrap();
+        assert_eq!(warnings.len(), 1);
+
+        assert!(check_warnings(warnings.clone(), false).is_ok());
+        let err = check_warnings(warnings, true).unwrap_err();
+        assert!(!err.is_warning());
+        assert_eq!(err.kind, error::ErrorKind::UnreachableCode);
+    }
+}
@@ -1,5 +1,6 @@
-use rapter_lang::compile;
+use rapter_lang::{compile_with_options, CompileOptions};
 use rapter_lang::lexer::tokenize;
+use rapter_lang::{error, parser};
 
 use std::env;
 use std::path::Path;
@@ -8,7 +9,7 @@ use std::fs;
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        eprintln!("Usage: {} <file.rapt> [-o output.c]", args[0]);
+        eprintln!("Usage: {} <file.rapt> [-o output.c] [-D NAME[=value]]... [-W error] [--test] [--emit-header]\n       {} <file.rapt> --tokens | --dump-ast", args[0], args[0]);
         std::process::exit(1);
     }
     
@@ -20,21 +21,71 @@ fn main() {
     
     if args.len() > 2 && args[2] == "--tokens" {
         let source = fs::read_to_string(file_path).unwrap();
-        let tokens = tokenize(&source, &file_path.to_path_buf()).unwrap();
+        let tokens = tokenize(&source, file_path).unwrap();
         for token in tokens {
             println!("{:?}", token);
         }
         return;
     }
     
-    // Parse -o flag for output file
-    let output_file = if args.len() > 3 && args[2] == "-o" {
-        Some(args[3].clone())
-    } else {
-        None
-    };
+    // Parse only, so parser problems can be inspected without semantic analysis or codegen
+    if args.len() > 2 && args[2] == "--dump-ast" {
+        let source = fs::read_to_string(file_path).unwrap();
+        let ast = match tokenize(&source, file_path) {
+            Ok(tokens) => parser::parse(tokens, file_path.to_path_buf()),
+            Err(e) => Err(e),
+        };
+        match ast {
+            Ok(ast) => println!("{:#?}", ast),
+            Err(e) => {
+                error::report_error(&e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    
+    // Parse -o, -D, -W, --test and --emit-header flags
+    let mut output_file: Option<String> = None;
+    let mut options = CompileOptions::default();
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        if arg == "-o" && i + 1 < args.len() {
+            output_file = Some(args[i + 1].clone());
+            i += 1;
+        } else if let Some(def) = arg.strip_prefix("-D") {
+            // `-D NAME` or `-DNAME`
+            let def = if def.is_empty() && i + 1 < args.len() {
+                i += 1;
+                args[i].as_str()
+            } else {
+                def
+            };
+            match parse_define(def) {
+                Ok(define) => options.defines.push(define),
+                Err(message) => {
+                    eprintln!("{}\nUsage: -D NAME[=value]", message);
+                    std::process::exit(1);
+                }
+            }
+        } else if arg == "-W" && i + 1 < args.len() && args[i + 1] == "error" {
+            options.warnings_as_errors = true;
+            i += 1;
+        } else if arg == "-Werror" {
+            options.warnings_as_errors = true;
+        } else if arg == "--test" {
+            options.test = true;
+        } else if arg == "--emit-header" {
+            options.emit_header = true;
+        } else {
+            eprintln!("Unknown argument: {}", arg);
+            std::process::exit(1);
+        }
+        i += 1;
+    }
     
-    match compile(file_path, output_file.as_deref()) {
+    match compile_with_options(file_path, output_file.as_deref(), &options) {
         Ok(_) => {
             if output_file.is_some() {
                 eprintln!("Compilation successful!");
@@ -48,3 +99,16 @@ fn main() {
         }
     }
 }
+
+// `NAME=value` -> (NAME, value); a bare `NAME` defines it as 1, like the C preprocessor.
+// NAME becomes a global, so it must be an identifier.
+fn parse_define(def: &str) -> Result<(String, String), String> {
+    let (name, value) = def.split_once('=').unwrap_or((def, "1"));
+    let mut chars = name.chars();
+    let is_identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_identifier {
+        return Err(format!("Invalid define `{}`: expected an identifier before `=`", def));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
@@ -20,6 +20,8 @@ pub struct Symbol {
     pub ty: Type,
     // For exported structs, include a map of field name -> field type
     pub fields: Option<std::collections::HashMap<String, Type>>,
+    // For exported enums, the value of every variant
+    pub variants: Option<std::collections::HashMap<String, i64>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -34,6 +36,21 @@ pub enum SymbolType {
 pub struct ModuleResolver {
     modules: HashMap<String, Module>,
     base_path: String,
+    // `-D` defines, for the `@cfg` conditions of loaded modules
+    defines: Vec<(String, String)>,
+}
+
+impl Module {
+    /// The module's `fn init()`, if it has one; the main wrapper runs it before `main`
+    pub fn init_function(&self) -> Option<&Function> {
+        self.program.functions.iter()
+            .find(|f| f.name == "init" && f.parameters.is_empty() && f.return_type.is_none())
+    }
+
+    /// C name of the module's `init`, distinct per module so several modules can have one
+    pub fn init_symbol(&self) -> String {
+        format!("__{}_init", self.name.replace('.', "_"))
+    }
 }
 
 impl ModuleResolver {
@@ -41,9 +58,16 @@ impl ModuleResolver {
         ModuleResolver {
             modules: HashMap::new(),
             base_path: base_path.to_string(),
+            defines: Vec::new(),
         }
     }
 
+    /// Filter every loaded module's functions by their `@cfg` conditions under `defines`
+    pub fn with_defines(mut self, defines: &[(String, String)]) -> Self {
+        self.defines = defines.to_vec();
+        self
+    }
+
     pub fn load_module(&mut self, module_name: &str) -> Result<&Module, CompilerError> {
         if self.modules.contains_key(module_name) {
             return Ok(&self.modules[module_name]);
@@ -79,13 +103,14 @@ impl ModuleResolver {
         ).with_suggestions(vec![Suggestion::simple(
             "Check the module file for syntax errors".to_string(),
         )]))?;
-        let program = parser::parse(tokens, full_path.clone()).map_err(|e| CompilerError::new(
+        let mut program = parser::parse(tokens, full_path.clone()).map_err(|e| CompilerError::new(
             ErrorKind::ModuleLoadError,
             format!("Failed to parse module '{}': {}", module_name, e.message),
             e.location.clone(),
         ).with_suggestions(vec![Suggestion::simple(
             "Check the module file for syntax errors".to_string(),
         )]))?;
+        crate::apply_cfg(&mut program, &self.defines);
 
         let exports = self.collect_exports(&program)?;
 
@@ -112,6 +137,7 @@ impl ModuleResolver {
                             symbol_type: SymbolType::Function,
                             ty: func.return_type.clone().unwrap_or(Type::Void),
                             fields: None,
+                            variants: None,
                         };
                         exports.insert(name.clone(), symbol);
                     } else {
@@ -137,6 +163,7 @@ impl ModuleResolver {
                             symbol_type: SymbolType::Struct,
                             ty: Type::Struct(name.clone()),
                             fields: Some(fields_map),
+                            variants: None,
                         };
                         exports.insert(name.clone(), symbol);
                     } else {
@@ -160,11 +187,15 @@ impl ModuleResolver {
                             variants_map.insert(variant.name.clone(), Type::Int);
                         }
                         
+                        let values = enm.variants.iter()
+                            .filter_map(|variant| variant.value.map(|value| (variant.name.clone(), value)))
+                            .collect();
                         let symbol = Symbol {
                             name: name.clone(),
                             symbol_type: SymbolType::Enum,
                             ty: Type::Enum(name.clone()),
                             fields: Some(variants_map),
+                            variants: Some(values),
                         };
                         exports.insert(name.clone(), symbol);
                     } else {
@@ -202,6 +233,7 @@ impl ModuleResolver {
                     symbol_type: symbol.symbol_type.clone(),
                     ty: symbol.ty.clone(),
                     fields: symbol.fields.clone(),
+                    variants: symbol.variants.clone(),
                 };
                 imported_symbols.insert(name.clone(), unqualified_symbol);
                 
@@ -212,6 +244,7 @@ impl ModuleResolver {
                     symbol_type: symbol.symbol_type.clone(),
                     ty: symbol.ty.clone(),
                     fields: symbol.fields.clone(),
+                    variants: symbol.variants.clone(),
                 };
                 imported_symbols.insert(prefixed_name, prefixed_symbol);
             }
@@ -3,10 +3,16 @@ use crate::lexer::{Token, TokenKind};
 use crate::error::{CompilerError, ErrorKind, SourceLocation};
 use std::path::PathBuf;
 
+// Attributes accepted on function declarations
+const FUNCTION_ATTRIBUTES: &[&str] = &["cfg", "deprecated", "inline", "noreturn", "test"];
+const STRUCT_ATTRIBUTES: &[&str] = &["packed", "repr"];
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
     file_path: PathBuf,
+    // Const parameters of the function being parsed; `N {` is never a struct literal
+    const_params: Vec<String>,
 }
 
 impl Parser {
@@ -15,6 +21,7 @@ impl Parser {
             tokens,
             current: 0,
             file_path,
+            const_params: Vec::new(),
         }
     }
 
@@ -28,7 +35,7 @@ impl Parser {
         )
     }
 
-    fn _previous_location(&self) -> SourceLocation {
+    fn previous_location(&self) -> SourceLocation {
         let token = self.previous();
         SourceLocation::new(
             self.file_path.clone(),
@@ -49,11 +56,13 @@ impl Parser {
     pub fn parse(&mut self) -> Result<Program, CompilerError> {
         let mut functions = Vec::new();
         let mut extern_functions = Vec::new();
+        let mut extern_structs = Vec::new();
         let mut structs = Vec::new();
         let mut enums = Vec::new();
         let mut imports = Vec::new();
         let mut exports = Vec::new();
         let mut global_variables = Vec::new();
+        let mut impls = Vec::new();
         
         while !self.is_at_end() {
             match self.peek().kind {
@@ -67,8 +76,37 @@ impl Parser {
                 TokenKind::Fn => {
                     functions.push(self.function()?);
                 }
+                TokenKind::Const => {
+                    if matches!(self.tokens.get(self.current + 1), Some(t) if t.kind == TokenKind::Fn) {
+                        functions.push(self.const_function()?);
+                    } else {
+                        global_variables.push(self.const_global()?);
+                    }
+                }
+                TokenKind::At => {
+                    let attributes = self.attributes(&[FUNCTION_ATTRIBUTES, STRUCT_ATTRIBUTES].concat())?;
+                    if self.check(TokenKind::Struct) {
+                        self.check_attribute_target(&attributes, STRUCT_ATTRIBUTES, "a struct")?;
+                        let mut st = self.struct_def()?;
+                        st.attributes = attributes;
+                        structs.push(st);
+                        continue;
+                    }
+                    if !self.check(TokenKind::Fn) {
+                        return Err(self.unexpected_token_error("`fn` or `struct` after attributes"));
+                    }
+                    self.check_attribute_target(&attributes, FUNCTION_ATTRIBUTES, "a function")?;
+                    let mut func = self.function()?;
+                    func.attributes = attributes;
+                    functions.push(func);
+                }
                 TokenKind::Extern => {
-                    extern_functions.push(self.extern_function()?);
+                    let is_struct = matches!(self.tokens.get(self.current + 1), Some(t) if t.kind == TokenKind::Struct);
+                    if is_struct {
+                        extern_structs.push(self.extern_struct()?);
+                    } else {
+                        extern_functions.push(self.extern_function()?);
+                    }
                 }
                 TokenKind::Struct => {
                     structs.push(self.struct_def()?);
@@ -79,11 +117,14 @@ impl Parser {
                 TokenKind::Import => {
                     imports.push(self.import()?);
                 }
+                TokenKind::Impl => {
+                    impls.push(self.impl_block()?);
+                }
                 TokenKind::Export => {
                     self.consume(TokenKind::Export)?;
                     match self.peek().kind {
-                        TokenKind::Fn => {
-                            let func = self.function()?;
+                        TokenKind::Fn | TokenKind::Const => {
+                            let func = if self.check(TokenKind::Const) { self.const_function()? } else { self.function()? };
                             functions.push(func.clone());
                             exports.push(Export {
                                 item: ExportItem::Function(func.name),
@@ -129,10 +170,12 @@ impl Parser {
             imports,
             exports,
             extern_functions,
+            extern_structs,
             functions,
             structs,
             enums,
             global_variables,
+            impls,
         })
     }
     
@@ -155,13 +198,57 @@ impl Parser {
             name,
             var_type,
             mutable,
+            constant: false,
             initializer,
         })
     }
     
+    // `const NAME: T = value;`: an immutable global whose value is fixed at compile time
+    fn const_global(&mut self) -> Result<GlobalVariable, CompilerError> {
+        self.consume(TokenKind::Const)?;
+        let name = self.identifier()?;
+        let var_type = if self.match_token(TokenKind::Colon) {
+            Some(self.type_annotation()?)
+        } else {
+            None
+        };
+        self.consume(TokenKind::Equal)?;
+        let initializer = self.expression()?;
+        self.consume(TokenKind::Semicolon)?;
+        Ok(GlobalVariable {
+            name,
+            var_type,
+            mutable: false,
+            constant: true,
+            initializer: Some(initializer),
+        })
+    }
+    
     fn function(&mut self) -> Result<Function, CompilerError> {
         self.consume(TokenKind::Fn)?;
         let name = self.identifier()?;
+        self.function_rest(name)
+    }
+    
+    // `const fn name(...)`: a function that can also be evaluated at compile time
+    fn const_function(&mut self) -> Result<Function, CompilerError> {
+        self.consume(TokenKind::Const)?;
+        if !self.check(TokenKind::Fn) {
+            return Err(self.unexpected_token_error("`fn` after `const`"));
+        }
+        let mut func = self.function()?;
+        func.is_const = true;
+        Ok(func)
+    }
+    
+    // Parameters, return type and body of a function whose name is already consumed
+    fn function_rest(&mut self, name: String) -> Result<Function, CompilerError> {
+        let (line, column) = (self.previous().line, self.previous().column);
+        let const_params = if self.match_token(TokenKind::Less) {
+            self.const_parameters()?
+        } else {
+            Vec::new()
+        };
         self.consume(TokenKind::LeftParen)?;
         let parameters = self.parameters()?;
         self.consume(TokenKind::RightParen)?;
@@ -171,16 +258,133 @@ impl Parser {
             None
         };
         self.consume(TokenKind::LeftBrace)?;
-        let body = self.block()?;
+        self.const_params = const_params.clone();
+        let body = self.block();
+        self.const_params.clear();
+        let body = body?;
         self.consume(TokenKind::RightBrace)?;
         Ok(Function {
             name,
             parameters,
             return_type,
+            return_type_inferred: false,
             body,
+            attributes: Vec::new(),
+            is_const: false,
+            const_params,
+            line,
+            column,
         })
     }
     
+    // `const N: int, ...>` after a function name's `<`
+    fn const_parameters(&mut self) -> Result<Vec<String>, CompilerError> {
+        let mut names = Vec::new();
+        loop {
+            if !self.check(TokenKind::Const) {
+                return Err(self.unexpected_token_error("`const` parameter"));
+            }
+            self.advance();
+            names.push(self.identifier()?);
+            self.consume(TokenKind::Colon)?;
+            if self.type_annotation()? != Type::Int {
+                return Err(self.error(
+                    ErrorKind::InvalidSyntax,
+                    "const parameters must have type `int`".to_string(),
+                ).with_suggestion(crate::error::Suggestion::with_example(
+                    "const parameters are array lengths",
+                    "fn sum<const N: int>(a: [int; N]) -> int"
+                )));
+            }
+            if !self.match_token(TokenKind::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenKind::Greater)?;
+        Ok(names)
+    }
+    
+    fn attributes(&mut self, allowed: &[&str]) -> Result<Vec<Attribute>, CompilerError> {
+        let mut attributes = Vec::new();
+        while self.check(TokenKind::At) {
+            let location = self.peek_location();
+            self.advance();
+            let name = self.identifier()?;
+            if !allowed.contains(&name.as_str()) {
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidSyntax,
+                    format!("unknown attribute `@{}`", name),
+                    location,
+                ).with_suggestion(crate::error::Suggestion::simple(
+                    format!("supported attributes here: {}", allowed.iter().map(|a| format!("`@{}`", a)).collect::<Vec<_>>().join(", "))
+                )));
+            }
+            // `@deprecated("message")`, `@repr(C)`: an optional string or name argument
+            let mut argument = None;
+            if name == "cfg" {
+                argument = Some(self.cfg_condition()?);
+            } else if self.check(TokenKind::LeftParen) {
+                self.advance();
+                match &self.peek().kind {
+                    TokenKind::StringLiteral(s) | TokenKind::Identifier(s) => {
+                        argument = Some(s.clone());
+                        self.advance();
+                    }
+                    _ => return Err(self.unexpected_token_error("a string literal or name")),
+                }
+                self.consume(TokenKind::RightParen)?;
+            }
+            if name == "repr" && argument.as_deref() != Some("C") {
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidSyntax,
+                    format!("unsupported representation `@repr({})`", argument.unwrap_or_default()),
+                    location,
+                ).with_suggestion(crate::error::Suggestion::simple(
+                    "only `@repr(C)` is supported: fields are laid out in declaration order"
+                )));
+            }
+            attributes.push(Attribute { name, argument });
+            // Attributes may be separated from the declaration by comments
+            while let TokenKind::Comment(_) = self.peek().kind {
+                self.advance();
+            }
+        }
+        Ok(attributes)
+    }
+    
+    // `@cfg(NAME)` or `@cfg(NAME = value)`, kept in the `NAME=value` form of `-D`
+    fn cfg_condition(&mut self) -> Result<String, CompilerError> {
+        self.consume(TokenKind::LeftParen)?;
+        let mut condition = self.identifier()?;
+        if self.check(TokenKind::Equal) {
+            self.advance();
+            let value = match &self.peek().kind {
+                TokenKind::StringLiteral(s) | TokenKind::Identifier(s) => s.clone(),
+                TokenKind::Integer(i) => i.to_string(),
+                TokenKind::BoolLiteral(b) => b.to_string(),
+                _ => return Err(self.unexpected_token_error("a string, name or integer value")),
+            };
+            self.advance();
+            condition = format!("{}={}", condition, value);
+        }
+        self.consume(TokenKind::RightParen)?;
+        Ok(condition)
+    }
+    
+    // Attributes before `fn` or `struct` are parsed together; each must suit what follows
+    fn check_attribute_target(&self, attributes: &[Attribute], allowed: &[&str], target: &str) -> Result<(), CompilerError> {
+        match attributes.iter().find(|a| !allowed.contains(&a.name.as_str())) {
+            Some(attribute) => Err(CompilerError::new(
+                ErrorKind::InvalidSyntax,
+                format!("attribute `@{}` cannot be applied to {}", attribute.name, target),
+                self.peek_location(),
+            ).with_suggestion(crate::error::Suggestion::simple(
+                format!("supported attributes here: {}", allowed.iter().map(|a| format!("`@{}`", a)).collect::<Vec<_>>().join(", "))
+            ))),
+            None => Ok(()),
+        }
+    }
+    
     fn extern_parameters(&mut self) -> Result<(Vec<Parameter>, bool), CompilerError> {
         let mut params = Vec::new();
         let mut variadic = false;
@@ -195,7 +399,7 @@ impl Parser {
                 let name = self.identifier()?;
                 self.consume(TokenKind::Colon)?;
                 let param_type = self.type_annotation()?;
-                params.push(Parameter { name, param_type });
+                params.push(Parameter::new(name, param_type));
                 
                 if !self.match_token(TokenKind::Comma) {
                     break;
@@ -210,10 +414,28 @@ impl Parser {
         let mut params = Vec::new();
         if !self.check(TokenKind::RightParen) {
             loop {
+                // `ref n: T` and `mut ref n: T` take the caller's variable by reference; `ref` is
+                // only a keyword in front of another parameter name
+                let mutable = self.match_token(TokenKind::Mut);
+                let by_ref = matches!(&self.peek().kind, TokenKind::Identifier(word) if word == "ref")
+                    && matches!(self.tokens.get(self.current + 1).map(|token| &token.kind), Some(TokenKind::Identifier(_)));
+                if by_ref {
+                    self.advance();
+                } else if mutable {
+                    return Err(self.unexpected_token_error("`ref` after `mut`"));
+                }
                 let name = self.identifier()?;
+                // Bare `self` receiver in impl blocks; its type is filled in by impl_block
+                if name == "self" && !by_ref && !self.check(TokenKind::Colon) {
+                    params.push(Parameter::new(name, Type::Struct("Self".to_string())));
+                    if !self.match_token(TokenKind::Comma) {
+                        break;
+                    }
+                    continue;
+                }
                 self.consume(TokenKind::Colon)?;
                 let param_type = self.type_annotation()?;
-                params.push(Parameter { name, param_type });
+                params.push(Parameter { name, param_type, by_ref, mutable });
                 if !self.match_token(TokenKind::Comma) {
                     break;
                 }
@@ -249,16 +471,13 @@ impl Parser {
                 let element_type = self.type_annotation()?;
                 if self.match_token(TokenKind::Semicolon) {
                     // [type; size] syntax for fixed-size arrays
-                    // For now, ignore size
-                    if let TokenKind::Integer(_) = self.peek().kind {
-                        self.advance();
-                    }
+                    let length = self.array_length()?;
                     self.consume(TokenKind::RightBracket)?;
-                    Ok(Type::Array(Box::new(element_type)))
+                    Ok(Type::Array(Box::new(element_type), Some(length)))
                 } else {
                     // [type] syntax for array type annotations
                     self.consume(TokenKind::RightBracket)?;
-                    Ok(Type::Array(Box::new(element_type)))
+                    Ok(Type::Array(Box::new(element_type), None))
                 }
             }
             TokenKind::Ampersand => {
@@ -271,6 +490,41 @@ impl Parser {
                 let pointee = self.type_annotation()?;
                 Ok(Type::Pointer(Box::new(pointee)))
             }
+            // (int, int) syntax for tuples
+            TokenKind::LeftParen => {
+                self.advance();
+                let mut elements = vec![self.type_annotation()?];
+                while self.match_token(TokenKind::Comma) {
+                    elements.push(self.type_annotation()?);
+                }
+                self.consume(TokenKind::RightParen)?;
+                if elements.len() < 2 {
+                    return Err(self.error(
+                        ErrorKind::InvalidSyntax,
+                        "a tuple type needs at least two elements".to_string(),
+                    ));
+                }
+                Ok(Type::Tuple(elements))
+            }
+            // fn(int, int) -> int syntax for function pointers
+            TokenKind::Fn => {
+                self.advance();
+                self.consume(TokenKind::LeftParen)?;
+                let mut params = Vec::new();
+                if !self.check(TokenKind::RightParen) {
+                    params.push(self.type_annotation()?);
+                    while self.match_token(TokenKind::Comma) {
+                        params.push(self.type_annotation()?);
+                    }
+                }
+                self.consume(TokenKind::RightParen)?;
+                let return_type = if self.match_token(TokenKind::Arrow) {
+                    self.type_annotation()?
+                } else {
+                    Type::Void
+                };
+                Ok(Type::Function(params, Box::new(return_type)))
+            }
             TokenKind::Identifier(name) => {
                 let mut ident = name.clone();
                 self.advance();
@@ -310,6 +564,8 @@ impl Parser {
                     let element_type = self.type_annotation()?;
                     self.consume(TokenKind::RightBracket)?;
                     Ok(Type::DynamicArray(Box::new(element_type)))
+                } else if ident == "long" {
+                    Ok(Type::Long)
                 } else {
                     Ok(Type::Struct(ident))
                 }
@@ -319,7 +575,7 @@ impl Parser {
                 format!("expected type, found `{}`", self.peek().kind),
             ).with_suggestion(crate::error::Suggestion::with_example(
                 "valid types include",
-                "int, float, bool, char, string, [int], *int, MyStruct"
+                "int, long, float, bool, char, string, [int], *int, fn(int) -> int, (int, int), MyStruct"
             ))),
         };
         
@@ -336,19 +592,21 @@ impl Parser {
     fn struct_def(&mut self) -> Result<Struct, CompilerError> {
         self.consume(TokenKind::Struct)?;
         let name = self.identifier()?;
+        let (line, column) = (self.previous().line, self.previous().column);
         self.consume(TokenKind::LeftBrace)?;
         let fields = self.fields()?;
         self.consume(TokenKind::RightBrace)?;
-        Ok(Struct { name, fields })
+        Ok(Struct { name, fields, attributes: Vec::new(), line, column })
     }
     
     fn enum_def(&mut self) -> Result<Enum, CompilerError> {
         self.consume(TokenKind::Enum)?;
         let name = self.identifier()?;
+        let (line, column) = (self.previous().line, self.previous().column);
         self.consume(TokenKind::LeftBrace)?;
         let variants = self.enum_variants()?;
         self.consume(TokenKind::RightBrace)?;
-        Ok(Enum { name, variants })
+        Ok(Enum { name, variants, line, column })
     }
     
     fn enum_variants(&mut self) -> Result<Vec<EnumVariant>, CompilerError> {
@@ -398,7 +656,12 @@ impl Parser {
             let name = self.identifier()?;
             self.consume(TokenKind::Colon)?;
             let field_type = self.type_annotation()?;
-            fields.push(Field { name, field_type });
+            let default = if self.match_token(TokenKind::Equal) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            fields.push(Field { name, field_type, default });
             if !self.match_token(TokenKind::Comma) {
                 break;
             }
@@ -408,6 +671,13 @@ impl Parser {
     
     fn extern_function(&mut self) -> Result<ExternFunction, CompilerError> {
         self.consume(TokenKind::Extern)?;
+        let header = if let TokenKind::StringLiteral(h) = &self.peek().kind {
+            let h = h.clone();
+            self.advance();
+            Some(h)
+        } else {
+            None
+        };
         self.consume(TokenKind::Fn)?;
         let name = self.identifier()?;
         self.consume(TokenKind::LeftParen)?;
@@ -424,9 +694,45 @@ impl Parser {
             parameters,
             return_type,
             variadic,
+            header,
         })
     }
     
+    fn impl_block(&mut self) -> Result<Impl, CompilerError> {
+        self.consume(TokenKind::Impl)?;
+        let type_name = self.identifier()?;
+        self.consume(TokenKind::LeftBrace)?;
+        let mut functions = Vec::new();
+        while !self.check(TokenKind::RightBrace) && !self.is_at_end() {
+            if let TokenKind::Comment(_) = self.peek().kind {
+                self.advance();
+                continue;
+            }
+            let attributes = self.attributes(FUNCTION_ATTRIBUTES)?;
+            self.consume(TokenKind::Fn)?;
+            let name = self.member_name()?;
+            let mut func = self.function_rest(name)?;
+            func.attributes = attributes;
+            // `Self` in the signature refers to the impl's type
+            let self_type = Type::Struct(type_name.clone());
+            for param in &mut func.parameters {
+                param.param_type = substitute_self(&param.param_type, &self_type);
+            }
+            func.return_type = func.return_type.as_ref().map(|t| substitute_self(t, &self_type));
+            functions.push(func);
+        }
+        self.consume(TokenKind::RightBrace)?;
+        Ok(Impl { type_name, functions })
+    }
+    
+    fn extern_struct(&mut self) -> Result<ExternStruct, CompilerError> {
+        self.consume(TokenKind::Extern)?;
+        self.consume(TokenKind::Struct)?;
+        let name = self.identifier()?;
+        self.consume(TokenKind::Semicolon)?;
+        Ok(ExternStruct { name })
+    }
+    
     fn import(&mut self) -> Result<Import, CompilerError> {
         self.consume(TokenKind::Import)?;
         let mut module = self.module_segment()?;
@@ -439,9 +745,53 @@ impl Parser {
         } else {
             None
         };
+        // The terminating semicolon is optional
+        self.match_token(TokenKind::Semicolon);
         Ok(Import { module, alias })
     }
 
+    // Length in `[T; len]`: an integer literal, a `const fn` call with literal arguments, or
+    // a const parameter of the function
+    fn array_length(&mut self) -> Result<ArrayLength, CompilerError> {
+        match self.peek().kind.clone() {
+            TokenKind::Integer(n) if n >= 0 => {
+                self.advance();
+                Ok(ArrayLength::Literal(n as usize))
+            }
+            TokenKind::Identifier(name) if !matches!(self.tokens.get(self.current + 1), Some(t) if t.kind == TokenKind::LeftParen) => {
+                self.advance();
+                Ok(ArrayLength::Param(name))
+            }
+            TokenKind::Identifier(function) => {
+                self.advance();
+                self.consume(TokenKind::LeftParen)?;
+                let mut arguments = Vec::new();
+                while !self.check(TokenKind::RightParen) {
+                    let negative = self.match_token(TokenKind::Minus);
+                    match self.peek().kind {
+                        TokenKind::Integer(n) => {
+                            self.advance();
+                            arguments.push(if negative { -n } else { n });
+                        }
+                        _ => return Err(self.unexpected_token_error("an integer literal argument")),
+                    }
+                    if !self.match_token(TokenKind::Comma) {
+                        break;
+                    }
+                }
+                self.consume(TokenKind::RightParen)?;
+                Ok(ArrayLength::ConstCall { function, arguments })
+            }
+            _ => Err(self.error(
+                ErrorKind::InvalidSyntax,
+                format!("expected array length, found `{}`", self.peek().kind),
+            ).with_suggestion(crate::error::Suggestion::with_example(
+                "an array length is an integer literal or a const fn call with literal arguments",
+                "[int; 8], [int; square(3)]"
+            ))),
+        }
+    }
+
     // Accept module path segments that may coincide with keywords like 'char', 'int', etc.
     fn module_segment(&mut self) -> Result<String, CompilerError> {
         match &self.peek().kind {
@@ -474,27 +824,32 @@ impl Parser {
         match self.peek().kind {
             TokenKind::Let => {
                 let stmt = self.let_statement()?;
-                self.consume(TokenKind::Semicolon)?;
+                self.consume_semicolon()?;
                 Ok(stmt)
             }
             TokenKind::Const => {
                 let stmt = self.const_statement()?;
-                self.consume(TokenKind::Semicolon)?;
+                self.consume_semicolon()?;
                 Ok(stmt)
             }
             TokenKind::Return => {
                 let stmt = self.return_statement()?;
-                self.consume(TokenKind::Semicolon)?;
+                self.consume_semicolon()?;
                 Ok(stmt)
             }
             TokenKind::Break => {
                 self.advance();
-                self.consume(TokenKind::Semicolon)?;
-                Ok(Statement::Break)
+                let value = if self.check(TokenKind::Semicolon) {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.consume_semicolon()?;
+                Ok(Statement::Break(value))
             }
             TokenKind::Continue => {
                 self.advance();
-                self.consume(TokenKind::Semicolon)?;
+                self.consume_semicolon()?;
                 Ok(Statement::Continue)
             }
             TokenKind::If => self.if_statement(),
@@ -504,13 +859,17 @@ impl Parser {
                 let expr = self.expression()?;
                 if self.match_token(TokenKind::Equal) {
                     let value = self.expression()?;
-                    self.consume(TokenKind::Semicolon)?;
+                    self.consume_semicolon()?;
                     Ok(Statement::Assignment {
                         target: expr,
                         value,
                     })
+                } else if matches!(expr, Expression::Match { .. } | Expression::Loop { .. }) {
+                    // Like other block statements, a match or loop statement needs no semicolon
+                    self.match_token(TokenKind::Semicolon);
+                    Ok(Statement::Expression(expr))
                 } else {
-                    self.consume(TokenKind::Semicolon)?;
+                    self.consume_semicolon()?;
                     Ok(Statement::Expression(expr))
                 }
             }
@@ -520,6 +879,17 @@ impl Parser {
     fn let_statement(&mut self) -> Result<Statement, CompilerError> {
         self.consume(TokenKind::Let)?;
         let mutable = self.match_token(TokenKind::Mut);
+        if self.match_token(TokenKind::LeftParen) {
+            // let (a, b) = tuple;
+            let mut names = vec![self.identifier()?];
+            while self.match_token(TokenKind::Comma) {
+                names.push(self.identifier()?);
+            }
+            self.consume(TokenKind::RightParen)?;
+            self.consume(TokenKind::Equal)?;
+            let initializer = self.expression()?;
+            return Ok(Statement::LetTuple { names, mutable, initializer });
+        }
         let name = self.identifier()?;
         let var_type = if self.match_token(TokenKind::Colon) {
             Some(self.type_annotation()?)
@@ -565,13 +935,20 @@ impl Parser {
     
     fn if_statement(&mut self) -> Result<Statement, CompilerError> {
         self.consume(TokenKind::If)?;
+        let binding = if self.match_token(TokenKind::Let) {
+            let pattern = self.parse_arm_pattern()?;
+            self.consume(TokenKind::Equal)?;
+            Some(pattern)
+        } else {
+            None
+        };
         let condition = self.expression()?;
         self.consume(TokenKind::LeftBrace)?;
         let then_branch = self.block()?;
         self.consume(TokenKind::RightBrace)?;
         let else_branch = if self.match_token(TokenKind::Else) {
-            if self.match_token(TokenKind::If) {
-                // else if
+            if self.check(TokenKind::If) {
+                // else if: the nested if_statement consumes the `if` itself
                 Some(vec![self.if_statement()?])
             } else {
                 self.consume(TokenKind::LeftBrace)?;
@@ -582,6 +959,10 @@ impl Parser {
         } else {
             None
         };
+        if let Some(pattern) = binding {
+            // if let P = x { a } else { b }  =>  match x { P => { a } _ => { b } }
+            return Ok(Statement::Expression(Self::let_match(pattern, condition, then_branch, else_branch.unwrap_or_default())));
+        }
         Ok(Statement::If {
             condition,
             then_branch,
@@ -591,13 +972,60 @@ impl Parser {
     
     fn while_statement(&mut self) -> Result<Statement, CompilerError> {
         self.consume(TokenKind::While)?;
+        let header_let = self.check(TokenKind::LeftParen)
+            && matches!(self.tokens.get(self.current + 1).map(|token| &token.kind), Some(TokenKind::Let));
+        if header_let {
+            // while (let x = next(); x > 0) { a }  =>  loop { let x = next(); if !(x > 0) { break; } a },
+            // so `x` is bound afresh before every check and a `continue` rebinds it too
+            self.advance();
+            let binding = self.let_statement()?;
+            self.consume(TokenKind::Semicolon)?;
+            let condition = self.expression()?;
+            self.consume(TokenKind::RightParen)?;
+            self.consume(TokenKind::LeftBrace)?;
+            let body = self.block()?;
+            self.consume(TokenKind::RightBrace)?;
+            let exit = Statement::If {
+                condition: Expression::Unary { operator: UnaryOp::Not, operand: Box::new(condition) },
+                then_branch: vec![Statement::Break(None)],
+                else_branch: None,
+            };
+            let mut statements = vec![binding, exit];
+            statements.extend(body);
+            return Ok(Statement::Expression(Expression::Loop { body: statements }));
+        }
+        let binding = if self.match_token(TokenKind::Let) {
+            let pattern = self.parse_arm_pattern()?;
+            self.consume(TokenKind::Equal)?;
+            Some(pattern)
+        } else {
+            None
+        };
         let condition = self.expression()?;
         self.consume(TokenKind::LeftBrace)?;
         let body = self.block()?;
         self.consume(TokenKind::RightBrace)?;
+        if let Some(pattern) = binding {
+            // while let P = x { a }  =>  loop { match x { P => { a } _ => { break; } } }, so a
+            // `break` or `continue` in the body targets the loop
+            let dispatch = Self::let_match(pattern, condition, body, vec![Statement::Break(None)]);
+            return Ok(Statement::Expression(Expression::Loop { body: vec![Statement::Expression(dispatch)] }));
+        }
         Ok(Statement::While { condition, body })
     }
     
+    // The statement match that `if let` and `while let` stand for
+    fn let_match(pattern: crate::ast::Pattern, scrutinee: Expression, matched: Vec<Statement>, otherwise: Vec<Statement>) -> Expression {
+        use crate::ast::{MatchArm, Pattern};
+        Expression::Match {
+            scrutinee: Box::new(scrutinee),
+            arms: vec![
+                MatchArm { pattern, body: ArmBody::Block(matched) },
+                MatchArm { pattern: Pattern::Wildcard, body: ArmBody::Block(otherwise) },
+            ],
+        }
+    }
+    
     fn for_statement(&mut self) -> Result<Statement, CompilerError> {
         self.consume(TokenKind::For)?;
         let variable = self.identifier()?;
@@ -825,6 +1253,25 @@ impl Parser {
                     expression: Box::new(expr),
                     target_type,
                 };
+            } else if self.check(TokenKind::Question)
+                && matches!(self.tokens.get(self.current + 1).map(|token| &token.kind), Some(TokenKind::Dot))
+            {
+                // Optional chaining: expr?.field
+                self.advance();
+                self.advance();
+                let field = self.identifier()?;
+                expr = Expression::OptionalField {
+                    object: Box::new(expr),
+                    field,
+                };
+            } else if let Some(negated) = self.null_check() {
+                // `p is null` / `p is not null` are sugar for comparing with `nil`
+                self.current += if negated { 3 } else { 2 };
+                expr = Expression::Binary {
+                    left: Box::new(expr),
+                    operator: if negated { BinaryOp::NotEqual } else { BinaryOp::Equal },
+                    right: Box::new(Expression::Literal(Literal::Nil)),
+                };
             } else if self.match_token(TokenKind::Question) {
                 // Error propagation: expr?
                 expr = Expression::TryOperator {
@@ -837,6 +1284,19 @@ impl Parser {
         Ok(expr)
     }
     
+    // Whether the next words are `is null` (Some(false)) or `is not null` (Some(true))
+    fn null_check(&self) -> Option<bool> {
+        let word = |offset: usize| match self.tokens.get(self.current + offset).map(|token| &token.kind) {
+            Some(TokenKind::Identifier(word)) => Some(word.as_str()),
+            _ => None,
+        };
+        match (word(0), word(1), word(2)) {
+            (Some("is"), Some("null"), _) => Some(false),
+            (Some("is"), Some("not"), Some("null")) => Some(true),
+            _ => None,
+        }
+    }
+    
     fn finish_call(&mut self, callee: Expression) -> Result<Expression, CompilerError> {
         let mut arguments = Vec::new();
         if !self.check(TokenKind::RightParen) {
@@ -876,10 +1336,20 @@ impl Parser {
                 self.advance();
                 Ok(Expression::Literal(Literal::Char(c)))
             }
+            TokenKind::Nil => {
+                self.advance();
+                Ok(Expression::Literal(Literal::Nil))
+            }
             TokenKind::StringLiteral(s) => {
-                let s = s.clone();
+                let mut s = s.clone();
                 self.advance();
                 
+                // Adjacent literals form one string, C-style: "abc" "def"
+                while let TokenKind::StringLiteral(next) = &self.peek().kind {
+                    s.push_str(next);
+                    self.advance();
+                }
+                
                 // Check if the string contains interpolations (:var:)
                 if s.contains(':') {
                     let parts = self.parse_string_interpolations(&s)?;
@@ -896,9 +1366,18 @@ impl Parser {
                 let name = name.clone();
                 self.advance();
                 
+                // assert_type(expr, T): its second argument is a type, not an expression
+                if name == "assert_type" && self.match_token(TokenKind::LeftParen) {
+                    let expression = self.expression()?;
+                    self.consume(TokenKind::Comma)?;
+                    let expected = self.type_annotation()?;
+                    self.consume(TokenKind::RightParen)?;
+                    return Ok(Expression::AssertType { expression: Box::new(expression), expected });
+                }
+                
                 // Enum access: EnumName::Variant
                 if self.match_token(TokenKind::ColonColon) {
-                    let variant = self.identifier()?;
+                    let variant = self.member_name()?;
                     return Ok(Expression::EnumAccess {
                         enum_name: name,
                         variant,
@@ -907,11 +1386,19 @@ impl Parser {
                 
                 // Struct literal: Identifier { field: expr, ... }
                 // Heuristic: only treat as struct literal if Identifier starts with uppercase
-                if self.check(TokenKind::LeftBrace) && name.chars().next().map(|c| c.is_uppercase()).unwrap_or(false) {
+                if self.check(TokenKind::LeftBrace) && name.chars().next().map(|c| c.is_uppercase()).unwrap_or(false)
+                    && !self.const_params.contains(&name) {
                     self.advance(); // consume '{'
                     let mut fields: Vec<(String, Expression)> = Vec::new();
+                    let mut base = None;
                     if !self.check(TokenKind::RightBrace) {
                         loop {
+                            // Spread: `..base` fills in the remaining fields and must come last
+                            if self.match_token(TokenKind::DotDot) {
+                                base = Some(Box::new(self.expression()?));
+                                self.match_token(TokenKind::Comma);
+                                break;
+                            }
                             let field_name = self.identifier()?;
                             self.consume(TokenKind::Colon)?;
                             let value_expr = self.expression()?;
@@ -922,7 +1409,7 @@ impl Parser {
                         }
                     }
                     self.consume(TokenKind::RightBrace)?;
-                    Ok(Expression::StructLiteral { name, fields })
+                    Ok(Expression::StructLiteral { name, fields, base })
                 } else {
                     Ok(Expression::Variable(name))
                 }
@@ -930,8 +1417,17 @@ impl Parser {
             TokenKind::LeftParen => {
                 self.advance();
                 let expr = self.expression()?;
+                if !self.check(TokenKind::Comma) {
+                    self.consume(TokenKind::RightParen)?;
+                    return Ok(expr);
+                }
+                // (a, b): a tuple
+                let mut elements = vec![expr];
+                while self.match_token(TokenKind::Comma) {
+                    elements.push(self.expression()?);
+                }
                 self.consume(TokenKind::RightParen)?;
-                Ok(expr)
+                Ok(Expression::Tuple(elements))
             }
             TokenKind::LeftBracket => {
                 self.advance();
@@ -954,10 +1450,18 @@ impl Parser {
                 
                 let mut arms = Vec::new();
                 while !self.check(TokenKind::RightBrace) {
-                    let pattern = self.parse_pattern()?;
+                    let pattern = self.parse_arm_pattern()?;
                     self.consume(TokenKind::FatArrow)?;
+                    if self.match_token(TokenKind::LeftBrace) {
+                        let body = self.block()?;
+                        self.consume(TokenKind::RightBrace)?;
+                        arms.push(crate::ast::MatchArm { pattern, body: ArmBody::Block(body) });
+                        // A block arm needs no comma
+                        self.match_token(TokenKind::Comma);
+                        continue;
+                    }
                     let expression = self.expression()?;
-                    arms.push(crate::ast::MatchArm { pattern, expression });
+                    arms.push(crate::ast::MatchArm { pattern, body: ArmBody::Expression(expression) });
                     
                     // Comma is optional after the last arm
                     if !self.check(TokenKind::RightBrace) {
@@ -968,6 +1472,13 @@ impl Parser {
                 self.consume(TokenKind::RightBrace)?;
                 Ok(Expression::Match { scrutinee, arms })
             }
+            TokenKind::Loop => {
+                self.advance(); // consume 'loop'
+                self.consume(TokenKind::LeftBrace)?;
+                let body = self.block()?;
+                self.consume(TokenKind::RightBrace)?;
+                Ok(Expression::Loop { body })
+            }
             _ => Err(self.error(
                 ErrorKind::InvalidSyntax,
                 format!("expected expression, found `{}`", self.peek().kind),
@@ -978,6 +1489,17 @@ impl Parser {
         }
     }
     
+    // Impl function names may also be `new`/`delete`, e.g. `Point::new`
+    fn member_name(&mut self) -> Result<String, CompilerError> {
+        if self.match_token(TokenKind::New) {
+            Ok("new".to_string())
+        } else if self.match_token(TokenKind::Delete) {
+            Ok("delete".to_string())
+        } else {
+            self.identifier()
+        }
+    }
+    
     fn identifier(&mut self) -> Result<String, CompilerError> {
         if let TokenKind::Identifier(ref name) = self.peek().kind {
             let name = name.clone();
@@ -993,6 +1515,55 @@ impl Parser {
         }
     }
     
+    // A match arm's pattern: one pattern, or alternatives separated by `|`
+    fn parse_arm_pattern(&mut self) -> Result<crate::ast::Pattern, CompilerError> {
+        let first = self.parse_pattern()?;
+        if !self.check(TokenKind::Pipe) {
+            return Ok(first);
+        }
+        let mut alternatives = vec![first];
+        while self.match_token(TokenKind::Pipe) {
+            alternatives.push(self.parse_pattern()?);
+        }
+        Ok(crate::ast::Pattern::Or(alternatives))
+    }
+    
+    // After a literal pattern: `..end` or `..=end` makes it a range; the end must be a literal of the same kind
+    fn range_pattern(&mut self, start: crate::ast::Literal) -> Result<crate::ast::Pattern, CompilerError> {
+        use crate::ast::{Literal, Pattern};
+        
+        let inclusive = if self.match_token(TokenKind::DotDotEq) {
+            true
+        } else if self.match_token(TokenKind::DotDot) {
+            false
+        } else {
+            return Ok(Pattern::Literal(start));
+        };
+        let end = match (&start, &self.peek().kind) {
+            (Literal::Integer(_), TokenKind::Integer(val)) => Literal::Integer(*val),
+            (Literal::Char(_), TokenKind::CharLiteral(val)) => Literal::Char(*val),
+            _ => return Err(self.error(
+                ErrorKind::InvalidSyntax,
+                format!("expected the end of the range pattern, found `{}`", self.peek().kind),
+            ).with_suggestion(crate::error::Suggestion::with_example(
+                "both ends of a range pattern are literals of the same kind",
+                "'a'..='z' => ..., 0..10 => ..."
+            ))),
+        };
+        self.advance();
+        if let (Literal::Char(_), false) = (&start, inclusive) {
+            // A char class names its last member: `'a'..'z'` would leave out `'z'`
+            return Err(self.error(
+                ErrorKind::InvalidSyntax,
+                "a char range pattern must include its end".to_string(),
+            ).with_suggestion(crate::error::Suggestion::with_example(
+                "write the range with `..=`",
+                "'a'..='z' => ..."
+            )));
+        }
+        Ok(Pattern::Range { start, end, inclusive })
+    }
+    
     fn parse_pattern(&mut self) -> Result<crate::ast::Pattern, CompilerError> {
         use crate::ast::Pattern;
         
@@ -1007,19 +1578,30 @@ impl Parser {
                 let enum_name = enum_name.clone();
                 self.advance();
                 
+                // Outer binding: name @ pattern
+                if self.match_token(TokenKind::At) {
+                    let pattern = self.parse_pattern()?;
+                    return Ok(Pattern::Binding { name: enum_name, pattern: Box::new(pattern) });
+                }
+                
                 if self.match_token(TokenKind::ColonColon) {
-                    let variant = self.identifier()?;
+                    let variant = self.member_name()?;
                     
-                    // Check for binding: Option::Some(x)
-                    let binding = if self.match_token(TokenKind::LeftParen) {
-                        let binding_name = self.identifier()?;
+                    // Check for binding: Option::Some(x), or a nested pattern: Option::Some(Result::Ok(x))
+                    let mut binding = None;
+                    let mut nested = None;
+                    if self.match_token(TokenKind::LeftParen) {
+                        let is_name = matches!(&self.peek().kind, TokenKind::Identifier(_))
+                            && matches!(self.tokens.get(self.current + 1).map(|token| &token.kind), Some(TokenKind::RightParen));
+                        if is_name {
+                            binding = Some(self.identifier()?);
+                        } else {
+                            nested = Some(Box::new(self.parse_pattern()?));
+                        }
                         self.consume(TokenKind::RightParen)?;
-                        Some(binding_name)
-                    } else {
-                        None
-                    };
+                    }
                     
-                    Ok(Pattern::EnumVariant { enum_name, variant, binding })
+                    Ok(Pattern::EnumVariant { enum_name, variant, binding, nested })
                 } else {
                     // It might be a literal or variable, but for now we don't support variable patterns
                     // Treat it as an error
@@ -1031,17 +1613,36 @@ impl Parser {
                     )))
                 }
             }
-            // Integer literal pattern
+            // Integer literal pattern, or an integer range `1..10`
             TokenKind::Integer(val) => {
-                let val = *val;
+                let start = crate::ast::Literal::Integer(*val);
                 self.advance();
-                Ok(Pattern::Literal(crate::ast::Literal::Integer(val)))
+                self.range_pattern(start)
             }
-            // Char literal pattern
+            // Char literal pattern, or a char range `'a'..='z'`
             TokenKind::CharLiteral(val) => {
-                let val = *val;
+                let start = crate::ast::Literal::Char(*val);
                 self.advance();
-                Ok(Pattern::Literal(crate::ast::Literal::Char(val)))
+                self.range_pattern(start)
+            }
+            // Tuple pattern: (0, _); a single pattern in parentheses is just that pattern
+            TokenKind::LeftParen => {
+                self.advance();
+                let mut elements = vec![self.parse_pattern()?];
+                let mut is_tuple = false;
+                while self.match_token(TokenKind::Comma) {
+                    is_tuple = true;
+                    if self.check(TokenKind::RightParen) {
+                        break;
+                    }
+                    elements.push(self.parse_pattern()?);
+                }
+                self.consume(TokenKind::RightParen)?;
+                if is_tuple {
+                    Ok(Pattern::Tuple(elements))
+                } else {
+                    Ok(elements.remove(0))
+                }
             }
             // String literal pattern
             TokenKind::StringLiteral(val) => {
@@ -1060,7 +1661,7 @@ impl Parser {
                 format!("expected pattern, found `{}`", self.peek().kind),
             ).with_suggestion(crate::error::Suggestion::with_example(
                 "valid patterns include",
-                "EnumName::Variant, 42, 'a', \"hello\", true, _"
+                "EnumName::Variant, 42, 'a', \"hello\", true, (0, _), _"
             ))),
         }
     }
@@ -1074,6 +1675,21 @@ impl Parser {
         }
     }
     
+    // The `;` ending a statement. When it is missing, the error points at the end of the
+    // statement instead of at whatever token happens to come next
+    fn consume_semicolon(&mut self) -> Result<(), CompilerError> {
+        if self.match_token(TokenKind::Semicolon) {
+            return Ok(());
+        }
+        Err(CompilerError::new(
+            ErrorKind::MissingSemicolon,
+            "expected `;` after statement".to_string(),
+            self.previous_location(),
+        ).with_suggestion(crate::error::Suggestion::simple(
+            "add `;` at the end of this statement"
+        )))
+    }
+    
     fn match_token(&mut self, kind: TokenKind) -> bool {
         if self.check(kind) {
             self.advance();
@@ -1198,4 +1814,121 @@ impl Parser {
 pub fn parse(tokens: Vec<Token>, file_path: PathBuf) -> Result<Program, CompilerError> {
     let mut parser = Parser::new(tokens, file_path);
     parser.parse()
-}
\ No newline at end of file
+}
+fn substitute_self(ty: &Type, self_type: &Type) -> Type {
+    match ty {
+        Type::Struct(name) if name == "Self" => self_type.clone(),
+        Type::Pointer(inner) => Type::Pointer(Box::new(substitute_self(inner, self_type))),
+        Type::Array(inner, length) => Type::Array(Box::new(substitute_self(inner, self_type)), length.clone()),
+        Type::DynamicArray(inner) => Type::DynamicArray(Box::new(substitute_self(inner, self_type))),
+        _ => ty.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer;
+
+    fn parse_source(source: &str) -> Result<Program, CompilerError> {
+        let path = PathBuf::from("test.rapt");
+        let tokens = lexer::tokenize(source, &path)?;
+        parse(tokens, path)
+    }
+
+    #[test]
+    fn test_unknown_attribute() {
+        let err = parse_source("@fast\nfn f() {}").unwrap_err();
+        assert!(err.message.contains("unknown attribute `@fast`"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_struct_attributes() {
+        let program = parse_source("@repr(C)\n@packed\nstruct S { a: char, b: int }").unwrap();
+        assert!(program.structs[0].has_attribute("packed") && program.structs[0].has_attribute("repr"));
+        let err = parse_source("@packed\nfn f() {}").unwrap_err();
+        assert!(err.message.contains("`@packed` cannot be applied to a function"), "{}", err.message);
+        let err = parse_source("@repr(Rust)\nstruct S { a: int }").unwrap_err();
+        assert!(err.message.contains("unsupported representation `@repr(Rust)`"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_null_check_sugar() {
+        let program = parse_source("fn main() -> bool { let p: *int = nil; return p is not null; }").unwrap();
+        let Statement::Return(Some(Expression::Binary { left, operator, right })) = &program.functions[0].body[1] else {
+            panic!("{:?}", program.functions[0].body[1]);
+        };
+        assert!(matches!(&**left, Expression::Variable(name) if name == "p"));
+        assert_eq!(*operator, BinaryOp::NotEqual);
+        assert!(matches!(**right, Expression::Literal(Literal::Nil)));
+    }
+
+    #[test]
+    fn test_extern_header() {
+        let program = parse_source("extern \"math.h\" fn hypot(x: float, y: float) -> float;").unwrap();
+        assert_eq!(program.extern_functions[0].header.as_deref(), Some("math.h"));
+    }
+
+    #[test]
+    fn test_impl_block() {
+        let program = parse_source("struct Point { x: int, y: int }\nimpl Point {\n    fn new(x: int, y: int) -> Self { return Point { x: x, y: y }; }\n    fn sum(self) -> int { return self.x + self.y; }\n}").unwrap();
+        let imp = &program.impls[0];
+        assert_eq!(imp.type_name, "Point");
+        assert!(!imp.functions[0].is_method());
+        assert_eq!(imp.functions[0].return_type, Some(Type::Struct("Point".to_string())));
+        assert!(imp.functions[1].is_method());
+        assert_eq!(imp.functions[1].parameters[0].param_type, Type::Struct("Point".to_string()));
+    }
+
+    #[test]
+    fn test_import_semicolon_is_optional() {
+        for source in ["import math\nfn main() {}", "import math;\nfn main() {}", "import std.io as io;\nimport math\nfn main() {}"] {
+            let program = parse_source(source).unwrap();
+            assert_eq!(program.functions.len(), 1, "{}", source);
+            assert!(program.imports.iter().any(|i| i.module == "math"), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_adjacent_string_literals_are_merged() {
+        let program = parse_source("fn main() {\n    let s = \"abc\"\n        \"def\";\n}").unwrap();
+        match &program.functions[0].body[0] {
+            Statement::Let { initializer: Some(Expression::Literal(Literal::String(s))), .. } => assert_eq!(s, "abcdef"),
+            other => panic!("expected a single string literal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_else_if_nests_an_if() {
+        let program = parse_source("fn main() {\n    if a {\n    } else if b {\n    } else {\n    }\n}").unwrap();
+        match &program.functions[0].body[0] {
+            Statement::If { else_branch: Some(else_branch), .. } => {
+                assert!(matches!(else_branch.as_slice(), [Statement::If { else_branch: Some(_), .. }]), "{:?}", else_branch);
+            }
+            other => panic!("expected an if statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_range_patterns() {
+        let program = parse_source("fn main() {\n    match c {\n        'a'..='z' => 1,\n        0..10 => 2,\n        _ => 3,\n    }\n}").unwrap();
+        match &program.functions[0].body[0] {
+            Statement::Expression(Expression::Match { arms, .. }) => {
+                assert!(matches!(arms[0].pattern, Pattern::Range { start: Literal::Char('a'), end: Literal::Char('z'), inclusive: true }), "{:?}", arms[0].pattern);
+                assert!(matches!(arms[1].pattern, Pattern::Range { start: Literal::Integer(0), end: Literal::Integer(10), inclusive: false }), "{:?}", arms[1].pattern);
+            }
+            other => panic!("expected a match statement, got {:?}", other),
+        }
+        assert!(parse_source("fn main() {\n    match c {\n        'a'..9 => 1,\n    }\n}").is_err());
+        let err = parse_source("fn main() {\n    match c {\n        'a'..'z' => 1,\n    }\n}").unwrap_err();
+        assert!(err.message.contains("must include its end"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_missing_semicolon_points_at_statement() {
+        let err = parse_source("fn main() {\n    let x = 1\n    return x;\n}").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::MissingSemicolon));
+        assert_eq!(err.message, "expected `;` after statement");
+        assert_eq!(err.location.line, 2);
+    }
+}
@@ -2,14 +2,17 @@ use crate::ast::*;
 use crate::error::{CompilerError, ErrorKind, SourceLocation, Suggestion, type_mismatch, undefined_variable, duplicate_definition};
 use crate::modules::{Symbol as ModuleSymbol, SymbolType as ModuleSymbolType};
 use crate::builtins::BuiltinRegistry;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use crate::consteval::{check_const_body, ConstEvaluator};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct Symbol {
     pub name: String,
     pub symbol_type: SymbolType,
     pub ty: Type,
+    // Where the symbol was defined, for duplicate-definition errors
+    pub location: SourceLocation,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -17,20 +20,55 @@ pub enum SymbolType {
     Function,
     Struct,
     Enum,
-    Variable,
+    // `let` or `let mut` binding; only a `let mut` one may be passed as a `mut ref` argument
+    Variable { mutable: bool },
     Parameter,
+    // `ref`/`mut ref` parameter; only a `mut ref` one may be assigned through
+    RefParameter { mutable: bool },
 }
 
 pub struct SymbolTable {
     scopes: Vec<HashMap<String, Symbol>>,
     // Map of struct name -> map of field name -> field type
     struct_defs: HashMap<String, HashMap<String, Type>>,
+    // Map of struct name -> fields that have a default value
+    struct_field_defaults: HashMap<String, HashSet<String>>,
     // Map of enum name -> map of variant name -> variant value
     enum_defs: HashMap<String, HashMap<String, i64>>,
+    // Opaque C types from `extern struct`, only usable behind a pointer
+    opaque_types: HashSet<String>,
+    // Map of type name -> functions from its impl blocks (unmangled names)
+    impl_functions: HashMap<String, HashMap<String, Function>>,
+    // Extern function declarations, for checking call arguments
+    extern_functions: HashMap<String, ExternFunction>,
+    // `const fn`s by name, for evaluating array lengths
+    const_functions: HashMap<String, Function>,
+    // Functions with `<const N: int>` parameters, whose calls must fix every `N`
+    const_generic_functions: HashMap<String, Function>,
+    // Parameters of functions taking some by `ref`, whose arguments must be assignable places
+    ref_functions: HashMap<String, Vec<Parameter>>,
+    // `@deprecated` functions by name, with the attribute's message if any
+    deprecated_functions: HashMap<String, Option<String>>,
+    // `@noreturn` functions, whose calls end a path like `return` does
+    noreturn_functions: HashSet<String>,
+    // Const parameters of the function being analyzed
+    const_params: Vec<String>,
+    // Map of function name -> parameter types, for functions used as values
+    function_params: HashMap<String, Vec<Type>>,
     // Built-in generic types (Option, Result, etc.)
     builtins: BuiltinRegistry,
     // Track current function's return type for ? operator validation
     current_function_return_type: Option<Type>,
+    // Non-fatal diagnostics collected during analysis
+    warnings: Vec<CompilerError>,
+    // While inferring an unannotated function's return type, the types of its `return` values
+    inferred_returns: Option<Vec<Type>>,
+    // Unannotated functions whose return types are not inferred yet; a `return` whose value
+    // calls one of them is left out of the inference and checked once the types are known
+    pending_returns: HashSet<String>,
+    // For each enclosing loop, its kind (`for`, `while` or `loop`) for diagnostics and the
+    // types of its `break` values; `None` unless it is a `loop` used as a value
+    loop_breaks: Vec<(&'static str, Option<Vec<Type>>)>,
 }
 
 impl SymbolTable {
@@ -38,9 +76,24 @@ impl SymbolTable {
         SymbolTable {
             scopes: vec![HashMap::new()],
             struct_defs: HashMap::new(),
+            struct_field_defaults: HashMap::new(),
             enum_defs: HashMap::new(),
+            opaque_types: HashSet::new(),
+            impl_functions: HashMap::new(),
+            extern_functions: HashMap::new(),
+            const_functions: HashMap::new(),
+            const_generic_functions: HashMap::new(),
+            ref_functions: HashMap::new(),
+            deprecated_functions: HashMap::new(),
+            noreturn_functions: HashSet::new(),
+            const_params: Vec::new(),
+            function_params: HashMap::new(),
+            warnings: Vec::new(),
             builtins: BuiltinRegistry::new(),
             current_function_return_type: None,
+            inferred_returns: None,
+            pending_returns: HashSet::new(),
+            loop_breaks: Vec::new(),
         }
     }
     
@@ -52,13 +105,10 @@ impl SymbolTable {
         self.scopes.pop();
     }
     
-    pub fn insert(&mut self, symbol: Symbol, file_path: &PathBuf) -> Result<(), CompilerError> {
+    pub fn insert(&mut self, symbol: Symbol) -> Result<(), CompilerError> {
         let name = symbol.name.clone();
-        if self.scopes.last_mut().unwrap().contains_key(&name) {
-            // For duplicate definition, we need the location of the previous definition
-            // For now, we'll use a dummy location since we don't track locations in the symbol table
-            let location = SourceLocation::new(file_path.clone(), 0, 0);
-            return Err(duplicate_definition(&name, location.clone(), location));
+        if let Some(previous) = self.scopes.last().unwrap().get(&name) {
+            return Err(duplicate_definition(&name, symbol.location, previous.location.clone()));
         }
         self.scopes.last_mut().unwrap().insert(name, symbol);
         Ok(())
@@ -79,6 +129,8 @@ impl SymbolTable {
             fields_map.insert(f.name.clone(), f.field_type.clone());
         }
         self.struct_defs.insert(st.name.clone(), fields_map);
+        let defaults = st.fields.iter().filter(|f| f.default.is_some()).map(|f| f.name.clone()).collect();
+        self.struct_field_defaults.insert(st.name.clone(), defaults);
     }
 
     pub fn get_struct_field_type(&self, struct_name: &str, field_name: &str) -> Option<&Type> {
@@ -97,6 +149,16 @@ impl SymbolTable {
         self.enum_defs.insert(enm.name.clone(), variants_map);
     }
     
+    pub fn warn(&mut self, warning: CompilerError) {
+        self.warnings.push(warning);
+    }
+    
+    pub fn get_impl_function(&self, type_name: &str, func_name: &str) -> Option<&Function> {
+        self.impl_functions
+            .get(type_name)
+            .and_then(|m| m.get(func_name))
+    }
+    
     pub fn get_enum_variant_value(&self, enum_name: &str, variant_name: &str) -> Option<&i64> {
         self.enum_defs
             .get(enum_name)
@@ -109,6 +171,131 @@ pub fn analyze(ast: &Program) -> Result<(), CompilerError> {
 }
 
 pub fn analyze_with_imports(ast: &Program, imported_symbols: &HashMap<String, ModuleSymbol>, file_path: &PathBuf) -> Result<(), CompilerError> {
+    analyze_with_warnings(ast, imported_symbols, file_path).map(|_| ())
+}
+
+// Full analysis; on success returns the warnings that were collected
+pub fn analyze_with_warnings(ast: &Program, imported_symbols: &HashMap<String, ModuleSymbol>, file_path: &PathBuf) -> Result<Vec<CompilerError>, CompilerError> {
+    let mut symbol_table = declare_program(ast, imported_symbols, file_path)?;
+    
+    // Second pass: analyze function bodies
+    for func in &ast.functions {
+        analyze_function(func, &mut symbol_table, file_path)?;
+    }
+    for imp in &ast.impls {
+        for func in imp.lowered_functions() {
+            analyze_function(&func, &mut symbol_table, file_path)?;
+        }
+    }
+    
+    Ok(symbol_table.warnings)
+}
+
+/// Give functions declared without `-> type` that return values the common type of their
+/// `return`s, so the rest of the compiler sees an ordinary annotated function. Run before
+/// analysis; an explicit annotation always wins.
+pub fn infer_return_types(ast: &mut Program, imported_symbols: &HashMap<String, ModuleSymbol>, file_path: &PathBuf) -> Result<(), CompilerError> {
+    if !ast.functions.iter().any(|f| f.return_type.is_none() && returns_value(&f.body)) {
+        return Ok(());
+    }
+    let mut symbol_table = declare_program(ast, imported_symbols, file_path)?;
+    let mut pending: Vec<usize> = (0..ast.functions.len())
+        .filter(|&i| ast.functions[i].return_type.is_none() && returns_value(&ast.functions[i].body))
+        .collect();
+    symbol_table.pending_returns = pending.iter().map(|&i| ast.functions[i].name.clone()).collect();
+    // Each round types the functions whose returns no longer wait on a pending function, so
+    // forward calls resolve once their callee has a type, and recursion from its base case
+    while !pending.is_empty() {
+        let mut resolved = Vec::new();
+        let mut failure = None;
+        for &i in &pending {
+            let func = &ast.functions[i];
+            let depth = symbol_table.scopes.len();
+            symbol_table.inferred_returns = Some(Vec::new());
+            let result = analyze_function(func, &mut symbol_table, file_path);
+            let returns = symbol_table.inferred_returns.take().unwrap_or_default();
+            // A failed analysis may leave its scopes behind
+            symbol_table.scopes.truncate(depth);
+            symbol_table.loop_breaks.clear();
+            if let Err(error) = result {
+                failure.get_or_insert(error);
+                continue;
+            }
+            let Some(ty) = returns.first().cloned() else { continue };
+            if let Some(other) = returns.iter().find(|t| !types_compatible(&ty, t)) {
+                let location = SourceLocation::new(file_path.clone(), func.line, func.column);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("function `{}` returns both `{:?}` and `{:?}`", func.name, ty, other),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "declare the return type with `-> type` or return values of a single type"
+                )));
+            }
+            // Later functions calling this one see the inferred type
+            if let Some(symbol) = symbol_table.scopes[0].get_mut(&func.name) {
+                symbol.ty = ty.clone();
+            }
+            symbol_table.pending_returns.remove(&func.name);
+            resolved.push((i, ty));
+        }
+        if resolved.is_empty() {
+            let func = &ast.functions[pending[0]];
+            return Err(failure.unwrap_or_else(|| CompilerError::new(
+                ErrorKind::TypeMismatch,
+                format!("cannot infer the return type of `{}`: every value it returns depends on a function whose type is not known", func.name),
+                SourceLocation::new(file_path.clone(), func.line, func.column),
+            ).with_suggestion(Suggestion::simple(
+                "declare the return type with `-> type`"
+            ))));
+        }
+        pending.retain(|i| !resolved.iter().any(|(j, _)| j == i));
+        for (i, ty) in resolved {
+            ast.functions[i].return_type = Some(ty);
+            ast.functions[i].return_type_inferred = true;
+        }
+    }
+    Ok(())
+}
+
+// Whether `expr` can initialize a global: C requires a constant for file-scope variables
+fn is_constant_initializer(expr: &Expression, symbol_table: &SymbolTable) -> bool {
+    match expr {
+        Expression::Literal(_) => true,
+        Expression::EnumAccess { enum_name, .. } => symbol_table.enum_defs.contains_key(enum_name),
+        Expression::Unary { operator: UnaryOp::Negate | UnaryOp::Not, operand } => is_constant_initializer(operand, symbol_table),
+        Expression::Binary { left, right, .. } => {
+            is_constant_initializer(left, symbol_table) && is_constant_initializer(right, symbol_table)
+        }
+        Expression::Cast { expression, target_type } => {
+            matches!(target_type, Type::Int | Type::Float | Type::Char | Type::Bool | Type::Enum(_) | Type::Struct(_))
+                && is_constant_initializer(expression, symbol_table)
+        }
+        Expression::Ternary { condition, true_expr, false_expr } => {
+            [condition, true_expr, false_expr].iter().all(|e| is_constant_initializer(e, symbol_table))
+        }
+        Expression::ArrayLiteral(elements) => elements.iter().all(|e| is_constant_initializer(e, symbol_table)),
+        _ => false,
+    }
+}
+
+// Whether a body has a `return` with a value, at any nesting depth
+fn returns_value(body: &[Statement]) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Statement::Return(value) => value.is_some(),
+        Statement::If { then_branch, else_branch, .. } => {
+            returns_value(then_branch) || else_branch.as_deref().is_some_and(returns_value)
+        }
+        Statement::While { body, .. } | Statement::For { body, .. } | Statement::Expression(Expression::Loop { body }) => returns_value(body),
+        Statement::Expression(Expression::Match { arms, .. }) => arms.iter().any(|arm| {
+            matches!(&arm.body, ArmBody::Block(body) if returns_value(body))
+        }),
+        _ => false,
+    })
+}
+
+// First pass: the symbol table with every global declaration of the program
+fn declare_program(ast: &Program, imported_symbols: &HashMap<String, ModuleSymbol>, file_path: &PathBuf) -> Result<SymbolTable, CompilerError> {
     let mut symbol_table = SymbolTable::new();
     
     // Add imported symbols to the symbol table
@@ -120,12 +307,13 @@ pub fn analyze_with_imports(ast: &Program, imported_symbols: &HashMap<String, Mo
                 ModuleSymbolType::Function => SymbolType::Function,
                 ModuleSymbolType::Struct => SymbolType::Struct,
                 ModuleSymbolType::Enum => SymbolType::Enum,
-                ModuleSymbolType::Variable => SymbolType::Variable,
+                ModuleSymbolType::Variable => SymbolType::Variable { mutable: true },
                 ModuleSymbolType::Parameter => SymbolType::Parameter,
             },
             ty: symbol.ty.clone(),
+            location: SourceLocation::new(file_path.clone(), 0, 0),
         };
-        symbol_table.insert(local_symbol, file_path)?;
+        symbol_table.insert(local_symbol)?;
         
         // If this is an imported struct with fields metadata, register the fields
         if let ModuleSymbolType::Struct = symbol.symbol_type {
@@ -138,33 +326,47 @@ pub fn analyze_with_imports(ast: &Program, imported_symbols: &HashMap<String, Mo
             }
         }
         
-        // If this is an imported enum with variants metadata, register the variants
+        // If this is an imported enum, register every variant with its value, so casts
+        // from integers and match exhaustiveness see the whole enum
         if let ModuleSymbolType::Enum = symbol.symbol_type {
-            if let Some(variants_map) = &symbol.fields {
+            if let Some(variants) = &symbol.variants {
                 let enum_name = match &symbol.ty {
                     Type::Enum(n) => n.clone(),
                     _ => name.clone(),
                 };
-                // Register variant names with placeholder values (0)
-                // The actual values don't matter for validation, only that the variants exist
-                // The C compiler will use the actual enum values from the typedef
-                let mut variants_with_values = HashMap::new();
-                for (variant_name, _) in variants_map {
-                    variants_with_values.insert(variant_name.clone(), 0i64);
-                }
-                symbol_table.enum_defs.insert(enum_name.clone(), variants_with_values);
+                symbol_table.enum_defs.insert(enum_name.clone(), variants.clone());
             }
         }
     }
     
     // First pass: collect global declarations
+    for ext_struct in &ast.extern_structs {
+        let symbol = Symbol {
+            name: ext_struct.name.clone(),
+            symbol_type: SymbolType::Struct,
+            ty: Type::Struct(ext_struct.name.clone()),
+            location: SourceLocation::new(file_path.clone(), 0, 0),
+        };
+        symbol_table.insert(symbol)?;
+        symbol_table.opaque_types.insert(ext_struct.name.clone());
+    }
+    
     for ext_func in &ast.extern_functions {
         let symbol = Symbol {
             name: ext_func.name.clone(),
             symbol_type: SymbolType::Function,
             ty: ext_func.return_type.clone().unwrap_or(Type::Void),
+            location: SourceLocation::new(file_path.clone(), 0, 0),
         };
-        symbol_table.insert(symbol, file_path)?;
+        symbol_table.insert(symbol)?;
+        for ty in ext_func.parameters.iter().map(|p| &p.param_type).chain(&ext_func.return_type) {
+            check_not_opaque_value(ty, &symbol_table, file_path)?;
+        }
+        symbol_table.extern_functions.insert(ext_func.name.clone(), ext_func.clone());
+        symbol_table.function_params.insert(
+            ext_func.name.clone(),
+            ext_func.parameters.iter().map(|p| p.param_type.clone()).collect(),
+        );
     }
     
     for func in &ast.functions {
@@ -172,8 +374,43 @@ pub fn analyze_with_imports(ast: &Program, imported_symbols: &HashMap<String, Mo
             name: func.name.clone(),
             symbol_type: SymbolType::Function,
             ty: func.return_type.clone().unwrap_or(Type::Void),
+            location: SourceLocation::new(file_path.clone(), func.line, func.column),
         };
-        symbol_table.insert(symbol, file_path)?;
+        symbol_table.insert(symbol)?;
+        symbol_table.function_params.insert(
+            func.name.clone(),
+            func.parameters.iter().map(|p| p.param_type.clone()).collect(),
+        );
+        if func.is_const {
+            symbol_table.const_functions.insert(func.name.clone(), func.clone());
+        }
+        if func.parameters.iter().any(|p| p.by_ref) {
+            symbol_table.ref_functions.insert(func.name.clone(), func.parameters.clone());
+        }
+        if let Some(attribute) = func.attribute("deprecated") {
+            symbol_table.deprecated_functions.insert(func.name.clone(), attribute.argument.clone());
+        }
+        if func.has_attribute("noreturn") {
+            symbol_table.noreturn_functions.insert(func.name.clone());
+        }
+        if !func.const_params.is_empty() {
+            // Calls infer each const parameter from the length of an array argument
+            let inferable = |name: &String| func.parameters.iter().any(|p| {
+                matches!(&p.param_type, Type::Array(_, Some(ArrayLength::Param(length))) if length == name)
+            });
+            if let Some(name) = func.const_params.iter().find(|name| !inferable(name)) {
+                let location = SourceLocation::new(file_path.clone(), func.line, func.column);
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidOperation,
+                    format!("const parameter `{}` of `{}` is not the length of any parameter", name, func.name),
+                    location,
+                ).with_suggestion(Suggestion::with_example(
+                    "calls infer const parameters from their array arguments",
+                    format!("fn {}<const {}: int>(a: [int; {}])", func.name, name, name)
+                )));
+            }
+            symbol_table.const_generic_functions.insert(func.name.clone(), func.clone());
+        }
     }
     
     for st in &ast.structs {
@@ -181,8 +418,12 @@ pub fn analyze_with_imports(ast: &Program, imported_symbols: &HashMap<String, Mo
             name: st.name.clone(),
             symbol_type: SymbolType::Struct,
             ty: Type::Struct(st.name.clone()),
+            location: SourceLocation::new(file_path.clone(), st.line, st.column),
         };
-        symbol_table.insert(symbol, file_path)?;
+        symbol_table.insert(symbol)?;
+        for field in &st.fields {
+            check_not_opaque_value(&field.field_type, &symbol_table, file_path)?;
+        }
         // record struct fields for semantic checks
         symbol_table.insert_struct_def(st);
     }
@@ -192,12 +433,91 @@ pub fn analyze_with_imports(ast: &Program, imported_symbols: &HashMap<String, Mo
             name: enm.name.clone(),
             symbol_type: SymbolType::Enum,
             ty: Type::Enum(enm.name.clone()),
+            location: SourceLocation::new(file_path.clone(), enm.line, enm.column),
         };
-        symbol_table.insert(symbol, file_path)?;
+        symbol_table.insert(symbol)?;
         // record enum variants for semantic checks
         symbol_table.insert_enum_def(enm);
     }
     
+    for imp in &ast.impls {
+        match symbol_table.lookup(&imp.type_name) {
+            Some(symbol) if symbol.symbol_type == SymbolType::Struct || symbol.symbol_type == SymbolType::Enum => {}
+            _ => {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::UndefinedType,
+                    format!("cannot find type `{}` for impl block", imp.type_name),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "impl blocks can only be written for structs and enums defined in this module"
+                )));
+            }
+        }
+        for func in &imp.functions {
+            if !func.const_params.is_empty() {
+                let location = SourceLocation::new(file_path.clone(), func.line, func.column);
+                return Err(CompilerError::new(
+                    ErrorKind::UnsupportedFeature,
+                    format!("`{}::{}` cannot have const parameters", imp.type_name, func.name),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "only free functions can be specialized on array lengths"
+                )));
+            }
+            if let Some(param) = func.parameters.iter().find(|p| p.by_ref) {
+                let location = SourceLocation::new(file_path.clone(), func.line, func.column);
+                return Err(CompilerError::new(
+                    ErrorKind::UnsupportedFeature,
+                    format!("`{}::{}` cannot take `{}` by `ref`", imp.type_name, func.name, param.name),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "only free functions can have `ref` parameters"
+                )));
+            }
+            // `drop` runs automatically on locals leaving scope, so it takes nothing else
+            if func.name == "drop" && (!func.is_method() || func.parameters.len() != 1 || func.return_type.is_some()) {
+                let location = SourceLocation::new(file_path.clone(), func.line, func.column);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("`{}::drop` must take only `self` and return nothing", imp.type_name),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "declare it as `fn drop(self) { ... }`"
+                )));
+            }
+            let symbol = Symbol {
+                name: Impl::mangled_name(&imp.type_name, &func.name),
+                symbol_type: SymbolType::Function,
+                ty: func.return_type.clone().unwrap_or(Type::Void),
+                location: SourceLocation::new(file_path.clone(), func.line, func.column),
+            };
+            symbol_table.insert(symbol)?;
+            symbol_table.impl_functions
+                .entry(imp.type_name.clone())
+                .or_default()
+                .insert(func.name.clone(), func.clone());
+        }
+    }
+    
+    // Field defaults are checked once, against the field's declared type
+    for st in &ast.structs {
+        for field in &st.fields {
+            if let Some(default) = &field.default {
+                let default_ty = infer_type_with_hint(default, Some(&field.field_type), &mut symbol_table, file_path)?;
+                if !types_compatible(&field.field_type, &default_ty) {
+                    let location = SourceLocation::new(file_path.clone(), st.line, st.column);
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("default for field `{}` of struct `{}` has type `{:?}`, expected `{:?}`",
+                                field.name, st.name, default_ty, field.field_type),
+                        location,
+                    ));
+                }
+            }
+        }
+    }
+    
     // Add global variables to symbol table
     for global_var in &ast.global_variables {
         let ty = if let Some(t) = &global_var.var_type {
@@ -217,6 +537,16 @@ pub fn analyze_with_imports(ast: &Program, imported_symbols: &HashMap<String, Mo
         
         // Validate initializer if present
         if let Some(init) = &global_var.initializer {
+            if !is_constant_initializer(init, &symbol_table) {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidOperation,
+                    format!("global variable `{}` must be initialized with a compile-time constant", global_var.name),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "C only allows literals, arithmetic on them and enum values here; assign other values in `main` or a module's `init` function"
+                )));
+            }
             let init_ty = infer_type(init, &mut symbol_table, file_path)?;
             if !types_compatible(&ty, &init_ty) {
                 let location = SourceLocation::new(file_path.clone(), 0, 0);
@@ -227,22 +557,40 @@ pub fn analyze_with_imports(ast: &Program, imported_symbols: &HashMap<String, Mo
                     location,
                 ));
             }
+            check_array_length(&ty, &symbol_table, file_path)?;
+            // `[T; N]` is emitted as a C array, so its initializer must have exactly N elements
+            if let (Type::Array(_, Some(length)), Expression::ArrayLiteral(elements)) = (&ty, init) {
+                let expected = match length {
+                    ArrayLength::Literal(n) => Some(*n as i64),
+                    ArrayLength::ConstCall { function, arguments } => {
+                        ConstEvaluator::new(&symbol_table.const_functions).call(function, arguments).ok()
+                    }
+                    ArrayLength::Param(_) => None,
+                };
+                if let Some(expected) = expected.filter(|n| *n != elements.len() as i64) {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("global variable `{}` has {} elements but its initializer has {}",
+                                global_var.name, expected, elements.len()),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        "give one initializer element per array element"
+                    )));
+                }
+            }
         }
         
         let symbol = Symbol {
             name: global_var.name.clone(),
-            symbol_type: SymbolType::Variable,
+            symbol_type: SymbolType::Variable { mutable: global_var.mutable },
             ty,
+            location: SourceLocation::new(file_path.clone(), 0, 0),
         };
-        symbol_table.insert(symbol, file_path)?;
-    }
-    
-    // Second pass: analyze function bodies
-    for func in &ast.functions {
-        analyze_function(func, &mut symbol_table, file_path)?;
+        symbol_table.insert(symbol)?;
     }
     
-    Ok(())
+    Ok(symbol_table)
 }
 
 fn analyze_function(func: &Function, symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<(), CompilerError> {
@@ -252,47 +600,123 @@ fn analyze_function(func: &Function, symbol_table: &mut SymbolTable, file_path:
     let expected_ret = func.return_type.clone().unwrap_or(Type::Void);
     symbol_table.current_function_return_type = Some(expected_ret.clone());
     
+    if func.is_const {
+        check_const_function(func, symbol_table, file_path)?;
+    }
+    if func.has_attribute("test") && (!func.parameters.is_empty() || func.return_type.is_some()) {
+        let location = SourceLocation::new(file_path.clone(), func.line, func.column);
+        return Err(CompilerError::new(
+            ErrorKind::InvalidOperation,
+            format!("test function `{}` must take no parameters and return nothing", func.name),
+            location,
+        ).with_suggestion(Suggestion::simple(
+            "the test runner calls each `@test` function as `name()`; report failures with assert()"
+        )));
+    }
+    
+    if func.has_attribute("noreturn") && (func.return_type.is_some() || contains_return(&func.body)) {
+        let location = SourceLocation::new(file_path.clone(), func.line, func.column);
+        return Err(CompilerError::new(
+            ErrorKind::InvalidOperation,
+            format!("`@noreturn` function `{}` must not return", func.name),
+            location,
+        ).with_suggestion(Suggestion::simple(
+            "end every path with a call to `exit` or another `@noreturn` function, and declare no return type"
+        )));
+    }
+    
+    if let Some(return_type) = &func.return_type {
+        check_not_opaque_value(return_type, symbol_table, file_path)?;
+    }
+    
+    // Const parameters are int constants in the body, with the value of each specialization
+    symbol_table.const_params = func.const_params.clone();
+    for name in &func.const_params {
+        symbol_table.insert(Symbol {
+            name: name.clone(),
+            symbol_type: SymbolType::Variable { mutable: false },
+            ty: Type::Int,
+            location: SourceLocation::new(file_path.clone(), func.line, func.column),
+        })?;
+    }
+    
     // Add parameters to scope
     for param in &func.parameters {
+        if let (true, Type::Array(..)) = (param.by_ref, &param.param_type) {
+            let location = SourceLocation::new(file_path.clone(), func.line, func.column);
+            return Err(CompilerError::new(
+                ErrorKind::UnsupportedFeature,
+                format!("array parameter `{}` cannot be taken by `ref`", param.name),
+                location,
+            ).with_suggestion(Suggestion::simple(
+                "arrays are already passed by reference; drop the `ref`"
+            )));
+        }
+        check_not_opaque_value(&param.param_type, symbol_table, file_path)?;
+        check_array_length(&param.param_type, symbol_table, file_path)?;
         let symbol = Symbol {
             name: param.name.clone(),
-            symbol_type: SymbolType::Parameter,
+            symbol_type: if param.by_ref { SymbolType::RefParameter { mutable: param.mutable } } else { SymbolType::Parameter },
             ty: param.param_type.clone(),
+            location: SourceLocation::new(file_path.clone(), func.line, func.column),
         };
-        symbol_table.insert(symbol, file_path)?;
+        symbol_table.insert(symbol)?;
     }
     
     // Analyze body
     for stmt in &func.body {
         analyze_statement(stmt, symbol_table, file_path, SourceLocation::new(file_path.clone(), 1, 1), &expected_ret)?;
     }
+    check_unreachable(&func.body, symbol_table, file_path);
+    if always_recurses(&func.body, &func.name) {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        symbol_table.warn(CompilerError::warning(
+            ErrorKind::UnboundedRecursion,
+            format!("function `{}` calls itself on every path before it can return", func.name),
+            location,
+        ).with_suggestion(Suggestion::simple(
+            "add a base case that returns before the recursive call"
+        )));
+    }
     // If function is non-void, ensure all paths return
     if expected_ret != Type::Void {
         if !block_returns(&func.body, symbol_table, file_path)? {
             let location = SourceLocation::new(file_path.clone(), 0, 0);
+            let declared = if func.return_type_inferred { "inferred" } else { "declared" };
             return Err(CompilerError::new(
                 ErrorKind::MissingReturnType,
-                format!("function `{}` is declared to return `{:?}` but not all paths return a value", func.name, expected_ret),
+                format!("function `{}` is {} to return `{:?}` but not all paths return a value", func.name, declared, expected_ret),
                 location,
             ).with_suggestion(Suggestion::simple(
-                "ensure every execution path returns a value of the declared type"
+                format!("ensure every execution path returns a value of the {} type", declared)
             )));
         }
     }
     
     // Clear current function return type
     symbol_table.current_function_return_type = None;
+    symbol_table.const_params.clear();
     symbol_table.exit_scope();
     Ok(())
 }
 
 fn analyze_statement(stmt: &Statement, symbol_table: &mut SymbolTable, file_path: &PathBuf, stmt_location: SourceLocation, expected_return: &Type) -> Result<(), CompilerError> {
     match stmt {
-        Statement::Let { name, var_type, mutable: _, initializer } => {
+        Statement::Let { name, var_type, mutable, initializer } => {
             let ty = if let Some(t) = var_type {
                 t.clone()
             } else if let Some(expr) = initializer {
-                infer_type(expr, symbol_table, file_path)?
+                let ty = infer_type(expr, symbol_table, file_path)?;
+                if ty == Type::Nil {
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("cannot infer the type of `{}` from `nil`", name),
+                        stmt_location,
+                    ).with_suggestion(Suggestion::simple(
+                        "annotate the variable with a pointer or string type, like `: *int`"
+                    )));
+                }
+                ty
             } else {
                 return Err(CompilerError::new(
                     ErrorKind::InvalidSyntax,
@@ -303,9 +727,13 @@ fn analyze_statement(stmt: &Statement, symbol_table: &mut SymbolTable, file_path
                 )));
             };
             
-            if let Some(init) = initializer {
+            check_not_opaque_value(&ty, symbol_table, file_path)?;
+            check_array_length(&ty, symbol_table, file_path)?;
+            
+            // Without an annotation the type was just inferred from the initializer
+            if let (Some(init), Some(_)) = (initializer, var_type) {
                 // Special case: empty array literal with type annotation is allowed
-                if let (Expression::ArrayLiteral(elements), Type::Array(_)) = (init, &ty) {
+                if let (Expression::ArrayLiteral(elements), Type::Array(_, _)) = (init, &ty) {
                     if elements.is_empty() {
                         // Empty array literal with type annotation is OK
                     } else {
@@ -332,10 +760,34 @@ fn analyze_statement(stmt: &Statement, symbol_table: &mut SymbolTable, file_path
             
             let symbol = Symbol {
                 name: name.clone(),
-                symbol_type: SymbolType::Variable,
+                symbol_type: SymbolType::Variable { mutable: *mutable },
                 ty,
+                location: stmt_location.clone(),
+            };
+            symbol_table.insert(symbol)?;
+        }
+        Statement::LetTuple { names, mutable, initializer } => {
+            let elements = match infer_type(initializer, symbol_table, file_path)? {
+                Type::Tuple(elements) if elements.len() == names.len() => elements,
+                ty => {
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("cannot destructure `{:?}` into {} names", ty, names.len()),
+                        stmt_location,
+                    ).with_suggestion(Suggestion::simple(
+                        "give one name per tuple element, using `_` for elements you don't need"
+                    )));
+                }
             };
-            symbol_table.insert(symbol, file_path)?;
+            for (name, ty) in names.iter().zip(elements).filter(|(name, _)| *name != "_") {
+                let symbol = Symbol {
+                    name: name.clone(),
+                    symbol_type: SymbolType::Variable { mutable: *mutable },
+                    ty,
+                    location: stmt_location.clone(),
+                };
+                symbol_table.insert(symbol)?;
+            }
         }
         Statement::Const { name, var_type, initializer } => {
             let ty = var_type.clone().unwrap_or_else(|| infer_type(initializer, symbol_table, file_path).unwrap());
@@ -349,12 +801,37 @@ fn analyze_statement(stmt: &Statement, symbol_table: &mut SymbolTable, file_path
             
             let symbol = Symbol {
                 name: name.clone(),
-                symbol_type: SymbolType::Variable,
+                symbol_type: SymbolType::Variable { mutable: false },
                 ty,
+                location: stmt_location.clone(),
             };
-            symbol_table.insert(symbol, file_path)?;
+            symbol_table.insert(symbol)?;
         }
         Statement::Assignment { target, value } => {
+            let is_lvalue = match target {
+                Expression::Variable(_) | Expression::StructAccess { .. } => true,
+                Expression::ArrayAccess { index, .. } => !matches!(**index, Expression::Range { .. }),
+                Expression::Unary { operator: UnaryOp::Dereference, .. } => true,
+                _ => false,
+            };
+            if !is_lvalue {
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidOperation,
+                    "invalid assignment target".to_string(),
+                    stmt_location,
+                ).with_suggestion(Suggestion::simple(
+                    "only variables, array elements, struct fields and dereferenced pointers can be assigned to"
+                )));
+            }
+            if let Some(name) = read_only_ref(target, symbol_table) {
+                return Err(CompilerError::new(
+                    ErrorKind::ImmutableAssignment,
+                    format!("cannot assign through `ref` parameter `{}`", name),
+                    stmt_location,
+                ).with_suggestion(Suggestion::simple(
+                    format!("declare it as `mut ref {}` to let the function modify the caller's value", name)
+                )));
+            }
             let target_ty = infer_type(target, symbol_table, file_path)?;
             let value_ty = infer_type(value, symbol_table, file_path)?;
             if !types_compatible(&target_ty, &value_ty) {
@@ -365,6 +842,13 @@ fn analyze_statement(stmt: &Statement, symbol_table: &mut SymbolTable, file_path
             }
         }
         Statement::Return(value) => {
+            if symbol_table.inferred_returns.is_some() {
+                if let Some(expr) = value.as_ref().filter(|expr| !symbol_table.pending_returns.iter().any(|name| calls_function(expr, name))) {
+                    let ret_ty = infer_type(expr, symbol_table, file_path)?;
+                    symbol_table.inferred_returns.get_or_insert_with(Vec::new).push(ret_ty);
+                }
+                return Ok(());
+            }
             match expected_return {
                 Type::Void => {
                     if let Some(expr) = value {
@@ -408,6 +892,16 @@ fn analyze_statement(stmt: &Statement, symbol_table: &mut SymbolTable, file_path
                         "use a boolean expression in the if condition, such as a comparison or boolean variable"
                     )));
             }
+            if let Some(value) = constant_condition(condition, symbol_table) {
+                let branch = if value { "the `if` branch" } else { "the `else` branch, if any," };
+                symbol_table.warn(CompilerError::warning(
+                    ErrorKind::ConstantCondition,
+                    format!("`if` condition is always {}", value),
+                    stmt_location.clone(),
+                ).with_suggestion(Suggestion::simple(
+                    format!("remove the `if` and keep only {}", branch)
+                )));
+            }
             symbol_table.enter_scope();
             for stmt in then_branch {
                 analyze_statement(stmt, symbol_table, file_path, stmt_location.clone(), expected_return)?;
@@ -429,10 +923,23 @@ fn analyze_statement(stmt: &Statement, symbol_table: &mut SymbolTable, file_path
                         "use a boolean expression in the while condition, such as a comparison or boolean variable"
                     )));
             }
+            // `while true` is the usual way to spell an infinite loop
+            let spelled_true = matches!(condition, Expression::Literal(Literal::Bool(true)));
+            if let (Some(value), false) = (constant_condition(condition, symbol_table), spelled_true) {
+                let suggestion = if value { "write `loop { ... }` for a loop that runs until it breaks" } else { "the body never runs; remove the loop" };
+                symbol_table.warn(CompilerError::warning(
+                    ErrorKind::ConstantCondition,
+                    format!("`while` condition is always {}", value),
+                    stmt_location.clone(),
+                ).with_suggestion(Suggestion::simple(suggestion)));
+            }
+            check_continue_skips_update(condition, body, symbol_table, &stmt_location);
             symbol_table.enter_scope();
+            symbol_table.loop_breaks.push(("while", None));
             for stmt in body {
                 analyze_statement(stmt, symbol_table, file_path, stmt_location.clone(), expected_return)?;
             }
+            symbol_table.loop_breaks.pop();
             symbol_table.exit_scope();
         }
         Statement::For { variable, iterable, body } => {
@@ -441,7 +948,7 @@ fn analyze_statement(stmt: &Statement, symbol_table: &mut SymbolTable, file_path
             // Validate that iterable is a range or array
             let iterable_ty = infer_type(iterable, symbol_table, file_path)?;
             let loop_var_ty = match &iterable_ty {
-                Type::Array(elem_ty) => *elem_ty.clone(),
+                Type::Array(elem_ty, _) => *elem_ty.clone(),
                 Type::DynamicArray(elem_ty) => *elem_ty.clone(),
                 Type::Void => {
                     // Range expressions have Void type - loop variable is int
@@ -463,19 +970,104 @@ fn analyze_statement(stmt: &Statement, symbol_table: &mut SymbolTable, file_path
             let symbol = Symbol {
                 name: variable.clone(),
                 ty: loop_var_ty,
-                symbol_type: SymbolType::Variable,
+                symbol_type: SymbolType::Variable { mutable: false },
+                location: stmt_location.clone(),
             };
-            symbol_table.insert(symbol, file_path)?;
+            symbol_table.insert(symbol)?;
             
+            symbol_table.loop_breaks.push(("for", None));
+            for stmt in body {
+                analyze_statement(stmt, symbol_table, file_path, stmt_location.clone(), expected_return)?;
+            }
+            symbol_table.loop_breaks.pop();
+            symbol_table.exit_scope();
+        }
+        Statement::Break(value) => {
+            // `while let` is a `loop` by the time it gets here, so it counts as well
+            if symbol_table.loop_breaks.is_empty() {
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidOperation,
+                    "`break` outside of a loop".to_string(),
+                    stmt_location,
+                ).with_suggestion(Suggestion::simple(
+                    "`break` leaves a `while`, `for` or `loop`; use `return` to leave the function"
+                )));
+            }
+            let (kind, breaks) = symbol_table.loop_breaks.last().unwrap();
+            let (kind, yields_value) = (*kind, breaks.is_some());
+            match value {
+                Some(expr) if yields_value => {
+                    let value_ty = infer_type(expr, symbol_table, file_path)?;
+                    if let Some((_, Some(types))) = symbol_table.loop_breaks.last_mut() {
+                        types.push(value_ty);
+                    }
+                }
+                Some(_) => {
+                    let message = if kind == "loop" {
+                        "`break` with a value in a `loop` whose value is not used".to_string()
+                    } else {
+                        format!("`break` with a value in a `{}` loop", kind)
+                    };
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidOperation,
+                        message,
+                        stmt_location,
+                    ).with_suggestion(Suggestion::with_example(
+                        "only a `loop` whose value is used can break with a value",
+                        "let found = loop { if done { break value; } };"
+                    )));
+                }
+                None if yields_value => {
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidOperation,
+                        "`break` without a value in a `loop` used as a value".to_string(),
+                        stmt_location,
+                    ).with_suggestion(Suggestion::simple(
+                        "every `break` of this loop must provide its value, as in `break value;`"
+                    )));
+                }
+                None => {}
+            }
+        }
+        Statement::Continue => {
+            if symbol_table.loop_breaks.is_empty() {
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidOperation,
+                    "`continue` outside of a loop".to_string(),
+                    stmt_location,
+                ).with_suggestion(Suggestion::simple(
+                    "`continue` starts the next iteration of a `while`, `for` or `loop`"
+                )));
+            }
+        }
+        Statement::Expression(Expression::Loop { body }) => {
+            // Used as a statement, the loop produces no value
+            symbol_table.enter_scope();
+            symbol_table.loop_breaks.push(("loop", None));
             for stmt in body {
                 analyze_statement(stmt, symbol_table, file_path, stmt_location.clone(), expected_return)?;
             }
+            symbol_table.loop_breaks.pop();
             symbol_table.exit_scope();
         }
-        Statement::Break | Statement::Continue => {
-            // Break and continue are valid statements
-            // Note: We could add loop context tracking here to ensure they're only used in loops
-            // For now, we'll let the code generator handle that
+        Statement::Expression(Expression::Match { scrutinee, arms }) => {
+            // Used as a statement, arms may be blocks and need not agree on a type
+            let scrutinee_ty = check_match_patterns(scrutinee, arms, symbol_table, file_path)?;
+            for arm in arms {
+                symbol_table.enter_scope();
+                bind_arm_pattern(&arm.pattern, &scrutinee_ty, symbol_table, file_path)?;
+                match &arm.body {
+                    ArmBody::Expression(expression) => {
+                        infer_type(expression, symbol_table, file_path)?;
+                    }
+                    ArmBody::Block(body) => {
+                        for stmt in body {
+                            analyze_statement(stmt, symbol_table, file_path, stmt_location.clone(), expected_return)?;
+                        }
+                    }
+                }
+                symbol_table.exit_scope();
+            }
         }
         Statement::Expression(expr) => {
             let _ = infer_type(expr, symbol_table, file_path)?;
@@ -580,6 +1172,7 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
             Literal::Bool(_) => Ok(Type::Bool),
             Literal::Char(_) => Ok(Type::Char),
             Literal::String(_) => Ok(Type::String),
+            Literal::Nil => Ok(Type::Nil),
         },
         Expression::Variable(name) => {
             if let Some(symbol) = symbol_table.lookup(name) {
@@ -590,7 +1183,15 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                         ty = Type::String;
                     }
                 }
+                // A function named as a value is a pointer to it
+                if symbol.symbol_type == SymbolType::Function {
+                    let params = symbol_table.function_params.get(name).cloned().unwrap_or_default();
+                    ty = Type::Function(params, Box::new(ty));
+                }
                 Ok(ty)
+            } else if let Some(field_ty) = implicit_self_field(name, symbol_table) {
+                // In a method, a bare field name that is no variable stands for `self.field`
+                Ok(field_ty)
             } else {
                 let location = SourceLocation::new(file_path.clone(), 0, 0);
                 Err(undefined_variable(name, location))
@@ -600,6 +1201,22 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
             let left_ty = infer_type(left, symbol_table, file_path)?;
             let right_ty = infer_type(right, symbol_table, file_path)?;
             
+            // Operator overloading: `a + b` on a struct with an `add` method calls it
+            if let Some(method) = operator.method_name() {
+                if let Some((type_name, func)) = impl_method_of(&left_ty, method, symbol_table) {
+                    let ty = check_impl_call(&func, &type_name, std::slice::from_ref(&**right), true, symbol_table, file_path)?;
+                    if method == "eq" && ty != Type::Bool {
+                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        return Err(CompilerError::new(
+                            ErrorKind::TypeMismatch,
+                            format!("`{}::eq` must return `bool` to overload `==`, but returns `{:?}`", type_name, ty),
+                            location,
+                        ));
+                    }
+                    return Ok(ty);
+                }
+            }
+            
             // Check for division/modulo by constant zero
             if matches!(operator, BinaryOp::Divide | BinaryOp::Modulo) {
                 if let Expression::Literal(Literal::Integer(0)) = &**right {
@@ -617,9 +1234,15 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
             
             match operator {
                 BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => {
-                    if left_ty == Type::Int && right_ty == Type::Int {
-                        Ok(Type::Int)
-                    } else if (left_ty == Type::Int || left_ty == Type::Float) && (right_ty == Type::Int || right_ty == Type::Float) {
+                    let is_numeric = |t: &Type| t.integer_rank().is_some() || *t == Type::Float;
+                    if let (Some(left_rank), Some(right_rank)) = (left_ty.integer_rank(), right_ty.integer_rank()) {
+                        // Mixed-width integer arithmetic widens to the wider type
+                        Ok(if left_rank >= right_rank { left_ty } else { right_ty })
+                    } else if *operator == BinaryOp::Add && left_ty == Type::String && right_ty == Type::String {
+                        // String concatenation
+                        Ok(Type::String)
+                    } else if is_numeric(&left_ty) && is_numeric(&right_ty) {
+                        // Floats dominate integers
                         Ok(Type::Float)
                     } else {
                         let location = SourceLocation::new(file_path.clone(), 0, 0);
@@ -628,22 +1251,54 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                             format!("cannot apply arithmetic operator to types `{:?}` and `{:?}`", left_ty, right_ty),
                             location,
                         ).with_suggestion(Suggestion::simple(
-                            "arithmetic operators require numeric operands (int or float)"
+                            "arithmetic operators require numeric operands (int, long or float)"
                         )))
                     }
                 }
                 BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => {
-                    if types_compatible(&left_ty, &right_ty) {
+                    // A fixed array is a pointer in C, so `==` would only compare addresses
+                    if matches!(left_ty, Type::Array(..)) || matches!(right_ty, Type::Array(..)) {
+                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        return Err(CompilerError::new(
+                            ErrorKind::InvalidOperation,
+                            format!("cannot compare fixed-size arrays `{:?}` and `{:?}`", left_ty, right_ty),
+                            location,
+                        ).with_suggestion(Suggestion::simple(
+                            "compare the elements in a loop, or use `DynamicArray[T]` values, which `==` compares element by element"
+                        )));
+                    }
+                    // Option/Result and dynamic arrays support equality only; there is no ordering between them
+                    let is_generic = |t: &Type| matches!(t, Type::Generic { .. });
+                    let is_dynamic_array = |t: &Type| matches!(t, Type::DynamicArray(_));
+                    let unordered = |t: &Type| is_generic(t) || is_dynamic_array(t);
+                    if (unordered(&left_ty) || unordered(&right_ty)) && !matches!(operator, BinaryOp::Equal | BinaryOp::NotEqual) {
+                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        let suggestion = if is_generic(&left_ty) || is_generic(&right_ty) {
+                            "only `==` and `!=` are supported; use `match` to compare the payloads"
+                        } else {
+                            "only `==` and `!=` are supported; compare the elements in a loop"
+                        };
+                        return Err(CompilerError::new(
+                            ErrorKind::InvalidOperation,
+                            format!("cannot order values of type `{:?}`", if unordered(&left_ty) { &left_ty } else { &right_ty }),
+                            location,
+                        ).with_suggestion(Suggestion::simple(suggestion)));
+                    }
+                    let both_integers = left_ty.integer_rank().is_some() && right_ty.integer_rank().is_some();
+                    if types_compatible(&left_ty, &right_ty) || both_integers {
                         Ok(Type::Bool)
                     } else {
                         let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        let suggestion = if left_ty == Type::Nil || right_ty == Type::Nil {
+                            "only pointers and strings can be null; `is null` and `== nil` need one of those"
+                        } else {
+                            "comparison operators require operands of compatible types"
+                        };
                         Err(CompilerError::new(
                             ErrorKind::InvalidOperation,
                             format!("cannot compare `{:?}` with `{:?}`", left_ty, right_ty),
                             location,
-                        ).with_suggestion(Suggestion::simple(
-                            "comparison operators require operands of compatible types"
-                        )))
+                        ).with_suggestion(Suggestion::simple(suggestion)))
                     }
                 }
                 BinaryOp::And | BinaryOp::Or => {
@@ -666,7 +1321,7 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
             let op_ty = infer_type(operand, symbol_table, file_path)?;
             match operator {
                 UnaryOp::Negate => {
-                    if op_ty == Type::Int || op_ty == Type::Float {
+                    if op_ty.integer_rank().is_some() || op_ty == Type::Float {
                         Ok(op_ty)
                     } else {
                         let location = SourceLocation::new(file_path.clone(), 0, 0);
@@ -718,17 +1373,30 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                 Expression::Variable(name) => {
                     // Regular function call
                     if name == "print" || name == "println" {
-                        // Built-in print functions - accept any argument type
-                        Ok(Type::Void)
-                    } else if name == "len" {
-                        // Built-in len function - takes a string, returns int
-                        if arguments.len() != 1 {
+                        // Built-in print functions - accept any argument type, at most one argument
+                        if arguments.len() > 1 {
                             let location = SourceLocation::new(file_path.clone(), 0, 0);
                             return Err(CompilerError::new(
                                 ErrorKind::WrongArgumentCount,
-                                "len() function expects exactly 1 argument".to_string(),
+                                format!("{}() expects at most 1 argument, got {}", name, arguments.len()),
                                 location,
-                            ));
+                            ).with_suggestion(Suggestion::simple(
+                                "use string interpolation to print several values, e.g. `println(\"x = :x:\")`"
+                            )));
+                        }
+                        for arg in arguments {
+                            infer_type(arg, symbol_table, file_path)?;
+                        }
+                        Ok(Type::Void)
+                    } else if name == "len" {
+                        // Built-in len function - takes a string, returns int
+                        if arguments.len() != 1 {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::WrongArgumentCount,
+                                "len() function expects exactly 1 argument".to_string(),
+                                location,
+                            ));
                         }
                         // Validate argument is a string
                         let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
@@ -743,10 +1411,196 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                             )));
                         }
                         Ok(Type::Int)
+                    } else if name == "hex" {
+                        // Built-in hex function - takes an int, returns its hexadecimal digits
+                        if arguments.len() != 1 {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::WrongArgumentCount,
+                                "hex() function expects exactly 1 argument".to_string(),
+                                location,
+                            ));
+                        }
+                        let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+                        if arg_ty != Type::Int {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::TypeMismatch,
+                                format!("hex() expects an int argument, got `{:?}`", arg_ty),
+                                location,
+                            ).with_suggestion(Suggestion::simple(
+                                "pass an int to hex() to format it in base 16"
+                            )));
+                        }
+                        Ok(Type::String)
+                    } else if name == "pad" {
+                        // Built-in pad function - pads a string with spaces to a width; a negative width pads on the left
+                        if arguments.len() != 2 {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::WrongArgumentCount,
+                                "pad() function expects exactly 2 arguments".to_string(),
+                                location,
+                            ));
+                        }
+                        let value_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+                        let width_ty = infer_type(&arguments[1], symbol_table, file_path)?;
+                        if value_ty != Type::String || width_ty != Type::Int {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::TypeMismatch,
+                                format!("pad() expects a string and an int width, got `{:?}` and `{:?}`", value_ty, width_ty),
+                                location,
+                            ).with_suggestion(Suggestion::with_example(
+                                "pad(s, 8) left-aligns s in 8 columns, pad(s, -8) right-aligns it",
+                                "println(pad(name, 10) + pad(score, -6));"
+                            )));
+                        }
+                        Ok(Type::String)
+                    } else if name == "read_lines" {
+                        // Built-in read_lines function - takes a path, returns the file's lines
+                        if arguments.len() != 1 {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::WrongArgumentCount,
+                                "read_lines() function expects exactly 1 argument".to_string(),
+                                location,
+                            ));
+                        }
+                        let path_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+                        if path_ty != Type::String {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::TypeMismatch,
+                                format!("read_lines() expects a string path, got `{:?}`", path_ty),
+                                location,
+                            ).with_suggestion(Suggestion::simple(
+                                "pass the path of the file to read as a string"
+                            )));
+                        }
+                        Ok(Type::DynamicArray(Box::new(Type::String)))
+                    } else if name == "env" {
+                        // Built-in env function - takes a variable name, returns Option<string>
+                        if arguments.len() != 1 {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::WrongArgumentCount,
+                                "env() function expects exactly 1 argument".to_string(),
+                                location,
+                            ));
+                        }
+                        let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+                        if arg_ty != Type::String {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::TypeMismatch,
+                                format!("env() expects a string argument, got `{:?}`", arg_ty),
+                                location,
+                            ).with_suggestion(Suggestion::simple(
+                                "pass the environment variable's name as a string"
+                            )));
+                        }
+                        Ok(Type::Generic { name: "Option".to_string(), type_params: vec![Type::String] })
+                    } else if name == "exit" {
+                        // Built-in exit(code) - terminates the program; counts as a return in block_returns
+                        if arguments.len() != 1 {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::WrongArgumentCount,
+                                "exit() function expects exactly 1 argument".to_string(),
+                                location,
+                            ));
+                        }
+                        let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+                        if arg_ty != Type::Int {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::TypeMismatch,
+                                format!("exit() expects an int exit code, got `{:?}`", arg_ty),
+                                location,
+                            ));
+                        }
+                        Ok(Type::Void)
+                    } else if name == "assert" {
+                        // Built-in assert(condition) or assert(condition, message)
+                        if arguments.is_empty() || arguments.len() > 2 {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::WrongArgumentCount,
+                                format!("assert() expects 1 or 2 arguments, got {}", arguments.len()),
+                                location,
+                            ));
+                        }
+                        let condition_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+                        if condition_ty != Type::Bool {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::TypeMismatch,
+                                format!("assert() expects a bool condition, got `{:?}`", condition_ty),
+                                location,
+                            ));
+                        }
+                        if let Some(message) = arguments.get(1) {
+                            let message_ty = infer_type(message, symbol_table, file_path)?;
+                            if !types_compatible(&Type::String, &message_ty) {
+                                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                                return Err(CompilerError::new(
+                                    ErrorKind::TypeMismatch,
+                                    format!("assert() expects a string message, got `{:?}`", message_ty),
+                                    location,
+                                ));
+                            }
+                        }
+                        Ok(Type::Void)
+                    } else if name == "in_range" {
+                        // Built-in in_range(x, lo, hi) - inclusive bounds check, returns bool
+                        if arguments.len() != 3 {
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            return Err(CompilerError::new(
+                                ErrorKind::WrongArgumentCount,
+                                format!("in_range() function expects exactly 3 arguments, got {}", arguments.len()),
+                                location,
+                            ));
+                        }
+                        for arg in arguments {
+                            let arg_ty = infer_type(arg, symbol_table, file_path)?;
+                            if arg_ty != Type::Int && arg_ty != Type::Float {
+                                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                                return Err(CompilerError::new(
+                                    ErrorKind::TypeMismatch,
+                                    format!("in_range() expects numeric arguments, got `{:?}`", arg_ty),
+                                    location,
+                                ).with_suggestion(Suggestion::simple(
+                                    "pass the value and its inclusive lower and upper bounds as ints or floats"
+                                )));
+                            }
+                        }
+                        Ok(Type::Bool)
                     } else if let Some(symbol) = symbol_table.lookup(name) {
                         if symbol.symbol_type == SymbolType::Function {
+                            let ty = symbol.ty.clone();
+                            if let Some(ext_func) = symbol_table.extern_functions.get(name).cloned() {
+                                check_extern_arguments(&ext_func, arguments, symbol_table, file_path)?;
+                            }
+                            if let Some(func) = symbol_table.const_generic_functions.get(name).cloned() {
+                                check_const_arguments(&func, arguments, symbol_table, file_path)?;
+                            }
+                            if let Some(params) = symbol_table.ref_functions.get(name).cloned() {
+                                check_ref_arguments(name, &params, arguments, symbol_table, file_path)?;
+                            }
+                            if let Some(message) = symbol_table.deprecated_functions.get(name).cloned() {
+                                let message = match message {
+                                    Some(message) => format!("`{}` is deprecated: {}", name, message),
+                                    None => format!("`{}` is deprecated", name),
+                                };
+                                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                                symbol_table.warn(CompilerError::warning(ErrorKind::DeprecatedFunction, message, location));
+                            }
                             // TODO: check argument types
-                            Ok(symbol.ty.clone())
+                            Ok(ty)
+                        } else if let Type::Function(params, ret) = symbol.ty.clone() {
+                            check_function_pointer_arguments(name, &params, arguments, symbol_table, file_path)?;
+                            Ok(*ret)
                         } else {
                             let location = SourceLocation::new(file_path.clone(), 0, 0);
                             Err(CompilerError::new(
@@ -776,7 +1630,10 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                     // Check if this is a module-qualified call like math.add or token.TK_INTEGER
                     if let Expression::Variable(module_name) = &**object {
                         let qualified_name = format!("{}.{}", module_name, field);
-                        if let Some(symbol) = symbol_table.lookup(&qualified_name) {
+                        // In a method, a bare field name that is no variable is `self.field`, even if
+                        // a module has the same name
+                        let is_field = symbol_table.lookup(module_name).is_none() && implicit_self_field(module_name, symbol_table).is_some();
+                        if let Some(symbol) = symbol_table.lookup(&qualified_name).filter(|_| !is_field) {
                             if symbol.symbol_type == SymbolType::Function {
                                 // TODO: check argument types
                                 Ok(symbol.ty.clone())
@@ -790,8 +1647,8 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                                     "only functions can be called with parentheses"
                                 )))
                             }
-                        } else if symbol_table.lookup(module_name).is_some() {
-                            // Object is a known variable, check if it's a method call
+                        } else if is_field || symbol_table.lookup(module_name).is_some() {
+                            // Object is a known variable or field, check if it's a method call
                             let mut object_ty = infer_type(object, symbol_table, file_path)?;
                             
                             // Normalize str to String type
@@ -838,12 +1695,12 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                                     }
                                     Ok(Type::Int)
                                 }
-                                (&Type::String, "trim") => {
+                                (&Type::String, "trim" | "trim_start" | "trim_end" | "to_upper" | "to_lower") => {
                                     if !arguments.is_empty() {
                                         let location = SourceLocation::new(file_path.clone(), 0, 0);
                                         return Err(CompilerError::new(
                                             ErrorKind::WrongArgumentCount,
-                                            format!("trim() expects 0 arguments, got {}", arguments.len()),
+                                            format!("{}() expects 0 arguments, got {}", field, arguments.len()),
                                             location,
                                         ));
                                     }
@@ -861,7 +1718,7 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                                     Ok(Type::DynamicArray(Box::new(Type::String)))
                                 }
                                 // Dynamic array methods
-                                (&Type::DynamicArray(ref elem_ty), "push") => {
+                                (Type::DynamicArray(elem_ty), "push") => {
                                     // push(element) - validate argument count and type
                                     if arguments.len() != 1 {
                                         let location = SourceLocation::new(file_path.clone(), 0, 0);
@@ -872,7 +1729,7 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                                         ));
                                     }
                                     let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
-                                    if !types_compatible(&*elem_ty, &arg_ty) {
+                                    if !types_compatible(elem_ty, &arg_ty) {
                                         let location = SourceLocation::new(file_path.clone(), 0, 0);
                                         return Err(CompilerError::new(
                                             ErrorKind::TypeMismatch,
@@ -885,7 +1742,7 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                                     // push returns the array (for chaining)
                                     Ok(object_ty)
                                 }
-                                (&Type::DynamicArray(ref elem_ty), "pop") => {
+                                (Type::DynamicArray(elem_ty), "pop") => {
                                     // pop() - validate no arguments
                                     if arguments.len() != 0 {
                                         let location = SourceLocation::new(file_path.clone(), 0, 0);
@@ -898,18 +1755,46 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                                     // pop returns the element type
                                     Ok(*elem_ty.clone())
                                 }
-                                (&Type::DynamicArray(_), "length") => {
-                                    // length() - validate no arguments
+                                (Type::Int | Type::Long | Type::Float | Type::Bool | Type::Char, "to_string") => {
+                                    check_to_string_arguments(arguments, SourceLocation::new(file_path.clone(), 0, 0))
+                                }
+                                (Type::DynamicArray(elem_ty), "to_string") if **elem_ty == Type::Char => {
+                                    if !arguments.is_empty() {
+                                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                                        return Err(CompilerError::new(
+                                            ErrorKind::WrongArgumentCount,
+                                            format!("to_string() expects 0 arguments, got {}", arguments.len()),
+                                            location,
+                                        ));
+                                    }
+                                    // Copies the characters into a null-terminated string
+                                    Ok(Type::String)
+                                }
+                                (Type::DynamicArray(elem_ty), "map" | "filter") => {
+                                    check_map_filter(field, elem_ty, arguments, symbol_table, file_path)
+                                }
+                                (Type::DynamicArray(elem_ty), "length" | "len" | "data") => {
+                                    // length()/len() and data() - validate no arguments
                                     if arguments.len() != 0 {
                                         let location = SourceLocation::new(file_path.clone(), 0, 0);
                                         return Err(CompilerError::new(
                                             ErrorKind::WrongArgumentCount,
-                                            format!("length() expects 0 arguments, got {}", arguments.len()),
+                                            format!("{}() expects 0 arguments, got {}", field, arguments.len()),
                                             location,
                                         ));
                                     }
-                                    // length returns int
-                                    Ok(Type::Int)
+                                    // length returns int; data the elements' storage, e.g. for C functions
+                                    if field == "data" {
+                                        Ok(Type::Pointer(elem_ty.clone()))
+                                    } else {
+                                        Ok(Type::Int)
+                                    }
+                                }
+                                (&Type::Struct(ref type_name), _) | (&Type::Enum(ref type_name), _)
+                                    if symbol_table.get_impl_function(type_name, field).is_some() =>
+                                {
+                                    let func = symbol_table.get_impl_function(type_name, field).unwrap().clone();
+                                    check_impl_call(&func, type_name, arguments, true, symbol_table, file_path)
                                 }
                                 _ => {
                                     let location = SourceLocation::new(file_path.clone(), 0, 0);
@@ -935,17 +1820,35 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                             )))
                         }
                     } else {
-                        // Regular struct field access used as function call - not allowed
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        Err(CompilerError::new(
-                            ErrorKind::InvalidOperation,
-                            "cannot call struct field as function".to_string(),
-                            location,
-                        ).with_suggestion(Suggestion::simple(
-                            "struct fields cannot be called like functions"
-                        )))
+                        let object_ty = infer_type(object, symbol_table, file_path)?;
+                        if let Some((type_name, func)) = impl_method_of(&object_ty, field, symbol_table) {
+                            // Method call on an arbitrary expression: self.pos.length()
+                            check_impl_call(&func, &type_name, arguments, true, symbol_table, file_path)
+                        } else if field == "to_string" && matches!(object_ty, Type::Int | Type::Long | Type::Float | Type::Bool | Type::Char) {
+                            // (a + b).to_string()
+                            check_to_string_arguments(arguments, SourceLocation::new(file_path.clone(), 0, 0))
+                        } else if object_ty == Type::String || (field == "data" && matches!(object_ty, Type::DynamicArray(_))) {
+                            // String methods on any string expression: "abc".length(), s.trim().to_upper(),
+                            // and the storage of any array expression: make_vec().data()
+                            infer_method_call(object_ty, field, arguments, symbol_table, file_path)
+                        } else {
+                            // Regular struct field access used as function call - not allowed
+                            let location = SourceLocation::new(file_path.clone(), 0, 0);
+                            Err(CompilerError::new(
+                                ErrorKind::InvalidOperation,
+                                "cannot call struct field as function".to_string(),
+                                location,
+                            ).with_suggestion(Suggestion::simple(
+                                "struct fields cannot be called like functions"
+                            )))
+                        }
                     }
                 }
+                Expression::EnumAccess { enum_name, variant } if symbol_table.get_impl_function(enum_name, variant).is_some() => {
+                    // Associated function call: Point::new(1, 2)
+                    let func = symbol_table.get_impl_function(enum_name, variant).unwrap().clone();
+                    check_impl_call(&func, enum_name, arguments, false, symbol_table, file_path)
+                }
                 Expression::EnumAccess { enum_name, variant } => {
                     // Enum variant constructor call: Option::Some(42)
                     // Check if this is a built-in generic type
@@ -1017,6 +1920,13 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                 }
             }
         }
+        Expression::Tuple(elements) => {
+            let mut types = Vec::new();
+            for element in elements {
+                types.push(infer_type(element, symbol_table, file_path)?);
+            }
+            Ok(Type::Tuple(types))
+        }
         Expression::ArrayLiteral(elements) => {
             if elements.is_empty() {
                 let location = SourceLocation::new(file_path.clone(), 0, 0);
@@ -1042,7 +1952,7 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                     )));
                 }
             }
-            Ok(Type::Array(Box::new(first_ty)))
+            Ok(Type::Array(Box::new(first_ty), Some(ArrayLength::Literal(elements.len()))))
         }
         Expression::DynamicArrayLiteral { element_type, elements } => {
             // Check that all elements match the declared element type
@@ -1061,7 +1971,7 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
             }
             Ok(Type::DynamicArray(element_type.clone()))
         }
-        Expression::StructLiteral { name, fields } => {
+        Expression::StructLiteral { name, fields, base } => {
             // Ensure struct exists and fields match
             if let Some(symbol) = symbol_table.lookup(name) {
                 if symbol.symbol_type != SymbolType::Struct {
@@ -1103,10 +2013,67 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                 }
             }
 
+            if let Some(base_expr) = base {
+                // Spread base must be the same struct type; it supplies all omitted fields
+                let base_ty = infer_type(base_expr, symbol_table, file_path)?;
+                if base_ty != Type::Struct(name.clone()) {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("struct spread base must be `{}`, found `{:?}`", name, base_ty),
+                        location,
+                    ));
+                }
+            } else if let Some(defs) = symbol_table.struct_defs.get(name) {
+                // Without a spread every field without a default must be initialized explicitly
+                let defaults = symbol_table.struct_field_defaults.get(name);
+                let mut missing: Vec<&String> = defs
+                    .keys()
+                    .filter(|f| !fields.iter().any(|(provided, _)| provided == *f))
+                    .filter(|f| !defaults.is_some_and(|d| d.contains(*f)))
+                    .collect();
+                if !missing.is_empty() {
+                    missing.sort();
+                    let missing_list = missing.iter().map(|f| format!("`{}`", f)).collect::<Vec<_>>().join(", ");
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidOperation,
+                        format!("missing field(s) {} in initializer of struct `{}`", missing_list, name),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        "initialize every field, give it a default in the struct, or use `..base` to copy the remaining fields from another value"
+                    )));
+                }
+            }
+
             Ok(Type::Struct(name.clone()))
         }
         Expression::ArrayAccess { array, index } => {
             let array_ty = infer_type(array, symbol_table, file_path)?;
+            // Slicing `a[start..end]` yields a new array or string of the same type
+            if let Expression::Range { start, end } = &**index {
+                for bound in [start, end] {
+                    let bound_ty = infer_type(bound, symbol_table, file_path)?;
+                    if bound_ty != Type::Int {
+                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        return Err(type_mismatch("int", &format!("{:?}", bound_ty), location)
+                            .with_suggestion(Suggestion::simple(
+                                "slice bounds must be integers"
+                            )));
+                    }
+                }
+                if !matches!(array_ty, Type::DynamicArray(_) | Type::String) {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidOperation,
+                        format!("cannot slice type `{:?}`", array_ty),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        "only dynamic arrays and strings can be sliced with `[start..end]`"
+                    )));
+                }
+                return Ok(array_ty);
+            }
             let index_ty = infer_type(index, symbol_table, file_path)?;
             if index_ty != Type::Int {
                 let location = SourceLocation::new(file_path.clone(), 0, 0);
@@ -1116,21 +2083,22 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                     )));
             }
             
-            // Warn about negative constant indices
-            if let Expression::Literal(Literal::Integer(n)) = &**index {
-                if *n < 0 {
+            // Negative constant indices count from the end, which needs a known runtime
+            // length: allowed for dynamic arrays and strings, rejected otherwise
+            if let Some(n) = negative_constant_index(index) {
+                if !matches!(array_ty, Type::DynamicArray(_) | Type::String) {
                     let location = SourceLocation::new(file_path.clone(), 0, 0);
                     return Err(CompilerError::new(
                         ErrorKind::InvalidOperation,
                         format!("array index cannot be negative (got {})", n),
                         location,
                     ).with_suggestion(Suggestion::simple(
-                        "array indices must be non-negative integers"
+                        "negative indices are only supported on dynamic arrays and strings"
                     )));
                 }
             }
             match array_ty {
-                Type::Array(elem_ty) => Ok(*elem_ty),
+                Type::Array(elem_ty, _) => Ok(*elem_ty),
                 Type::DynamicArray(elem_ty) => Ok(*elem_ty),
                 Type::Pointer(elem_ty) => Ok(*elem_ty),
                 Type::String => Ok(Type::Char),
@@ -1149,6 +2117,16 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
         Expression::StructAccess { object, field } => {
             let obj_ty = infer_type(object, symbol_table, file_path)?;
             if let Type::Struct(struct_name) = obj_ty {
+                if symbol_table.opaque_types.contains(&struct_name) {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidOperation,
+                        format!("cannot access field `{}` of opaque extern type `{}`", field, struct_name),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        "opaque types declared with `extern struct` can only be passed to C functions by pointer"
+                    )));
+                }
                 if let Some(fty) = symbol_table.get_struct_field_type(&struct_name, field) {
                     Ok(fty.clone())
                 } else {
@@ -1172,14 +2150,56 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                 )))
             }
         }
+        Expression::OptionalField { object, field } => {
+            // `a?.field` reads through an Option's value or a pointer that may be null
+            let obj_ty = infer_type(object, symbol_table, file_path)?;
+            let target = match &obj_ty {
+                Type::Generic { name, type_params } if name == "Option" && type_params.len() == 1 => type_params.first(),
+                Type::Pointer(pointee) => Some(&**pointee),
+                _ => None,
+            };
+            let Some(Type::Struct(struct_name)) = target else {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidOperation,
+                    format!("`?.` needs an Option or a pointer holding a struct, got `{:?}`", obj_ty),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "use `.` to access the field of a struct value"
+                )));
+            };
+            match symbol_table.get_struct_field_type(struct_name, field) {
+                Some(fty) => Ok(Type::Generic { name: "Option".to_string(), type_params: vec![fty.clone()] }),
+                None => {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    Err(CompilerError::new(
+                        ErrorKind::UndefinedVariable,
+                        format!("unknown field `{}.{}`", struct_name, field),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        "check the field name or struct definition"
+                    )))
+                }
+            }
+        }
         Expression::New(expr) => {
             // new returns a pointer to the allocated type
             let inner_ty = infer_type(expr, symbol_table, file_path)?;
             Ok(Type::Pointer(Box::new(inner_ty)))
         }
         Expression::Delete(expr) => {
-            // delete returns void
-            let _ = infer_type(expr, symbol_table, file_path)?;
+            // delete frees memory obtained from `new`, so it needs a pointer; returns void
+            let ty = infer_type(expr, symbol_table, file_path)?;
+            if !matches!(ty, Type::Pointer(_)) {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("cannot delete a value of type `{:?}`", ty),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "delete only accepts pointers returned by `new`"
+                )));
+            }
             Ok(Type::Void)
         }
         Expression::Range { start, end } => {
@@ -1188,14 +2208,47 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
             let _ = infer_type(end, symbol_table, file_path)?;
             Ok(Type::Void) // ranges don't have a specific type
         }
+        Expression::AssertType { expression, expected } => {
+            // A check on inference itself: the types must agree exactly, not just be compatible.
+            // Annotations spell enums as struct types and `str` as a struct
+            let actual = infer_type(expression, symbol_table, file_path)?;
+            let normalize = |ty: &Type| match ty {
+                Type::Struct(name) if name == "str" => Type::String,
+                Type::Struct(name) if symbol_table.enum_defs.contains_key(name) => Type::Enum(name.clone()),
+                _ => ty.clone(),
+            };
+            if normalize(&actual) != normalize(expected) {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("assert_type failed: expression has type `{:?}`, expected `{:?}`", actual, expected),
+                    location,
+                ));
+            }
+            Ok(actual)
+        }
         Expression::Cast { expression, target_type } => {
             // Type casting: expr as Type
             let expr_ty = infer_type(expression, symbol_table, file_path)?;
             
+            // Enum annotations parse as struct types; resolve names that are enums
+            let as_enum = |ty: &Type| match ty {
+                Type::Enum(name) | Type::Struct(name) if symbol_table.enum_defs.contains_key(name) => Some(name.clone()),
+                _ => None,
+            };
+            let (from_enum, to_enum) = (as_enum(&expr_ty), as_enum(target_type));
+            
             // Check if the cast is valid
             let valid_cast = match (&expr_ty, target_type) {
+                // Enums convert to and from their integer values
+                (_, Type::Int) if from_enum.is_some() => true,
+                (Type::Int, _) if to_enum.is_some() => true,
+                
+
                 // Numeric conversions
-                (Type::Int, Type::Int) |
+                (Type::Int | Type::Long, Type::Int | Type::Long) |
+                (Type::Long, Type::Float) |
+                (Type::Float, Type::Long) |
                 (Type::Int, Type::Float) |
                 (Type::Int, Type::Char) |
                 (Type::Float, Type::Int) |
@@ -1211,11 +2264,44 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
                 // String to pointer conversions
                 (Type::String, Type::Pointer(inner)) if **inner == Type::Char => true,
                 
+                // Copying conversions between strings and char vectors
+                (Type::String, Type::DynamicArray(elem)) | (Type::DynamicArray(elem), Type::String) => **elem == Type::Char,
+                
                 // Allow casting between any two types (unsafe cast)
                 // In a production compiler, you might want to restrict this more
                 _ => false,
             };
             
+            if let (Type::Pointer(from), Type::Pointer(to)) = (&expr_ty, target_type) {
+                if let (Type::Struct(from_name), Type::Struct(to_name)) = (&**from, &**to) {
+                    if from_name != to_name {
+                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        symbol_table.warn(CompilerError::warning(
+                            ErrorKind::PointerReinterpretCast,
+                            format!("casting `*{}` to `*{}` reinterprets the memory as a different struct", from_name, to_name),
+                            location,
+                        ).with_suggestion(Suggestion::simple(
+                            "make sure both structs share the same layout, as C does no checking here"
+                        )));
+                    }
+                }
+            }
+            
+            // A constant must be the value of one of the enum's variants
+            if let (Some(enum_name), Expression::Literal(Literal::Integer(value))) = (&to_enum, &**expression) {
+                let is_variant = symbol_table.enum_defs.get(enum_name).is_some_and(|variants| variants.values().any(|v| v == value));
+                if !is_variant {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidOperation,
+                        format!("{} is not the value of any variant of enum `{}`", value, enum_name),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        "use a value listed in the enum, or one of its variants directly"
+                    )));
+                }
+            }
+            
             if !valid_cast {
                 let location = SourceLocation::new(file_path.clone(), 0, 0);
                 return Err(CompilerError::new(
@@ -1331,262 +2417,104 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
             }
         }
         Expression::Match { scrutinee, arms } => {
-            use crate::ast::Pattern;
-            
-            // Infer the type of the scrutinee
-            let scrutinee_ty = infer_type(scrutinee, symbol_table, file_path)?;
+            let scrutinee_ty = check_match_patterns(scrutinee, arms, symbol_table, file_path)?;
             
-            if arms.is_empty() {
+            // Used as a value, the match must produce one for every scrutinee. Enums and
+            // Option/Result can list all of their values and bools both of theirs; other
+            // types need a wildcard arm
+            let matches_bool = |value: bool| arms.iter()
+                .flat_map(|arm| arm.pattern.alternatives())
+                .any(|pattern| matches!(pattern, Pattern::Literal(Literal::Bool(b)) if *b == value));
+            let has_wildcard = arms.iter().any(|arm| arm.pattern.alternatives().iter().any(|p| matches!(p, Pattern::Wildcard)));
+            let exhaustive = match scrutinee_ty {
+                Type::Bool => has_wildcard || (matches_bool(true) && matches_bool(false)),
+                Type::Int | Type::Long | Type::Float | Type::Char | Type::String => has_wildcard,
+                _ => true,
+            };
+            if !exhaustive {
                 let location = SourceLocation::new(file_path.clone(), 0, 0);
                 return Err(CompilerError::new(
                     ErrorKind::InvalidSyntax,
-                    "match expression must have at least one arm".to_string(),
+                    format!("non-exhaustive match on `{:?}` used as a value", scrutinee_ty),
                     location,
-                ));
-            }
-            
-            // Check each pattern is compatible with scrutinee type
-            let mut has_wildcard = false;
-            let mut matched_variants = std::collections::HashSet::new();
-            
-            for arm in arms {
-                match &arm.pattern {
-                    Pattern::Wildcard => {
-                        has_wildcard = true;
-                    }
-                    Pattern::Literal(lit) => {
-                        let pattern_ty = match lit {
-                            Literal::Integer(_) => Type::Int,
-                            Literal::Float(_) => Type::Float,
-                            Literal::Bool(_) => Type::Bool,
-                            Literal::Char(_) => Type::Char,
-                            Literal::String(_) => Type::String,
-                        };
-                        if !types_compatible(&scrutinee_ty, &pattern_ty) {
-                            let location = SourceLocation::new(file_path.clone(), 0, 0);
-                            return Err(CompilerError::new(
-                                ErrorKind::TypeMismatch,
-                                format!("pattern type `{:?}` doesn't match scrutinee type `{:?}`", pattern_ty, scrutinee_ty),
-                                location,
-                            ));
-                        }
-                    }
-                    Pattern::EnumVariant { enum_name, variant, binding } => {
-                        // Check if this is a built-in generic type
-                        if symbol_table.builtins.is_generic_builtin(enum_name) {
-                            let builtin = symbol_table.builtins.get_generic(enum_name).unwrap();
-                            
-                            // Check variant exists
-                            let variant_info = match builtin.get_variant(variant) {
-                                Some(v) => v,
-                                None => {
-                                    let location = SourceLocation::new(file_path.clone(), 0, 0);
-                                    return Err(CompilerError::new(
-                                        ErrorKind::UndefinedType,
-                                        format!("type `{}` has no variant `{}`", enum_name, variant),
-                                        location,
-                                    ));
-                                }
-                            };
-                            
-                            // Validate binding matches variant requirements
-                            if binding.is_some() && !variant_info.has_value {
-                                let location = SourceLocation::new(file_path.clone(), 0, 0);
-                                return Err(CompilerError::new(
-                                    ErrorKind::InvalidSyntax,
-                                    format!("variant `{}::{}` does not have a value to bind", enum_name, variant),
-                                    location,
-                                ).with_suggestion(Suggestion::simple(
-                                    &format!("use `{}::{}` without a binding", enum_name, variant)
-                                )));
-                            }
-                            
-                            if binding.is_none() && variant_info.has_value {
-                                let location = SourceLocation::new(file_path.clone(), 0, 0);
-                                return Err(CompilerError::new(
-                                    ErrorKind::InvalidSyntax,
-                                    format!("variant `{}::{}` has a value that should be bound", enum_name, variant),
-                                    location,
-                                ).with_suggestion(Suggestion::simple(
-                                    &format!("use `{}::{}(name)` to bind the value", enum_name, variant)
-                                )));
-                            }
-                            
-                            matched_variants.insert(variant.clone());
-                            
-                            // Check scrutinee type is compatible
-                            if let Type::Generic { name, .. } = &scrutinee_ty {
-                                if name != enum_name {
-                                    let location = SourceLocation::new(file_path.clone(), 0, 0);
-                                    return Err(CompilerError::new(
-                                        ErrorKind::TypeMismatch,
-                                        format!("pattern type `{}` doesn't match scrutinee type `{:?}`", enum_name, scrutinee_ty),
-                                        location,
-                                    ));
-                                }
-                            } else {
-                                let location = SourceLocation::new(file_path.clone(), 0, 0);
-                                return Err(CompilerError::new(
-                                    ErrorKind::TypeMismatch,
-                                    format!("pattern expects generic type `{}`, but scrutinee is `{:?}`", enum_name, scrutinee_ty),
-                                    location,
-                                ));
-                            }
-                        }
-                        // Check enum exists in symbol table (user-defined enum)
-                        else if let Some(symbol) = symbol_table.lookup(enum_name) {
-                            if symbol.symbol_type != SymbolType::Enum {
-                                let location = SourceLocation::new(file_path.clone(), 0, 0);
-                                return Err(CompilerError::new(
-                                    ErrorKind::TypeMismatch,
-                                    format!("`{}` is not an enum", enum_name),
-                                    location,
-                                ));
-                            }
-                            
-                            if symbol_table.get_enum_variant_value(enum_name, variant).is_none() {
-                                let location = SourceLocation::new(file_path.clone(), 0, 0);
-                                return Err(CompilerError::new(
-                                    ErrorKind::UndefinedType,
-                                    format!("enum `{}` has no variant `{}`", enum_name, variant),
-                                    location,
-                                ));
-                            }
-                            
-                            matched_variants.insert(variant.clone());
-                            
-                            // Check scrutinee is this enum type
-                            let pattern_ty = Type::Enum(enum_name.clone());
-                            if !types_compatible(&scrutinee_ty, &pattern_ty) {
-                                let location = SourceLocation::new(file_path.clone(), 0, 0);
-                                return Err(CompilerError::new(
-                                    ErrorKind::TypeMismatch,
-                                    format!("pattern type `{:?}` doesn't match scrutinee type `{:?}`", pattern_ty, scrutinee_ty),
-                                    location,
-                                ));
-                            }
-                        } else {
-                            let location = SourceLocation::new(file_path.clone(), 0, 0);
-                            return Err(CompilerError::new(
-                                ErrorKind::UndefinedType,
-                                format!("enum `{}` not found", enum_name),
-                                location,
-                            ));
-                        }
-                    }
-                }
-            }
-            
-            // Check exhaustiveness for enum matches
-            if let Type::Enum(enum_name) = &scrutinee_ty {
-                if !has_wildcard {
-                    // Get all variants from the enum definition
-                    if let Some(variants_map) = symbol_table.enum_defs.get(enum_name) {
-                        let all_variants: std::collections::HashSet<_> = variants_map.keys().cloned().collect();
-                        let missing: Vec<_> = all_variants.difference(&matched_variants).collect();
-                        
-                        if !missing.is_empty() {
-                            let location = SourceLocation::new(file_path.clone(), 0, 0);
-                            return Err(CompilerError::new(
-                                ErrorKind::InvalidSyntax,
-                                format!("non-exhaustive match on enum `{}`, missing variants: {:?}", enum_name, missing),
-                                location,
-                            ).with_suggestion(Suggestion::simple(
-                                "add a wildcard pattern `_` or match all remaining variants"
-                            )));
-                        }
-                    }
-                }
+                ).with_suggestion(Suggestion::simple(
+                    "add a wildcard arm `_ => ...` for the values no other arm matches"
+                )));
             }
             
             // All arms must have compatible types
             // For each arm, we need to analyze the expression with bound variables in scope
-            let first_arm_ty = {
-                symbol_table.enter_scope();
-                
-                // Add bound variables from the pattern to the scope
-                if let Pattern::EnumVariant { enum_name, variant: _, binding } = &arms[0].pattern {
-                    if let Some(binding_name) = binding {
-                        // Determine the type of the bound variable
-                        let bound_type = if symbol_table.builtins.is_generic_builtin(enum_name) {
-                            // For built-in generic types, extract the type parameter
-                            if let Type::Generic { ref type_params, .. } = scrutinee_ty {
-                                if !type_params.is_empty() {
-                                    type_params[0].clone()
-                                } else {
-                                    Type::Int // Fallback
-                                }
-                            } else {
-                                Type::Int // Fallback
-                            }
-                        } else {
-                            Type::Int // User-defined enums don't support values yet
-                        };
-                        
-                        let binding_symbol = Symbol {
-                            name: binding_name.clone(),
-                            symbol_type: SymbolType::Variable,
-                            ty: bound_type,
-                        };
-                        
-                        symbol_table.insert(binding_symbol, file_path)?;
+            let mut first_arm_ty: Option<Type> = None;
+            for arm in arms {
+                let expression = match &arm.body {
+                    ArmBody::Expression(expression) => expression,
+                    ArmBody::Block(_) => {
+                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        return Err(CompilerError::new(
+                            ErrorKind::InvalidOperation,
+                            "a match arm block does not produce a value".to_string(),
+                            location,
+                        ).with_suggestion(Suggestion::simple(
+                            "use the match as a statement, or give every arm an expression"
+                        )));
                     }
-                }
+                };
                 
-                let ty = infer_type(&arms[0].expression, symbol_table, file_path)?;
-                symbol_table.exit_scope();
-                ty
-            };
-            
-            for arm in &arms[1..] {
                 symbol_table.enter_scope();
-                
-                // Add bound variables from the pattern to the scope
-                if let Pattern::EnumVariant { enum_name, variant: _, binding } = &arm.pattern {
-                    if let Some(binding_name) = binding {
-                        // Determine the type of the bound variable
-                        let bound_type = if symbol_table.builtins.is_generic_builtin(enum_name) {
-                            // For built-in generic types, extract the type parameter
-                            if let Type::Generic { ref type_params, .. } = scrutinee_ty {
-                                if !type_params.is_empty() {
-                                    type_params[0].clone()
-                                } else {
-                                    Type::Int // Fallback
-                                }
-                            } else {
-                                Type::Int // Fallback
-                            }
-                        } else {
-                            Type::Int // User-defined enums don't support values yet
-                        };
-                        
-                        let binding_symbol = Symbol {
-                            name: binding_name.clone(),
-                            symbol_type: SymbolType::Variable,
-                            ty: bound_type,
-                        };
-                        
-                        symbol_table.insert(binding_symbol, file_path)?;
-                    }
-                }
-                
-                let arm_ty = infer_type(&arm.expression, symbol_table, file_path)?;
+                bind_arm_pattern(&arm.pattern, &scrutinee_ty, symbol_table, file_path)?;
+                let arm_ty = infer_type(expression, symbol_table, file_path)?;
                 symbol_table.exit_scope();
                 
-                if !types_compatible(&first_arm_ty, &arm_ty) {
-                    let location = SourceLocation::new(file_path.clone(), 0, 0);
-                    return Err(CompilerError::new(
-                        ErrorKind::TypeMismatch,
-                        format!("match arms must have compatible types: `{:?}` vs `{:?}`", first_arm_ty, arm_ty),
-                        location,
-                    ).with_suggestion(Suggestion::simple(
-                        "ensure all match arms return the same type"
-                    )));
+                match &first_arm_ty {
+                    None => first_arm_ty = Some(arm_ty),
+                    Some(first) if !types_compatible(first, &arm_ty) => {
+                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        return Err(CompilerError::new(
+                            ErrorKind::TypeMismatch,
+                            format!("match arms must have compatible types: `{:?}` vs `{:?}`", first, arm_ty),
+                            location,
+                        ).with_suggestion(Suggestion::simple(
+                            "ensure all match arms return the same type"
+                        )));
+                    }
+                    Some(_) => {}
                 }
             }
             
-            // Return type of first arm (all are compatible)
-            Ok(first_arm_ty)
+            // Return type of first arm (all are compatible); arms are never empty here
+            Ok(first_arm_ty.unwrap_or(Type::Void))
+        }
+        Expression::Loop { body } => {
+            let expected_return = symbol_table.current_function_return_type.clone().unwrap_or(Type::Void);
+            let location = SourceLocation::new(file_path.clone(), 0, 0);
+            symbol_table.enter_scope();
+            symbol_table.loop_breaks.push(("loop", Some(Vec::new())));
+            for stmt in body {
+                analyze_statement(stmt, symbol_table, file_path, location.clone(), &expected_return)?;
+            }
+            let break_types = symbol_table.loop_breaks.pop().and_then(|(_, types)| types).unwrap_or_default();
+            symbol_table.exit_scope();
+            
+            // Every `break` value must agree with the first
+            let Some(first) = break_types.first() else {
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidOperation,
+                    "`loop` used as a value never breaks with a value".to_string(),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "leave the loop with `break value;`, or use it as a statement"
+                )));
+            };
+            if let Some(other) = break_types.iter().find(|ty| !types_compatible(first, ty)) {
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("loop breaks with values of different types: `{:?}` vs `{:?}`", first, other),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "ensure every `break` of the loop provides a value of the same type"
+                )));
+            }
+            Ok(first.clone())
         }
         Expression::InterpolatedString { parts } => {
             // Type-check all interpolated expressions
@@ -1676,180 +2604,395 @@ fn infer_type(expr: &Expression, symbol_table: &mut SymbolTable, file_path: &Pat
         }
         Expression::MethodCall { object, method, arguments } => {
             // Method call: object.method(args)
-            let mut object_ty = infer_type(object, symbol_table, file_path)?;
-            
-            // Normalize str to String type
-            if let Type::Struct(ref name) = object_ty {
-                if name == "str" {
-                    object_ty = Type::String;
-                }
-            }
-            
-            match (&object_ty, method.as_str()) {
-                // String methods
-                (&Type::String, "length") => {
-                    if !arguments.is_empty() {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::WrongArgumentCount,
-                            format!("length() expects 0 arguments, got {}", arguments.len()),
-                            location,
-                        ));
-                    }
-                    Ok(Type::Int)
-                }
-                (&Type::String, "substring") => {
-                    if arguments.len() != 2 {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::WrongArgumentCount,
-                            format!("substring() expects 2 arguments (start, end), got {}", arguments.len()),
-                            location,
-                        ).with_suggestion(Suggestion::simple(
-                            "usage: str.substring(start_index, end_index)"
-                        )));
-                    }
-                    // Validate arguments are integers
-                    for (i, arg) in arguments.iter().enumerate() {
-                        let arg_ty = infer_type(arg, symbol_table, file_path)?;
-                        if arg_ty != Type::Int {
-                            let location = SourceLocation::new(file_path.clone(), 0, 0);
-                            return Err(CompilerError::new(
-                                ErrorKind::TypeMismatch,
-                                format!("substring() argument {} must be int, got `{:?}`", i + 1, arg_ty),
-                                location,
-                            ));
-                        }
-                    }
-                    Ok(Type::String)
-                }
-                (&Type::String, "contains") => {
-                    if arguments.len() != 1 {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::WrongArgumentCount,
-                            format!("contains() expects 1 argument, got {}", arguments.len()),
-                            location,
-                        ).with_suggestion(Suggestion::simple(
-                            "usage: str.contains(needle)"
-                        )));
-                    }
-                    let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
-                    if arg_ty != Type::String {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::TypeMismatch,
-                            format!("contains() expects string argument, got `{:?}`", arg_ty),
-                            location,
-                        ));
-                    }
-                    Ok(Type::Bool)
-                }
-                (&Type::String, "trim") => {
-                    if !arguments.is_empty() {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::WrongArgumentCount,
-                            format!("trim() expects 0 arguments, got {}", arguments.len()),
-                            location,
-                        ));
-                    }
-                    Ok(Type::String)
-                }
-                (&Type::String, "split") => {
-                    if arguments.len() != 1 {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::WrongArgumentCount,
-                            format!("split() expects 1 argument (delimiter), got {}", arguments.len()),
-                            location,
-                        ).with_suggestion(Suggestion::simple(
-                            "usage: str.split(delimiter)"
-                        )));
-                    }
-                    let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
-                    if arg_ty != Type::String && arg_ty != Type::Char {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::TypeMismatch,
-                            format!("split() expects string or char delimiter, got `{:?}`", arg_ty),
-                            location,
-                        ));
-                    }
-                    // Returns a dynamic array of strings
-                    Ok(Type::DynamicArray(Box::new(Type::String)))
-                }
-                // Dynamic array methods
-                (&Type::DynamicArray(ref elem_ty), "push") => {
-                    if arguments.len() != 1 {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::WrongArgumentCount,
-                            format!("push() expects 1 argument, got {}", arguments.len()),
-                            location,
-                        ));
-                    }
-                    let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
-                    if !types_compatible(&elem_ty, &arg_ty) {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::TypeMismatch,
-                            format!("push() expects element of type `{:?}`, got `{:?}`", elem_ty, arg_ty),
-                            location,
-                        ));
-                    }
-                    Ok(Type::Void)
-                }
-                (&Type::DynamicArray(ref elem_ty), "pop") => {
-                    if !arguments.is_empty() {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::WrongArgumentCount,
-                            format!("pop() expects 0 arguments, got {}", arguments.len()),
-                            location,
-                        ));
-                    }
-                    Ok(*elem_ty.clone())
-                }
-                (&Type::DynamicArray(_), "length") => {
-                    if !arguments.is_empty() {
-                        let location = SourceLocation::new(file_path.clone(), 0, 0);
-                        return Err(CompilerError::new(
-                            ErrorKind::WrongArgumentCount,
-                            format!("length() expects 0 arguments, got {}", arguments.len()),
-                            location,
-                        ));
-                    }
-                    Ok(Type::Int)
-                }
-                _ => {
-                    let location = SourceLocation::new(file_path.clone(), 0, 0);
-                    Err(CompilerError::new(
-                        ErrorKind::UndefinedFunction,
-                        format!("unknown method `{}` on type `{:?}`", method, object_ty),
-                        location,
-                    ).with_suggestion(Suggestion::simple(
-                        "check the method name or ensure the type supports this operation"
-                    )))
-                }
-            }
+            let object_ty = infer_type(object, symbol_table, file_path)?;
+            infer_method_call(object_ty, method, arguments, symbol_table, file_path)
         }
     }
 }
 
-fn types_compatible(left: &Type, right: &Type) -> bool {
-    // Direct equality
-    if left == right {
-        return true;
+// The type of `object.method(args)` once the object's type is known
+fn infer_method_call(mut object_ty: Type, method: &str, arguments: &[Expression], symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<Type, CompilerError> {
+    // Normalize str to String type
+    if let Type::Struct(ref name) = object_ty {
+        if name == "str" {
+            object_ty = Type::String;
+        }
     }
     
-    // Handle struct/enum ambiguity:
-    // Parser can't distinguish between enum and struct names in type annotations
-    // So Type::Struct("Foo") and Type::Enum("Foo") should be compatible if they refer to the same type
-    match (left, right) {
-        (Type::Struct(name1), Type::Enum(name2)) | (Type::Enum(name1), Type::Struct(name2)) => {
-            name1 == name2
-        }
+    match (&object_ty, method) {
+        // String methods
+        (&Type::String, "length") => {
+            if !arguments.is_empty() {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::WrongArgumentCount,
+                    format!("length() expects 0 arguments, got {}", arguments.len()),
+                    location,
+                ));
+            }
+            Ok(Type::Int)
+        }
+        (&Type::String, "substring") => {
+            if arguments.len() != 2 {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::WrongArgumentCount,
+                    format!("substring() expects 2 arguments (start, end), got {}", arguments.len()),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "usage: str.substring(start_index, end_index)"
+                )));
+            }
+            // Validate arguments are integers
+            for (i, arg) in arguments.iter().enumerate() {
+                let arg_ty = infer_type(arg, symbol_table, file_path)?;
+                if arg_ty != Type::Int {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("substring() argument {} must be int, got `{:?}`", i + 1, arg_ty),
+                        location,
+                    ));
+                }
+            }
+            Ok(Type::String)
+        }
+        (&Type::String, "contains") => {
+            if arguments.len() != 1 {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::WrongArgumentCount,
+                    format!("contains() expects 1 argument, got {}", arguments.len()),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "usage: str.contains(needle)"
+                )));
+            }
+            let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+            if arg_ty != Type::String {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("contains() expects string argument, got `{:?}`", arg_ty),
+                    location,
+                ));
+            }
+            Ok(Type::Bool)
+        }
+        (&Type::String, "trim" | "trim_start" | "trim_end" | "to_upper" | "to_lower") => {
+            if !arguments.is_empty() {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::WrongArgumentCount,
+                    format!("{}() expects 0 arguments, got {}", method, arguments.len()),
+                    location,
+                ));
+            }
+            Ok(Type::String)
+        }
+        (&Type::String, "split") => {
+            if arguments.len() != 1 {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::WrongArgumentCount,
+                    format!("split() expects 1 argument (delimiter), got {}", arguments.len()),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "usage: str.split(delimiter)"
+                )));
+            }
+            let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+            if arg_ty != Type::String && arg_ty != Type::Char {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("split() expects string or char delimiter, got `{:?}`", arg_ty),
+                    location,
+                ));
+            }
+            // Returns a dynamic array of strings
+            Ok(Type::DynamicArray(Box::new(Type::String)))
+        }
+        // Dynamic array methods
+        (Type::DynamicArray(elem_ty), "push") => {
+            if arguments.len() != 1 {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::WrongArgumentCount,
+                    format!("push() expects 1 argument, got {}", arguments.len()),
+                    location,
+                ));
+            }
+            let arg_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+            if !types_compatible(&elem_ty, &arg_ty) {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("push() expects element of type `{:?}`, got `{:?}`", elem_ty, arg_ty),
+                    location,
+                ));
+            }
+            Ok(Type::Void)
+        }
+        (Type::DynamicArray(elem_ty), "pop") => {
+            if !arguments.is_empty() {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::WrongArgumentCount,
+                    format!("pop() expects 0 arguments, got {}", arguments.len()),
+                    location,
+                ));
+            }
+            Ok(*elem_ty.clone())
+        }
+        (Type::Int | Type::Long | Type::Float | Type::Bool | Type::Char, "to_string") => {
+            check_to_string_arguments(arguments, SourceLocation::new(file_path.clone(), 0, 0))
+        }
+        (Type::DynamicArray(elem_ty), "to_string") if **elem_ty == Type::Char => {
+            if !arguments.is_empty() {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::WrongArgumentCount,
+                    format!("to_string() expects 0 arguments, got {}", arguments.len()),
+                    location,
+                ));
+            }
+            Ok(Type::String)
+        }
+        (Type::DynamicArray(elem_ty), "map" | "filter") => {
+            check_map_filter(method, elem_ty, arguments, symbol_table, file_path)
+        }
+        (Type::DynamicArray(elem_ty), "length" | "len" | "data") => {
+            if !arguments.is_empty() {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::WrongArgumentCount,
+                    format!("{}() expects 0 arguments, got {}", method, arguments.len()),
+                    location,
+                ));
+            }
+            if method == "data" {
+                Ok(Type::Pointer(elem_ty.clone()))
+            } else {
+                Ok(Type::Int)
+            }
+        }
+        _ => {
+            let location = SourceLocation::new(file_path.clone(), 0, 0);
+            Err(CompilerError::new(
+                ErrorKind::UndefinedFunction,
+                format!("unknown method `{}` on type `{:?}`", method, object_ty),
+                location,
+            ).with_suggestion(Suggestion::simple(
+                "check the method name or ensure the type supports this operation"
+            )))
+        }
+    }
+}
+
+// Check a call against an extern declaration. Arguments beyond the declared parameters are
+// only allowed for variadic externs and are passed through unchecked.
+fn check_extern_arguments(ext_func: &ExternFunction, arguments: &[Expression], symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<(), CompilerError> {
+    let fixed = ext_func.parameters.len();
+    if arguments.len() < fixed || (!ext_func.variadic && arguments.len() > fixed) {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        let expected = if ext_func.variadic { format!("at least {}", fixed) } else { fixed.to_string() };
+        return Err(CompilerError::new(
+            ErrorKind::WrongArgumentCount,
+            format!("extern function `{}` expects {} argument(s), got {}", ext_func.name, expected, arguments.len()),
+            location,
+        ));
+    }
+    for (i, arg) in arguments.iter().enumerate() {
+        let arg_ty = infer_type(arg, symbol_table, file_path)?;
+        if let Some(param) = ext_func.parameters.get(i) {
+            if !extern_argument_compatible(&param.param_type, &arg_ty) {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("argument `{}` of extern function `{}` has type `{:?}` but got `{:?}`",
+                            param.name, ext_func.name, param.param_type, arg_ty),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "only arguments after the declared parameters of a variadic extern are unchecked"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+// `v.map(f)` and `v.filter(f)`: `f` takes one element; map collects what `f` returns,
+// filter keeps the elements for which `f` returns true
+fn check_map_filter(method: &str, elem_ty: &Type, arguments: &[Expression], symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<Type, CompilerError> {
+    if arguments.len() != 1 {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        return Err(CompilerError::new(
+            ErrorKind::WrongArgumentCount,
+            format!("{}() expects 1 argument, got {}", method, arguments.len()),
+            location,
+        ));
+    }
+    let func_ty = infer_type(&arguments[0], symbol_table, file_path)?;
+    let result = match &func_ty {
+        Type::Function(params, ret) if params.len() == 1 && types_compatible(&params[0], elem_ty) => {
+            match (method, &**ret) {
+                ("map", Type::Void) => None,
+                ("map", ret) => Some(Type::DynamicArray(Box::new(ret.clone()))),
+                (_, Type::Bool) => Some(Type::DynamicArray(Box::new(elem_ty.clone()))),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    result.ok_or_else(|| {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        let expected = if method == "map" { "a value" } else { "bool" };
+        CompilerError::new(
+            ErrorKind::TypeMismatch,
+            format!("{}() expects a function taking `{:?}` and returning {}, got `{:?}`", method, elem_ty, expected, func_ty),
+            location,
+        ).with_suggestion(Suggestion::simple(
+            "pass the name of a function with a single parameter of the array's element type"
+        ))
+    })
+}
+
+// Check a call through a function pointer variable or parameter against its type
+fn check_function_pointer_arguments(name: &str, params: &[Type], arguments: &[Expression], symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<(), CompilerError> {
+    if arguments.len() != params.len() {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        return Err(CompilerError::new(
+            ErrorKind::WrongArgumentCount,
+            format!("function pointer `{}` expects {} argument(s), got {}", name, params.len(), arguments.len()),
+            location,
+        ));
+    }
+    for (param_ty, arg) in params.iter().zip(arguments) {
+        let arg_ty = infer_type(arg, symbol_table, file_path)?;
+        if !types_compatible(param_ty, &arg_ty) {
+            let location = SourceLocation::new(file_path.clone(), 0, 0);
+            return Err(CompilerError::new(
+                ErrorKind::TypeMismatch,
+                format!("function pointer `{}` expects an argument of type `{:?}` but got `{:?}`", name, param_ty, arg_ty),
+                location,
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Arguments for `ref` parameters are passed by address: each must be a variable, field or
+// element of the parameter's type, and a `mut ref` one must be a `let mut` variable or a
+// `mut ref` parameter, or part of one
+fn check_ref_arguments(name: &str, params: &[Parameter], arguments: &[Expression], symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<(), CompilerError> {
+    for (param, arg) in params.iter().zip(arguments).filter(|(p, _)| p.by_ref) {
+        let keyword = if param.mutable { "mut ref" } else { "ref" };
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        let is_place = match arg {
+            Expression::Variable(_) | Expression::StructAccess { .. } => true,
+            Expression::ArrayAccess { index, .. } => !matches!(**index, Expression::Range { .. }),
+            Expression::Unary { operator: UnaryOp::Dereference, .. } => true,
+            _ => false,
+        };
+        if !is_place {
+            return Err(CompilerError::new(
+                ErrorKind::InvalidOperation,
+                format!("`{}` takes `{}` by `{}`, so the argument must be a variable", name, param.name, keyword),
+                location,
+            ).with_suggestion(Suggestion::simple(
+                "store the value in a variable first and pass that"
+            )));
+        }
+        let arg_ty = infer_type(arg, symbol_table, file_path)?;
+        if !types_compatible(&param.param_type, &arg_ty) {
+            return Err(CompilerError::new(
+                ErrorKind::TypeMismatch,
+                format!("`{}` takes `{}` by `{}` as `{:?}` but got `{:?}`", name, param.name, keyword, param.param_type, arg_ty),
+                location,
+            ));
+        }
+        if let Some(outer) = param.mutable.then(|| read_only_ref(arg, symbol_table)).flatten() {
+            return Err(CompilerError::new(
+                ErrorKind::ImmutableAssignment,
+                format!("cannot pass `ref` parameter `{}` on as `mut ref` `{}`", outer, param.name),
+                location,
+            ).with_suggestion(Suggestion::simple(
+                format!("declare it as `mut ref {}` as well", outer)
+            )));
+        }
+        if let Some(root) = param.mutable.then(|| immutable_root(arg, symbol_table)).flatten() {
+            return Err(CompilerError::new(
+                ErrorKind::ImmutableAssignment,
+                format!("cannot pass immutable `{}` as `mut ref` `{}`", root, param.name),
+                location,
+            ).with_suggestion(Suggestion::simple(
+                format!("declare it with `let mut {}`", root)
+            )));
+        }
+    }
+    Ok(())
+}
+
+// The read-only `ref` parameter that an assignment target or argument writes through, if any
+fn read_only_ref(place: &Expression, symbol_table: &SymbolTable) -> Option<String> {
+    match place {
+        Expression::Variable(name) => match symbol_table.lookup(name) {
+            Some(Symbol { symbol_type: SymbolType::RefParameter { mutable: false }, .. }) => Some(name.clone()),
+            _ => None,
+        },
+        Expression::StructAccess { object, .. } => read_only_ref(object, symbol_table),
+        Expression::ArrayAccess { array, .. } => read_only_ref(array, symbol_table),
+        _ => None,
+    }
+}
+
+// The variable a `mut ref` argument writes through, when it is neither `let mut` nor a `mut ref`
+// parameter
+fn immutable_root(place: &Expression, symbol_table: &SymbolTable) -> Option<String> {
+    match place {
+        Expression::Variable(name) => match &symbol_table.lookup(name)?.symbol_type {
+            SymbolType::Variable { mutable: true } | SymbolType::RefParameter { mutable: true } => None,
+            _ => Some(name.clone()),
+        },
+        Expression::StructAccess { object, .. } => immutable_root(object, symbol_table),
+        Expression::ArrayAccess { array, .. } => immutable_root(array, symbol_table),
+        _ => None,
+    }
+}
+
+// Like types_compatible, plus the conversions C applies when calling an extern:
+// strings to `*char`, any pointer to `*void`, and widening between numeric types
+fn extern_argument_compatible(param: &Type, arg: &Type) -> bool {
+    if types_compatible(param, arg) {
+        return true;
+    }
+    match (param, arg) {
+        (Type::Pointer(inner), Type::String) => matches!(**inner, Type::Char | Type::Void),
+        (Type::Pointer(inner), Type::Pointer(_)) => **inner == Type::Void,
+        (Type::Float, Type::Int) | (Type::Int, Type::Char | Type::Bool) | (Type::Char, Type::Int) => true,
+        _ => false,
+    }
+}
+
+fn types_compatible(left: &Type, right: &Type) -> bool {
+    // Direct equality. Note `int` and `bool` stay distinct even though both are a C `int`
+    if left == right {
+        return true;
+    }
+    
+    // Handle struct/enum ambiguity:
+    // Parser can't distinguish between enum and struct names in type annotations
+    // So Type::Struct("Foo") and Type::Enum("Foo") should be compatible if they refer to the same type
+    match (left, right) {
+        // An int widens to long without a cast
+        (Type::Long, Type::Int) => true,
+        // `nil` takes the pointer or string type it is assigned to or compared with
+        (Type::Pointer(_) | Type::String, Type::Nil) | (Type::Nil, Type::Pointer(_) | Type::String) => true,
+        (Type::Struct(name), Type::Nil) | (Type::Nil, Type::Struct(name)) => name == "str",
+        (Type::Struct(name1), Type::Enum(name2)) | (Type::Enum(name1), Type::Struct(name2)) => {
+            name1 == name2
+        }
         // Handle str/String compatibility
         // Type annotation `str` becomes Type::Struct("str"), but string literals are Type::String
         (Type::Struct(name), Type::String) | (Type::String, Type::Struct(name)) => {
@@ -1859,6 +3002,13 @@ fn types_compatible(left: &Type, right: &Type) -> bool {
         (Type::DynamicArray(inner1), Type::DynamicArray(inner2)) => {
             types_compatible(inner1, inner2)
         }
+        // Any fixed array can be passed as `[T]`, so lengths are not compared
+        (Type::Array(inner1, _), Type::Array(inner2, _)) => {
+            types_compatible(inner1, inner2)
+        }
+        (Type::Tuple(elements1), Type::Tuple(elements2)) => {
+            elements1.len() == elements2.len() && elements1.iter().zip(elements2).all(|(a, b)| types_compatible(a, b))
+        }
         // Handle qualified vs unqualified type names
         // e.g., ast.AstType should match AstType
         (Type::Struct(name1), Type::Struct(name2)) => {
@@ -1877,34 +3027,1572 @@ fn types_compatible(left: &Type, right: &Type) -> bool {
         (Type::Pointer(inner1), Type::Pointer(inner2)) => {
             types_compatible(inner1, inner2)
         }
+        (Type::Function(params1, ret1), Type::Function(params2, ret2)) => {
+            params1.len() == params2.len()
+                && params1.iter().zip(params2).all(|(p1, p2)| types_compatible(p1, p2))
+                && types_compatible(ret1, ret2)
+        }
         _ => false
     }
 }
 
-// Determine if a block of statements guarantees a return on all control-flow paths
-fn block_returns(stmts: &Vec<Statement>, symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<bool, CompilerError> {
-    let mut guaranteed = false;
-    for stmt in stmts {
-        match stmt {
-            Statement::Return(_) => { return Ok(true); }
-            Statement::If { then_branch, else_branch, .. } => {
-                let then_ret = block_returns(then_branch, symbol_table, file_path)?;
-                let else_ret = if let Some(else_b) = else_branch {
-                    block_returns(else_b, symbol_table, file_path)?
-                } else { false };
-                if then_ret && else_ret { return Ok(true); }
-            }
-            Statement::While { body, .. } => {
-                // A while-loop alone cannot guarantee return (it may not execute)
-                let _ = block_returns(body, symbol_table, file_path)?; // analyze nested but ignore for guarantee
+// Validate a match's patterns against its scrutinee and return the scrutinee type
+fn check_match_patterns(scrutinee: &Expression, arms: &[MatchArm], symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<Type, CompilerError> {
+    // Infer the type of the scrutinee
+    let scrutinee_ty = infer_type(scrutinee, symbol_table, file_path)?;
+    if scrutinee_ty == Type::Void {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        return Err(CompilerError::new(
+            ErrorKind::TypeMismatch,
+            "cannot match on an expression that produces no value (`Void`)".to_string(),
+            location,
+        ).with_suggestion(Suggestion::simple(
+            "match on a value; a call to a function without a return type has none"
+        )));
+    }
+    
+    if arms.is_empty() {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        return Err(CompilerError::new(
+            ErrorKind::InvalidSyntax,
+            "match expression must have at least one arm".to_string(),
+            location,
+        ));
+    }
+    
+    // Check each pattern is compatible with scrutinee type
+    let mut has_wildcard = false;
+    let mut matched_variants = std::collections::HashSet::new();
+    
+    for arm in arms {
+        let alternatives = arm.pattern.alternatives();
+        if alternatives.len() > 1 && alternatives.iter().any(|pattern| pattern.binds_variables()) {
+            let location = SourceLocation::new(file_path.clone(), 0, 0);
+            return Err(CompilerError::new(
+                ErrorKind::InvalidOperation,
+                "or-patterns cannot bind variables".to_string(),
+                location,
+            ).with_suggestion(Suggestion::simple(
+                "give each alternative that needs a binding its own arm"
+            )));
+        }
+        for pattern in alternatives {
+            match pattern {
+                Pattern::Wildcard => has_wildcard = true,
+                // A nested pattern matches only some values of its variant
+                Pattern::EnumVariant { variant, nested: None, .. } => {
+                    matched_variants.insert(variant.clone());
+                }
+                _ => {}
             }
-            Statement::For { body, .. } => {
-                // Similarly, for-loops don't guarantee return by themselves
-                let _ = block_returns(body, symbol_table, file_path)?;
+            check_pattern(pattern, &scrutinee_ty, symbol_table, file_path)?;
+        }
+    }
+    
+    // A value matched by several literal or range arms goes to the first one, so a pattern whose
+    // values earlier arms all match can never be reached
+    let mut matched_values: Vec<(i64, i64)> = Vec::new();
+    for pattern in arms.iter().flat_map(|arm| arm.pattern.alternatives()) {
+        let Some(left) = pattern.unmatched_values(&matched_values) else { continue };
+        if left.is_empty() {
+            let location = SourceLocation::new(file_path.clone(), 0, 0);
+            return Err(CompilerError::new(
+                ErrorKind::InvalidOperation,
+                "unreachable match pattern: earlier arms match every value it matches".to_string(),
+                location,
+            ).with_suggestion(Suggestion::simple(
+                "remove the pattern, or move it before the arms that cover it"
+            )));
+        }
+        matched_values.extend(left);
+    }
+    
+    // Check exhaustiveness for enum matches; annotations spell enum types as structs
+    if let Type::Enum(enum_name) | Type::Struct(enum_name) = &scrutinee_ty {
+        if !has_wildcard {
+            // Get all variants from the enum definition
+            if let Some(variants_map) = symbol_table.enum_defs.get(enum_name) {
+                let all_variants: std::collections::HashSet<_> = variants_map.keys().cloned().collect();
+                let missing: Vec<_> = all_variants.difference(&matched_variants).collect();
+                
+                if !missing.is_empty() {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidSyntax,
+                        format!("non-exhaustive match on enum `{}`, missing variants: {:?}", enum_name, missing),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        "add a wildcard pattern `_` or match all remaining variants"
+                    )));
+                }
             }
-            _ => {}
         }
-        guaranteed = false;
     }
-    Ok(guaranteed)
-}
\ No newline at end of file
+    
+    Ok(scrutinee_ty)
+}
+
+// Check that one alternative of a match arm can match a value of type `scrutinee_ty`
+fn check_pattern(pattern: &Pattern, scrutinee_ty: &Type, symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<(), CompilerError> {
+    match pattern {
+        Pattern::Or(_) => unreachable!("alternatives have no nested alternatives"),
+        // Only a nested pattern can still carry a binding: Option::Some(v @ Result::Ok(_))
+        Pattern::Binding { pattern, .. } => check_pattern(pattern, scrutinee_ty, symbol_table, file_path)?,
+        Pattern::Wildcard => {}
+        Pattern::Literal(lit) => {
+            let pattern_ty = match lit {
+                Literal::Integer(_) => Type::Int,
+                Literal::Float(_) => Type::Float,
+                Literal::Bool(_) => Type::Bool,
+                Literal::Char(_) => Type::Char,
+                Literal::String(_) => Type::String,
+                Literal::Nil => Type::Nil,
+            };
+            if !types_compatible(scrutinee_ty, &pattern_ty) {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("pattern type `{:?}` doesn't match scrutinee type `{:?}`", pattern_ty, scrutinee_ty),
+                    location,
+                ));
+            }
+        }
+        Pattern::Range { start, end, .. } => {
+            for bound in [start, end] {
+                let pattern_ty = if matches!(bound, Literal::Char(_)) { Type::Char } else { Type::Int };
+                if !types_compatible(scrutinee_ty, &pattern_ty) {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("range pattern type `{:?}` doesn't match scrutinee type `{:?}`", pattern_ty, scrutinee_ty),
+                        location,
+                    ));
+                }
+            }
+            if pattern.matched_values().is_none() {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::InvalidOperation,
+                    "range pattern matches nothing".to_string(),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "write the smaller end first; `1..10` stops before its end, `1..=10` includes it"
+                )));
+            }
+        }
+        Pattern::Tuple(elements) => {
+            let Type::Tuple(element_types) = scrutinee_ty else {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("tuple pattern doesn't match scrutinee type `{:?}`", scrutinee_ty),
+                    location,
+                ));
+            };
+            if elements.len() != element_types.len() {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("tuple pattern has {} elements but the scrutinee has {}", elements.len(), element_types.len()),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "write one pattern per element, using `_` for the elements that can be anything"
+                )));
+            }
+            for (element, element_ty) in elements.iter().zip(element_types) {
+                check_pattern(element, element_ty, symbol_table, file_path)?;
+            }
+        }
+        Pattern::EnumVariant { enum_name, variant, binding, nested } => {
+            // Check if this is a built-in generic type
+            if symbol_table.builtins.is_generic_builtin(enum_name) {
+                let builtin = symbol_table.builtins.get_generic(enum_name).unwrap();
+            
+                // Check variant exists
+                let variant_info = match builtin.get_variant(variant) {
+                    Some(v) => v,
+                    None => {
+                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        return Err(CompilerError::new(
+                            ErrorKind::UndefinedType,
+                            format!("type `{}` has no variant `{}`", enum_name, variant),
+                            location,
+                        ));
+                    }
+                };
+            
+                // Validate binding matches variant requirements
+                if (binding.is_some() || nested.is_some()) && !variant_info.has_value {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidSyntax,
+                        format!("variant `{}::{}` does not have a value to bind", enum_name, variant),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        &format!("use `{}::{}` without a binding", enum_name, variant)
+                    )));
+                }
+            
+                if binding.is_none() && nested.is_none() && variant_info.has_value {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidSyntax,
+                        format!("variant `{}::{}` has a value that should be bound", enum_name, variant),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        &format!("use `{}::{}(name)` to bind the value", enum_name, variant)
+                    )));
+                }
+            
+                // Check scrutinee type is compatible
+                if let Type::Generic { name, type_params } = scrutinee_ty {
+                    if name != enum_name {
+                        let location = SourceLocation::new(file_path.clone(), 0, 0);
+                        return Err(CompilerError::new(
+                            ErrorKind::TypeMismatch,
+                            format!("pattern type `{}` doesn't match scrutinee type `{:?}`", enum_name, scrutinee_ty),
+                            location,
+                        ));
+                    }
+                    // A nested pattern matches the variant's value
+                    if let Some(nested) = nested {
+                        let value_ty = builtin.variant_value_type(variant, type_params).unwrap_or(Type::Int);
+                        check_pattern(nested, &value_ty, symbol_table, file_path)?;
+                    }
+                } else {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("pattern expects generic type `{}`, but scrutinee is `{:?}`", enum_name, scrutinee_ty),
+                        location,
+                    ));
+                }
+            }
+            // Check enum exists in symbol table (user-defined enum)
+            else if let Some(symbol) = symbol_table.lookup(enum_name) {
+                if symbol.symbol_type != SymbolType::Enum {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("`{}` is not an enum", enum_name),
+                        location,
+                    ));
+                }
+            
+                if symbol_table.get_enum_variant_value(enum_name, variant).is_none() {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::UndefinedType,
+                        format!("enum `{}` has no variant `{}`", enum_name, variant),
+                        location,
+                    ));
+                }
+            
+                if nested.is_some() {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidSyntax,
+                        format!("variant `{}::{}` does not have a value to match", enum_name, variant),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        format!("use `{}::{}` without a nested pattern", enum_name, variant)
+                    )));
+                }
+            
+                // Check scrutinee is this enum type
+                let pattern_ty = Type::Enum(enum_name.clone());
+                if !types_compatible(scrutinee_ty, &pattern_ty) {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("pattern type `{:?}` doesn't match scrutinee type `{:?}`", pattern_ty, scrutinee_ty),
+                        location,
+                    ));
+                }
+            } else {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                return Err(CompilerError::new(
+                    ErrorKind::UndefinedType,
+                    format!("enum `{}` not found", enum_name),
+                    location,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// The type of the field of `self` named `name`, inside a method whose struct has one
+fn implicit_self_field(name: &str, symbol_table: &SymbolTable) -> Option<Type> {
+    let self_ty = &symbol_table.lookup("self")?.ty;
+    let Type::Struct(struct_name) = self_ty else { return None };
+    symbol_table.get_struct_field_type(struct_name, name).cloned()
+}
+
+// Add the variable bound by an arm's pattern to the current scope
+fn bind_arm_pattern(pattern: &Pattern, scrutinee_ty: &Type, symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<(), CompilerError> {
+    // `name @ pattern` binds the whole scrutinee
+    for name in pattern.outer_bindings() {
+        let binding_symbol = Symbol {
+            name: name.to_string(),
+            symbol_type: SymbolType::Variable { mutable: false },
+            ty: scrutinee_ty.clone(),
+            location: SourceLocation::new(file_path.clone(), 0, 0),
+        };
+        symbol_table.insert(binding_symbol)?;
+    }
+    
+    // Each element of a tuple pattern binds inside its own element
+    if let (Pattern::Tuple(elements), Type::Tuple(element_types)) = (pattern.without_binding(), scrutinee_ty) {
+        for (element, element_ty) in elements.iter().zip(element_types) {
+            bind_arm_pattern(element, element_ty, symbol_table, file_path)?;
+        }
+    }
+    
+    // Add bound variables from the pattern to the scope
+    if let Pattern::EnumVariant { enum_name, variant, binding, nested } = pattern.without_binding() {
+        // Determine the type of the variant's value
+        let bound_type = if symbol_table.builtins.is_generic_builtin(enum_name) {
+            // For built-in generic types, the variant's own type parameter (`E` for `Err`)
+            match (symbol_table.builtins.get_generic(enum_name), scrutinee_ty) {
+                (Some(builtin), Type::Generic { type_params, .. }) => {
+                    builtin.variant_value_type(variant, type_params).unwrap_or(Type::Int)
+                }
+                _ => Type::Int, // Fallback
+            }
+        } else {
+            Type::Int // User-defined enums don't support values yet
+        };
+        
+        if let Some(binding_name) = binding {
+            let binding_symbol = Symbol {
+                name: binding_name.clone(),
+                symbol_type: SymbolType::Variable { mutable: false },
+                ty: bound_type,
+                location: SourceLocation::new(file_path.clone(), 0, 0),
+            };
+            
+            symbol_table.insert(binding_symbol)?;
+        } else if let Some(nested) = nested {
+            // The nested pattern binds inside the variant's value
+            bind_arm_pattern(nested, &bound_type, symbol_table, file_path)?;
+        }
+    }
+    Ok(())
+}
+
+// Determine if a block of statements guarantees a return on all control-flow paths
+fn block_returns(stmts: &Vec<Statement>, symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<bool, CompilerError> {
+    let mut guaranteed = false;
+    for stmt in stmts {
+        match stmt {
+            Statement::Return(_) => { return Ok(true); }
+            Statement::Expression(Expression::Call { callee, .. })
+                if matches!(&**callee, Expression::Variable(name) if name == "exit" || symbol_table.noreturn_functions.contains(name)) => { return Ok(true); }
+            Statement::If { then_branch, else_branch, .. } => {
+                let then_ret = block_returns(then_branch, symbol_table, file_path)?;
+                let else_ret = if let Some(else_b) = else_branch {
+                    block_returns(else_b, symbol_table, file_path)?
+                } else { false };
+                if then_ret && else_ret { return Ok(true); }
+            }
+            Statement::While { body, .. } => {
+                // A while-loop alone cannot guarantee return (it may not execute)
+                let _ = block_returns(body, symbol_table, file_path)?; // analyze nested but ignore for guarantee
+            }
+            Statement::For { body, .. } => {
+                // Similarly, for-loops don't guarantee return by themselves
+                let _ = block_returns(body, symbol_table, file_path)?;
+            }
+            Statement::Expression(Expression::Match { arms, .. }) => {
+                // A statement match returns when it has a catch-all and every arm is a returning block
+                let has_wildcard = arms.iter().any(|arm| arm.pattern.alternatives().iter().any(|p| matches!(p, Pattern::Wildcard)));
+                let mut all_return = true;
+                for arm in arms {
+                    all_return &= match &arm.body {
+                        ArmBody::Block(body) => block_returns(body, symbol_table, file_path)?,
+                        ArmBody::Expression(_) => false,
+                    };
+                }
+                if has_wildcard && all_return { return Ok(true); }
+            }
+            _ => {}
+        }
+        guaranteed = false;
+    }
+    Ok(guaranteed)
+}
+// Warn about statements following a `return`, `break` or `continue` in the same block
+fn check_unreachable(body: &[Statement], symbol_table: &mut SymbolTable, file_path: &PathBuf) {
+    for (i, stmt) in body.iter().enumerate() {
+        match stmt {
+            Statement::Return(_) | Statement::Break(_) | Statement::Continue if i + 1 < body.len() => {
+                let location = SourceLocation::new(file_path.clone(), 0, 0);
+                symbol_table.warn(CompilerError::warning(
+                    ErrorKind::UnreachableCode,
+                    format!("{} statement(s) after this point will never run", body.len() - i - 1),
+                    location,
+                ).with_suggestion(Suggestion::simple(
+                    "remove the unreachable statements or the early exit before them"
+                )));
+                return;
+            }
+            Statement::If { then_branch, else_branch, .. } => {
+                check_unreachable(then_branch, symbol_table, file_path);
+                if let Some(else_branch) = else_branch {
+                    check_unreachable(else_branch, symbol_table, file_path);
+                }
+            }
+            Statement::While { body, .. } | Statement::For { body, .. } | Statement::Expression(Expression::Loop { body }) => {
+                check_unreachable(body, symbol_table, file_path);
+            }
+            Statement::Expression(Expression::Match { arms, .. }) => {
+                for arm in arms {
+                    if let ArmBody::Block(body) = &arm.body {
+                        check_unreachable(body, symbol_table, file_path);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Best effort: warn about a `while` loop whose condition variables are only updated after
+// its first `continue`, so that continuing never reaches the update and may loop forever
+fn check_continue_skips_update(condition: &Expression, body: &[Statement], symbol_table: &mut SymbolTable, location: &SourceLocation) {
+    let Some(first_continue) = body.iter().position(continues_loop) else { return };
+    let mut names = Vec::new();
+    condition_variables(condition, &mut names);
+    let (before, after) = body.split_at(first_continue + 1);
+    let Some(name) = names.iter().find(|name| !assigns(before, name) && assigns(after, name)) else { return };
+    symbol_table.warn(CompilerError::warning(
+        ErrorKind::ContinueSkipsUpdate,
+        format!("`continue` skips the update of `{}` at the end of this `while` loop", name),
+        location.clone(),
+    ).with_suggestion(Suggestion::simple(
+        format!("update `{}` before the `continue`, or use a `for` loop", name)
+    )));
+}
+
+// Whether a statement of a loop body can `continue` that loop (not a loop nested in it)
+fn continues_loop(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Continue => true,
+        Statement::If { then_branch, else_branch, .. } => {
+            then_branch.iter().any(continues_loop) || else_branch.iter().flatten().any(continues_loop)
+        }
+        Statement::Expression(Expression::Match { arms, .. }) => arms.iter().any(|arm| {
+            matches!(&arm.body, ArmBody::Block(body) if body.iter().any(continues_loop))
+        }),
+        _ => false,
+    }
+}
+
+// Variables read by a loop condition
+fn condition_variables(expr: &Expression, names: &mut Vec<String>) {
+    match expr {
+        Expression::Variable(name) if !names.contains(name) => names.push(name.clone()),
+        Expression::Binary { left, right, .. } | Expression::ArrayAccess { array: left, index: right } => {
+            condition_variables(left, names);
+            condition_variables(right, names);
+        }
+        Expression::Unary { operand: inner, .. }
+        | Expression::Cast { expression: inner, .. }
+        | Expression::StructAccess { object: inner, .. } => condition_variables(inner, names),
+        Expression::Call { arguments, .. } => {
+            for argument in arguments {
+                condition_variables(argument, names);
+            }
+        }
+        Expression::MethodCall { object, arguments, .. } => {
+            condition_variables(object, names);
+            for argument in arguments {
+                condition_variables(argument, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Whether any statement, at any depth, assigns to `name`
+fn assigns(body: &[Statement], name: &str) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Statement::Assignment { target: Expression::Variable(target), .. } => target == name,
+        Statement::If { then_branch, else_branch, .. } => {
+            assigns(then_branch, name) || else_branch.as_deref().is_some_and(|b| assigns(b, name))
+        }
+        Statement::While { body, .. } | Statement::For { body, .. } | Statement::Expression(Expression::Loop { body }) => assigns(body, name),
+        Statement::Expression(Expression::Match { arms, .. }) => arms.iter().any(|arm| {
+            matches!(&arm.body, ArmBody::Block(body) if assigns(body, name))
+        }),
+        _ => false,
+    })
+}
+
+// Best effort: whether every path through `body` calls `name` before it can return.
+// Anything that may return first (a `return` in a branch or loop) counts as a base case.
+fn always_recurses(body: &[Statement], name: &str) -> bool {
+    for stmt in body {
+        let recurses = match stmt {
+            Statement::Let { initializer, .. } => initializer.as_ref().is_some_and(|e| calls_function(e, name)),
+            Statement::Const { initializer, .. } | Statement::LetTuple { initializer, .. } => calls_function(initializer, name),
+            Statement::Assignment { target, value } => calls_function(target, name) || calls_function(value, name),
+            Statement::Return(value) => return value.as_ref().is_some_and(|e| calls_function(e, name)),
+            Statement::If { condition, then_branch, else_branch } => {
+                if calls_function(condition, name) {
+                    return true;
+                }
+                if let Some(else_branch) = else_branch {
+                    if always_recurses(then_branch, name) && always_recurses(else_branch, name) {
+                        return true;
+                    }
+                }
+                if contains_return(then_branch) || else_branch.as_deref().is_some_and(contains_return) {
+                    return false;
+                }
+                false
+            }
+            Statement::While { condition: head, body } | Statement::For { iterable: head, body, .. } => {
+                if calls_function(head, name) {
+                    return true;
+                }
+                if contains_return(body) {
+                    return false;
+                }
+                false
+            }
+            Statement::Expression(Expression::Match { scrutinee, arms }) => {
+                if calls_function(scrutinee, name) {
+                    return true;
+                }
+                if arms.iter().any(|arm| matches!(&arm.body, ArmBody::Block(body) if contains_return(body))) {
+                    return false;
+                }
+                false
+            }
+            Statement::Expression(expr) => calls_function(expr, name),
+            Statement::Break(_) | Statement::Continue => return false,
+        };
+        if recurses {
+            return true;
+        }
+    }
+    false
+}
+
+fn contains_return(body: &[Statement]) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Statement::Return(_) => true,
+        Statement::If { then_branch, else_branch, .. } => {
+            contains_return(then_branch) || else_branch.as_deref().is_some_and(contains_return)
+        }
+        Statement::While { body, .. } | Statement::For { body, .. } | Statement::Expression(Expression::Loop { body }) => contains_return(body),
+        Statement::Expression(Expression::Match { arms, .. }) => {
+            arms.iter().any(|arm| matches!(&arm.body, ArmBody::Block(body) if contains_return(body)))
+        }
+        _ => false,
+    })
+}
+
+// Whether evaluating `expr` always calls `name`; operands that are only
+// conditionally evaluated (`&&`/`||` right sides, ternary and match arms) are ignored
+fn calls_function(expr: &Expression, name: &str) -> bool {
+    match expr {
+        Expression::Call { callee, arguments } => {
+            matches!(&**callee, Expression::Variable(callee_name) if callee_name == name)
+                || calls_function(callee, name)
+                || arguments.iter().any(|arg| calls_function(arg, name))
+        }
+        Expression::MethodCall { object, arguments, .. } => {
+            calls_function(object, name) || arguments.iter().any(|arg| calls_function(arg, name))
+        }
+        Expression::Binary { left, operator: BinaryOp::And | BinaryOp::Or, .. } => calls_function(left, name),
+        Expression::Binary { left, right, .. } | Expression::Range { start: left, end: right } | Expression::ArrayAccess { array: left, index: right } => {
+            calls_function(left, name) || calls_function(right, name)
+        }
+        Expression::Unary { operand: inner, .. }
+        | Expression::Cast { expression: inner, .. }
+        | Expression::AssertType { expression: inner, .. }
+        | Expression::TryOperator { expression: inner }
+        | Expression::StructAccess { object: inner, .. }
+        | Expression::OptionalField { object: inner, .. }
+        | Expression::New(inner)
+        | Expression::Delete(inner)
+        | Expression::Ternary { condition: inner, .. }
+        | Expression::Match { scrutinee: inner, .. } => calls_function(inner, name),
+        Expression::ArrayLiteral(elements) | Expression::Tuple(elements) | Expression::DynamicArrayLiteral { elements, .. } => {
+            elements.iter().any(|e| calls_function(e, name))
+        }
+        Expression::StructLiteral { fields, base, .. } => {
+            fields.iter().any(|(_, e)| calls_function(e, name)) || base.as_deref().is_some_and(|b| calls_function(b, name))
+        }
+        Expression::InterpolatedString { parts } => parts.iter().any(|part| {
+            matches!(part, crate::ast::StringPart::Interpolation(e) if calls_function(e, name))
+        }),
+        Expression::Literal(_) | Expression::Variable(_) | Expression::EnumAccess { .. } | Expression::Loop { .. } => false,
+    }
+}
+
+// `x.to_string()` on an int, float, bool or char takes no arguments and makes a new string
+fn check_to_string_arguments(arguments: &[Expression], location: SourceLocation) -> Result<Type, CompilerError> {
+    if !arguments.is_empty() {
+        return Err(CompilerError::new(
+            ErrorKind::WrongArgumentCount,
+            format!("to_string() expects 0 arguments, got {}", arguments.len()),
+            location,
+        ));
+    }
+    Ok(Type::String)
+}
+
+// Resolve `object.name(...)` to a method from the impl block of the object's type
+fn impl_method_of(object_ty: &Type, name: &str, symbol_table: &SymbolTable) -> Option<(String, Function)> {
+    match object_ty {
+        Type::Struct(type_name) | Type::Enum(type_name) => {
+            symbol_table.get_impl_function(type_name, name).map(|func| (type_name.clone(), func.clone()))
+        }
+        _ => None,
+    }
+}
+
+// Check a call to an impl function. With a receiver (`obj.f(..)`) the function must be a
+// method and `self` is supplied by the object; `Type::f(..)` passes every parameter explicitly.
+fn check_impl_call(func: &Function, type_name: &str, arguments: &[Expression], with_receiver: bool, symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<Type, CompilerError> {
+    if func.name == "drop" {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        return Err(CompilerError::new(
+            ErrorKind::InvalidOperation,
+            format!("`{}::drop` cannot be called explicitly", type_name),
+            location,
+        ).with_suggestion(Suggestion::simple(
+            format!("`drop` runs by itself when a local `{}` goes out of scope", type_name)
+        )));
+    }
+    if with_receiver && !func.is_method() {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        return Err(CompilerError::new(
+            ErrorKind::InvalidOperation,
+            format!("`{}::{}` is an associated function, not a method", type_name, func.name),
+            location,
+        ).with_suggestion(Suggestion::simple(
+            format!("call it as `{}::{}(...)`", type_name, func.name)
+        )));
+    }
+    let params = if with_receiver { &func.parameters[1..] } else { &func.parameters[..] };
+    if params.len() != arguments.len() {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        return Err(CompilerError::new(
+            ErrorKind::WrongArgumentCount,
+            format!("`{}::{}` expects {} argument(s), got {}", type_name, func.name, params.len(), arguments.len()),
+            location,
+        ));
+    }
+    for (param, arg) in params.iter().zip(arguments) {
+        let arg_ty = infer_type(arg, symbol_table, file_path)?;
+        if !types_compatible(&param.param_type, &arg_ty) {
+            let location = SourceLocation::new(file_path.clone(), 0, 0);
+            return Err(type_mismatch(&format!("{:?}", param.param_type), &format!("{:?}", arg_ty), location)
+                .with_suggestion(Suggestion::simple(
+                    format!("argument `{}` of `{}::{}` has the wrong type", param.name, type_name, func.name)
+                )));
+        }
+    }
+    Ok(func.return_type.clone().unwrap_or(Type::Void))
+}
+
+// Opaque extern types have no known size, so they may only appear behind a pointer
+fn check_not_opaque_value(ty: &Type, symbol_table: &SymbolTable, file_path: &Path) -> Result<(), CompilerError> {
+    if let Type::Struct(name) = ty {
+        if symbol_table.opaque_types.contains(name) {
+            let location = SourceLocation::new(file_path.to_path_buf(), 0, 0);
+            return Err(CompilerError::new(
+                ErrorKind::InvalidOperation,
+                format!("opaque extern type `{}` cannot be used by value", name),
+                location,
+            ).with_suggestion(Suggestion::simple(
+                format!("use a pointer instead, e.g. `*{}`", name)
+            )));
+        }
+    }
+    Ok(())
+}
+
+// A `const fn` takes and returns plain integers and its body must be evaluable by ConstEvaluator
+fn check_const_function(func: &Function, symbol_table: &SymbolTable, file_path: &Path) -> Result<(), CompilerError> {
+    let is_scalar = |ty: &Type| matches!(ty, Type::Int | Type::Bool | Type::Char);
+    let problem = if !func.parameters.iter().all(|p| is_scalar(&p.param_type)) {
+        Some("its parameters must be int, bool or char".to_string())
+    } else if !func.return_type.as_ref().is_some_and(is_scalar) {
+        Some("it must return an int, bool or char".to_string())
+    } else {
+        check_const_body(&func.body, &|name| symbol_table.const_functions.contains_key(name)).err()
+    };
+    match problem {
+        Some(problem) => {
+            let location = SourceLocation::new(file_path.to_path_buf(), func.line, func.column);
+            Err(CompilerError::new(
+                ErrorKind::InvalidOperation,
+                format!("const fn `{}` cannot be evaluated at compile time: {}", func.name, problem),
+                location,
+            ).with_suggestion(Suggestion::simple(
+                "const fns may only use integer arithmetic, locals, if/while/for and calls to other const fns; remove `const` otherwise"
+            )))
+        }
+        None => Ok(()),
+    }
+}
+
+// Fixed length of an array type, when it is known at compile time
+fn fixed_array_length(ty: &Type, symbol_table: &SymbolTable) -> Option<usize> {
+    match ty {
+        Type::Array(_, Some(ArrayLength::Literal(n))) => Some(*n),
+        Type::Array(_, Some(ArrayLength::ConstCall { function, arguments })) => {
+            ConstEvaluator::new(&symbol_table.const_functions).call(function, arguments).ok()
+                .and_then(|n| usize::try_from(n).ok())
+        }
+        _ => None,
+    }
+}
+
+// A call to a const-generic function must fix each const parameter to a single length
+fn check_const_arguments(func: &Function, arguments: &[Expression], symbol_table: &mut SymbolTable, file_path: &PathBuf) -> Result<(), CompilerError> {
+    let mut lengths = Vec::new();
+    for arg in arguments {
+        let ty = infer_type(arg, symbol_table, file_path)?;
+        lengths.push(fixed_array_length(&ty, symbol_table));
+    }
+    func.const_param_values(&lengths).map(|_| ()).map_err(|message| {
+        let location = SourceLocation::new(file_path.clone(), 0, 0);
+        CompilerError::new(
+            ErrorKind::TypeMismatch,
+            format!("in call to `{}`: {}", func.name, message),
+            location,
+        ).with_suggestion(Suggestion::simple(
+            "pass arrays whose length is known at compile time, such as array literals or `[T; n]` variables"
+        ))
+    })
+}
+
+// Value of a condition that folds to a constant, like `true` or `1 == 1`
+fn constant_condition(condition: &Expression, symbol_table: &SymbolTable) -> Option<bool> {
+    ConstEvaluator::new(&symbol_table.const_functions).evaluate(condition).ok().map(|value| value != 0)
+}
+
+// Evaluate `[T; f(..)]` lengths so a failing const fn call is reported where the type is used
+fn check_array_length(ty: &Type, symbol_table: &SymbolTable, file_path: &PathBuf) -> Result<(), CompilerError> {
+    match ty {
+        Type::Array(_, Some(ArrayLength::Param(name))) if !symbol_table.const_params.contains(name) => {
+            let location = SourceLocation::new(file_path.clone(), 0, 0);
+            Err(CompilerError::new(
+                ErrorKind::UndefinedVariable,
+                format!("cannot find array length `{}`", name),
+                location,
+            ).with_suggestion(Suggestion::with_example(
+                "declare it as a const parameter of the function",
+                format!("fn f<const {}: int>(a: [int; {}])", name, name)
+            )))
+        }
+        Type::Array(elem, length) => {
+            if let Some(ArrayLength::ConstCall { function, arguments }) = length {
+                let value = ConstEvaluator::new(&symbol_table.const_functions).call(function, arguments);
+                let problem = match value {
+                    Ok(n) if n < 0 => Some(format!("length is negative ({})", n)),
+                    Ok(_) => None,
+                    Err(message) => Some(message),
+                };
+                if let Some(problem) = problem {
+                    let location = SourceLocation::new(file_path.clone(), 0, 0);
+                    return Err(CompilerError::new(
+                        ErrorKind::InvalidOperation,
+                        format!("cannot evaluate array length `{}(..)`: {}", function, problem),
+                        location,
+                    ).with_suggestion(Suggestion::simple(
+                        "array lengths must be integer literals or calls to a `const fn` with literal arguments"
+                    )));
+                }
+            }
+            check_array_length(elem, symbol_table, file_path)
+        }
+        Type::Pointer(inner) | Type::DynamicArray(inner) => check_array_length(inner, symbol_table, file_path),
+        _ => Ok(()),
+    }
+}
+
+// `-1` parses as a negated literal; the lexer never produces negative literals itself
+pub fn negative_constant_index(index: &Expression) -> Option<i64> {
+    match index {
+        Expression::Literal(Literal::Integer(n)) if *n < 0 => Some(*n),
+        Expression::Unary { operator: UnaryOp::Negate, operand } => match &**operand {
+            Expression::Literal(Literal::Integer(n)) if *n > 0 => Some(-*n),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser};
+
+    fn check(source: &str) -> Result<(), CompilerError> {
+        warnings(source).map(|_| ())
+    }
+
+    fn warnings(source: &str) -> Result<Vec<CompilerError>, CompilerError> {
+        let path = PathBuf::from("test.rapt");
+        let tokens = lexer::tokenize(source, &path)?;
+        let mut ast = parser::parse(tokens, path.clone())?;
+        infer_return_types(&mut ast, &HashMap::new(), &path)?;
+        analyze_with_warnings(&ast, &HashMap::new(), &path)
+    }
+
+    #[test]
+    fn test_struct_literal_complete() {
+        let src = "struct Point { x: int, y: int }\nfn main() -> int { let p = Point { x: 1, y: 2 }; return p.x; }";
+        assert!(check(src).is_ok());
+    }
+
+    #[test]
+    fn test_struct_literal_missing_fields() {
+        let src = "struct Point { x: int, y: int, z: int }\nfn main() -> int { let p = Point { y: 2 }; return p.y; }";
+        let err = check(src).unwrap_err();
+        assert!(err.message.contains("`x`, `z`"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_struct_literal_spread() {
+        let src = "struct Point { x: int, y: int }\nfn main() -> int { let a = Point { x: 1, y: 2 }; let b = Point { x: 5, ..a }; return b.y; }";
+        assert!(check(src).is_ok());
+    }
+
+    #[test]
+    fn test_negative_index() {
+        let dynamic = "fn main() -> int { let mut a = new [int](); a.push(1); a.push(2); return a[-1]; }";
+        assert!(check(dynamic).is_ok());
+        let string = "fn main() -> int { let s = \"abc\"; let c = s[-1]; return 0; }";
+        assert!(check(string).is_ok());
+        let fixed = "fn main() -> int { let a = [1, 2, 3]; return a[-1]; }";
+        assert!(check(fixed).is_err());
+    }
+
+    #[test]
+    fn test_print_argument_count() {
+        assert!(check("fn main() { print(); println(); }").is_ok());
+        let err = check("fn main() { println(1, 2); }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::WrongArgumentCount);
+    }
+
+    #[test]
+    fn test_opaque_extern_struct() {
+        let header = "extern struct FILE;\nextern fn fopen(path: string, mode: string) -> *FILE;\nextern fn fclose(f: *FILE) -> int;\n";
+        let ok = format!("{}fn main() -> int {{ let f = fopen(\"data.txt\", \"r\"); return fclose(f); }}", header);
+        assert!(check(&ok).is_ok());
+        let by_value = format!("{}fn use_file(f: FILE) {{ }}", header);
+        assert!(check(&by_value).unwrap_err().message.contains("cannot be used by value"));
+        let returned = format!("{}fn open() -> FILE {{ return *fopen(\"a\", \"r\"); }}", header);
+        assert!(check(&returned).unwrap_err().message.contains("cannot be used by value"));
+        let extern_returned = format!("{}extern fn stdin_file() -> FILE;\nfn main() -> int {{ return 0; }}", header);
+        assert!(check(&extern_returned).unwrap_err().message.contains("cannot be used by value"));
+        let struct_field = format!("{}struct Log {{ file: FILE }}\nfn main() -> int {{ return 0; }}", header);
+        assert!(check(&struct_field).unwrap_err().message.contains("cannot be used by value"));
+        let pointer_field = format!("{}struct Log {{ file: *FILE }}\nfn main() -> int {{ return 0; }}", header);
+        assert!(check(&pointer_field).is_ok());
+        let field = format!("{}fn main() -> int {{ let f = fopen(\"a\", \"r\"); let x = (*f).fd; return 0; }}", header);
+        assert!(check(&field).unwrap_err().message.contains("opaque extern type"));
+    }
+
+    #[test]
+    fn test_associated_function() {
+        let header = "struct Point { x: int, y: int }\nimpl Point {\n    fn new(x: int, y: int) -> Point { return Point { x: x, y: y }; }\n    fn origin() -> Point { return Point { x: 0, y: 0 }; }\n}\n";
+        let ok = format!("{}fn main() -> int {{ let p = Point::new(1, 2); return p.x; }}", header);
+        assert!(check(&ok).is_ok());
+        let bad_args = format!("{}fn main() -> int {{ let p = Point::new(1); return p.x; }}", header);
+        assert_eq!(check(&bad_args).unwrap_err().kind, ErrorKind::WrongArgumentCount);
+        let as_method = format!("{}fn main() -> int {{ let p = Point::new(1, 2); let q = p.origin(); return 0; }}", header);
+        assert!(check(&as_method).unwrap_err().message.contains("not a method"));
+    }
+
+    #[test]
+    fn test_option_comparison() {
+        let eq = "fn main() -> int { let a: Option<int> = Option::Some(1); let n: Option<int> = Option::None; if a == n { return 1; } return 0; }";
+        assert!(check(eq).is_ok());
+        let ordered = "fn main() -> int { let a: Option<int> = Option::Some(1); let b: Option<int> = Option::Some(2); if a < b { return 1; } return 0; }";
+        assert!(check(ordered).unwrap_err().message.contains("cannot order"));
+    }
+
+    #[test]
+    fn test_unreachable_code_warning() {
+        let found = warnings("fn main() -> int { return 0; println(1); }").unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(found[0].is_warning());
+        assert_eq!(found[0].kind, ErrorKind::UnreachableCode);
+        assert!(warnings("fn main() -> int { println(1); return 0; }").unwrap().is_empty());
+    }
+    #[test]
+    fn test_match_arm_block_with_return() {
+        let early = "fn sign(n: int) -> int { match n { 0 => { return 0; } _ => { let m = n * 2; println(m); } } return 1; }";
+        assert!(check(early).is_ok());
+        let all_paths = "fn sign(n: int) -> int { match n { 0 => { return 0; } _ => { return 1; } } }";
+        assert!(check(all_paths).is_ok());
+        let as_value = "fn main() -> int { let x = match 1 { 0 => { return 0; } _ => 1 }; return x; }";
+        assert!(check(as_value).unwrap_err().message.contains("does not produce a value"));
+    }
+
+    #[test]
+    fn test_slicing() {
+        assert!(check("fn main() -> int { let s = \"hello\"; let t: string = s[1..3]; return 0; }").is_ok());
+        assert!(check("fn main() -> int { let mut a = new [int](); a.push(1); let b = a[0..1]; return b[0]; }").is_ok());
+        let fixed = "fn main() -> int { let a = [1, 2, 3]; let b = a[0..2]; return 0; }";
+        assert!(check(fixed).unwrap_err().message.contains("cannot slice"));
+    }
+
+    #[test]
+    fn test_hex_builtin() {
+        assert!(check("fn main() -> int { let h: string = hex(255); return 0; }").is_ok());
+        assert!(check("fn main() -> int { let h = hex(\"ff\"); return 0; }").is_err());
+    }
+
+    #[test]
+    fn test_env_builtin() {
+        let src = "fn main() -> int { let home = env(\"HOME\"); match home { Option::Some(path) => println(path), Option::None => println(\"unset\") } return 0; }";
+        assert!(check(src).is_ok());
+        let typed = "fn main() -> int { let v: Option<string> = env(\"NOPE\"); return 0; }";
+        assert!(check(typed).is_ok());
+        assert!(check("fn main() -> int { let v = env(1); return 0; }").is_err());
+    }
+
+    #[test]
+    fn test_struct_pointer_casts() {
+        let header = "struct A { x: int }\nstruct B { y: int }\n";
+        let reinterpret = format!("{}fn main() -> int {{ let a = A {{ x: 1 }}; let p = &a; let q = p as *B; return 0; }}", header);
+        let found = warnings(&reinterpret).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, ErrorKind::PointerReinterpretCast);
+        // The same cast in two functions is reported for each
+        let twice = format!("{}fn f(p: *A) {{ let q = p as *B; }}\nfn g(p: *A) {{ let q = p as *B; }}\nfn main() -> int {{ return 0; }}", header);
+        assert_eq!(warnings(&twice).unwrap().len(), 2);
+        let same = format!("{}fn main() -> int {{ let a = A {{ x: 1 }}; let p = &a; let q = p as *A; return 0; }}", header);
+        assert!(warnings(&same).unwrap().is_empty());
+        let nonsense = format!("{}fn main() -> int {{ let a = A {{ x: 1 }}; let b = a as B; return 0; }}", header);
+        assert!(check(&nonsense).unwrap_err().message.contains("cannot cast"));
+    }
+
+    #[test]
+    fn test_match_outer_binding() {
+        let ok = "fn main() -> int { let o: Option<int> = Option::Some(3); match o { v @ Option::Some(_) => { if v == Option::Some(3) { return 1; } } Option::None => {} } return 0; }";
+        assert!(check(ok).is_ok());
+        // The binding is scoped to its own arm
+        let leaked = "fn main() -> int { let x = match 4 { k @ 0 => k, _ => k }; return x; }";
+        assert!(check(leaked).is_err());
+    }
+
+    #[test]
+    fn test_nested_variant_pattern() {
+        let prelude = "fn ok(n: int) -> Result<int, string> { return Result::Ok(n); }\nfn some(r: Result<int, string>) -> Option<Result<int, string>> { return Option::Some(r); }\n";
+        let ok = format!("{}fn main() -> int {{ match some(ok(5)) {{ Option::Some(Result::Ok(x)) => {{ return x + 1; }} Option::Some(Result::Err(e)) => {{ println(e); }} Option::None => {{}} }} return 0; }}", prelude);
+        assert!(check(&ok).is_ok(), "{:?}", check(&ok));
+        // The nested pattern is checked against the variant's value type
+        let wrong = format!("{}fn main() -> int {{ match some(ok(5)) {{ Option::Some(Option::Some(x)) => {{ return x; }} _ => {{}} }} return 0; }}", prelude);
+        assert_eq!(check(&wrong).unwrap_err().kind, ErrorKind::TypeMismatch);
+        let literal = format!("{}fn main() -> int {{ match some(ok(5)) {{ Option::Some(Result::Ok(\"five\")) => {{ return 5; }} _ => {{}} }} return 0; }}", prelude);
+        assert_eq!(check(&literal).unwrap_err().kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_continue_before_loop_update_warning() {
+        let skipped = warnings("fn main() -> int { let mut i = 0; let mut odd = 0; while i < 10 { if i == 5 { continue; } odd = odd + 1; i = i + 1; } return odd; }").unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].kind, ErrorKind::ContinueSkipsUpdate);
+        assert_eq!(skipped[0].message, "`continue` skips the update of `i` at the end of this `while` loop");
+
+        // Updating before the `continue`, or on its way out, is fine
+        let early = "fn main() -> int { let mut i = 0; while i < 10 { i = i + 1; if i == 5 { continue; } } return i; }";
+        assert!(warnings(early).unwrap().is_empty());
+        let on_exit = "fn main() -> int { let mut i = 0; while i < 10 { if i == 5 { i = i + 2; continue; } i = i + 1; } return i; }";
+        assert!(warnings(on_exit).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_deprecated_function_warning() {
+        let src = "@deprecated(\"use bar instead\")\nfn foo() -> int { return 1; }\nfn bar() -> int { return 2; }\nfn main() -> int { return foo() + bar(); }";
+        let found = warnings(src).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, ErrorKind::DeprecatedFunction);
+        assert_eq!(found[0].message, "`foo` is deprecated: use bar instead");
+        // Declaring a deprecated function without calling it is fine
+        let unused = "@deprecated\nfn foo() -> int { return 1; }\nfn main() -> int { return 0; }";
+        assert!(warnings(unused).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_constant_condition_warning() {
+        let always = warnings("fn main() -> int { if 1 == 1 { return 1; } return 0; }").unwrap();
+        assert_eq!(always.len(), 1);
+        assert_eq!(always[0].kind, ErrorKind::ConstantCondition);
+        assert_eq!(always[0].message, "`if` condition is always true");
+        let never = warnings("fn main() -> int { let mut i = 0; while 2 < 1 { i = i + 1; } return i; }").unwrap();
+        assert_eq!(never[0].message, "`while` condition is always false");
+
+        let genuine = "fn main() -> int { let n = 3; if n > 2 { return 1; } return 0; }";
+        assert!(warnings(genuine).unwrap().is_empty());
+        let infinite = "fn main() -> int { let mut i = 0; while true { i = i + 1; if i > 3 { break; } } return i; }";
+        assert!(warnings(infinite).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unbounded_recursion_warning() {
+        let infinite = warnings("fn f(n: int) -> int { let m = n + 1; return f(m); }\nfn main() -> int { return f(0); }").unwrap();
+        assert_eq!(infinite.len(), 1);
+        assert_eq!(infinite[0].kind, ErrorKind::UnboundedRecursion);
+
+        let base_case = "fn fact(n: int) -> int { if n <= 1 { return 1; } return n * fact(n - 1); }\nfn main() -> int { return fact(5); }";
+        assert!(warnings(base_case).unwrap().is_empty());
+        let conditional = "fn count(n: int) { if n > 0 { count(n - 1); } }\nfn main() -> int { count(3); return 0; }";
+        assert!(warnings(conditional).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_function_reports_both_locations() {
+        let src = "fn helper() -> int { return 1; }\nfn main() -> int { return helper(); }\nfn helper() -> int { return 2; }";
+        let err = check(src).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::DuplicateDefinition);
+        assert_eq!(err.location.line, 3);
+        assert_eq!(err.related_errors[0].location.line, 1);
+    }
+
+    #[test]
+    fn test_one_sided_trim() {
+        assert!(check("fn main() -> int { let s = \"  lead\"; let t = \"trail  \"; let a: string = s.trim_start(); let b: string = t.trim_end(); return 0; }").is_ok());
+        let err = check("fn main() -> int { let s = \"x \"; let a = s.trim_end(1); return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::WrongArgumentCount);
+    }
+
+    #[test]
+    fn test_delete_requires_pointer() {
+        assert!(check("fn main() -> int { let p = new 5; delete p; return 0; }").is_ok());
+        let literal = check("fn main() -> int { delete 5; return 0; }").unwrap_err();
+        assert!(literal.message.contains("cannot delete"), "{}", literal.message);
+        let int_var = check("fn main() -> int { let n = 3; delete n; return 0; }").unwrap_err();
+        assert_eq!(int_var.kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_in_range_builtin() {
+        assert!(check("fn main() -> int { let x = 4; if in_range(x, 1, 10) { return 1; } return 0; }").is_ok());
+        assert!(check("fn main() -> int { let ok: bool = in_range(2.5, 0, 3.0); return 0; }").is_ok());
+        let err = check("fn main() -> int { let ok = in_range(\"a\", 0, 3); return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        let err = check("fn main() -> int { let ok = in_range(1, 2); return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::WrongArgumentCount);
+    }
+
+    #[test]
+    fn test_variadic_extern_arguments() {
+        let ext = "extern fn printf(format: *char, ...) -> int;\n";
+        assert!(check(&format!("{}fn main() -> int {{ printf(\"%d %s\\n\", 1, \"x\"); return 0; }}", ext)).is_ok());
+        let wrong = check(&format!("{}fn main() -> int {{ printf(42, 1); return 0; }}", ext)).unwrap_err();
+        assert_eq!(wrong.kind, ErrorKind::TypeMismatch);
+        let missing = check(&format!("{}fn main() -> int {{ printf(); return 0; }}", ext)).unwrap_err();
+        assert_eq!(missing.kind, ErrorKind::WrongArgumentCount);
+        // Non-variadic externs take exactly their declared arguments
+        let extra = check("extern fn strlen(s: *char) -> int;\nfn main() -> int { let n = strlen(\"a\", 2); return 0; }").unwrap_err();
+        assert_eq!(extra.kind, ErrorKind::WrongArgumentCount);
+    }
+
+    #[test]
+    fn test_struct_field_defaults() {
+        let header = "struct Config { name: string, retries: int = 3 }\n";
+        assert!(check(&format!("{}fn main() -> int {{ let c = Config {{ name: \"a\" }}; return c.retries; }}", header)).is_ok());
+        assert!(check(&format!("{}fn main() -> int {{ let c = Config {{ name: \"a\", retries: 5 }}; return c.retries; }}", header)).is_ok());
+        // Fields without a default are still required
+        let missing = check(&format!("{}fn main() -> int {{ let c = Config {{ retries: 1 }}; return 0; }}", header)).unwrap_err();
+        assert!(missing.message.contains("`name`"), "{}", missing.message);
+        let bad_default = check("struct S { x: int = \"three\" }\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(bad_default.kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_char_array_is_not_a_string() {
+        let chars = "let chars = new [char](); chars.push('a');";
+        for rejected in ["let s: string = chars;", "let s: str = chars;", "let n = len(chars);"] {
+            let src = format!("fn main() -> int {{ {} {} return 0; }}", chars, rejected);
+            assert!(check(&src).is_err(), "{}", rejected);
+        }
+        let ext = format!("extern fn puts(s: *char) -> int;\nfn main() -> int {{ {} puts(chars); return 0; }}", chars);
+        assert_eq!(check(&ext).unwrap_err().kind, ErrorKind::TypeMismatch);
+        let converted = format!("extern fn puts(s: *char) -> int;\nfn main() -> int {{ {} let s: string = chars.to_string(); puts(s); return len(s); }}", chars);
+        assert!(check(&converted).is_ok());
+    }
+
+    #[test]
+    fn test_exit_terminates_for_return_analysis() {
+        assert!(check("fn parse(n: int) -> int { if n >= 0 { return n; } else { exit(1); } }\nfn main() -> int { return parse(2); }").is_ok());
+        assert!(check("fn parse(n: int) -> int { if n >= 0 { return n; } }\nfn main() -> int { return parse(2); }").is_err());
+        let bad_code = check("fn main() -> int { exit(\"no\"); }").unwrap_err();
+        assert_eq!(bad_code.kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_string_char_vector_casts() {
+        assert!(check("fn main() -> int { let s = \"hi\"; let chars = s as DynamicArray[char]; let back: string = chars as string; return len(back); }").is_ok());
+        let ints = check("fn main() -> int { let s = \"hi\"; let v = s as DynamicArray[int]; return 0; }").unwrap_err();
+        assert!(ints.message.contains("cannot cast"), "{}", ints.message);
+    }
+
+    #[test]
+    fn test_const_fn() {
+        let square = "const fn square(n: int) -> int { return n * n; }\n";
+        assert!(check(&format!("{}fn main() -> int {{ let a: [int; square(3)]; a[8] = 1; return square(2); }}", square)).is_ok());
+        let impure = check("const fn noisy(n: int) -> int { println(n); return n; }\nfn main() -> int { return noisy(1); }").unwrap_err();
+        assert!(impure.message.contains("const fn `noisy` cannot be evaluated at compile time"), "{}", impure.message);
+        let calls_runtime = check("fn twice(n: int) -> int { return n * 2; }\nconst fn quad(n: int) -> int { return twice(twice(n)); }\nfn main() -> int { return quad(1); }").unwrap_err();
+        assert!(calls_runtime.message.contains("not a const fn"), "{}", calls_runtime.message);
+        let runtime_length = check("fn three() -> int { return 3; }\nfn main() -> int { let a: [int; three()]; return 0; }").unwrap_err();
+        assert!(runtime_length.message.contains("`three` is not a const fn"), "{}", runtime_length.message);
+    }
+
+    #[test]
+    fn test_const_generic_array_length() {
+        let sum = "fn sum<const N: int>(a: [int; N]) -> int {\n    let mut total = 0;\n    for i : 0..N {\n        total = total + a[i];\n    }\n    return total;\n}\n";
+        assert!(check(&format!("{}fn main() -> int {{ let a = [1, 2, 3]; let b: [int; 5]; return sum(a) + sum(b) + sum([4]); }}", sum)).is_ok());
+        let mismatch = check("fn dot<const N: int>(a: [int; N], b: [int; N]) -> int { return N; }\nfn main() -> int { return dot([1, 2], [1, 2, 3]); }").unwrap_err();
+        assert_eq!(mismatch.message, "in call to `dot`: `N` is both 2 and 3 in this call");
+        let unknown = check(&format!("{}fn g(a: [int]) -> int {{ return sum(a); }}\nfn main() -> int {{ return 0; }}", sum)).unwrap_err();
+        assert!(unknown.message.contains("cannot infer `N`"), "{}", unknown.message);
+        let unused = check("fn f<const N: int>(n: int) -> int { return N; }\nfn main() -> int { return f(1); }").unwrap_err();
+        assert!(unused.message.contains("is not the length of any parameter"), "{}", unused.message);
+    }
+
+    #[test]
+    fn test_assignment_target_must_be_lvalue() {
+        for target in ["5", "f()", "x + 1", "s[0..1]"] {
+            let src = format!("fn f() -> int {{ return 1; }}\nfn main() -> int {{ let x = 1; let s = \"ab\"; {} = x; return 0; }}", target);
+            let err = check(&src).unwrap_err();
+            assert_eq!(err.message, "invalid assignment target", "{}", target);
+        }
+        let ok = "struct P { x: int }\nfn main() -> int { let x = 1; let a = [1, 2]; let p = P { x: 1 }; let q = &x; x = 2; a[0] = 3; p.x = 4; *q = 5; return x; }";
+        assert!(check(ok).is_ok());
+    }
+
+    #[test]
+    fn test_function_passed_as_comparator() {
+        assert!(check("fn descending(a: int, b: int) -> int {\n    return b - a;\n}\nfn first_of(a: int, b: int, cmp: fn(int, int) -> int) -> int {\n    if cmp(a, b) <= 0 {\n        return a;\n    }\n    return b;\n}\nfn main() -> int {\n    return first_of(3, 7, descending);\n}").is_ok());
+        let err = check("fn descending(a: int, b: int) -> int {\n    return b - a;\n}\nfn main() -> int {\n    let f: fn(int) -> int = descending;\n    return f(1);\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        let err = check("fn apply(f: fn(int) -> int) -> int {\n    return f(1, 2);\n}\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::WrongArgumentCount);
+    }
+
+    #[test]
+    fn test_map_and_filter_signatures() {
+        assert!(check("fn square(n: int) -> int {\n    return n * n;\n}\nfn is_even(n: int) -> bool {\n    return n % 2 == 0;\n}\nfn main() -> int {\n    let nums = new [int]();\n    nums.push(3);\n    let squares = nums.map(square);\n    let evens = nums.filter(is_even);\n    return squares.length() + evens.length();\n}").is_ok());
+        // filter needs a predicate
+        let err = check("fn square(n: int) -> int {\n    return n * n;\n}\nfn main() -> int {\n    let nums = new [int]();\n    let odd = nums.filter(square);\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        // map's function must take the element type
+        let err = check("fn shout(s: string) -> string {\n    return s;\n}\nfn main() -> int {\n    let nums = new [int]();\n    let loud = nums.map(shout);\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_test_function_signature() {
+        assert!(check("@test\nfn it_works() {\n    assert(true, \"always\");\n}\nfn main() -> int { return 0; }").is_ok());
+        let err = check("@test\nfn needs_input(n: int) {\n    assert(n > 0);\n}\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperation);
+        let err = check("fn main() -> int {\n    assert(1);\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_err_binding_has_error_type() {
+        assert!(check("fn parse(n: int) -> Result<int, string> {\n    if n > 0 {\n        return Result::Ok(n);\n    }\n    return Result::Err(\"negative\");\n}\nfn main() -> int {\n    let r = parse(1);\n    let size = match r {\n        Result::Ok(v) => v,\n        Result::Err(v) => len(v),\n    };\n    return size;\n}").is_ok());
+    }
+
+    #[test]
+    fn test_return_type_inferred_from_returns() {
+        let src = "fn twice(n: int) {\n    if n < 0 {\n        return 0;\n    }\n    return n * 2;\n}\nfn main() -> int {\n    let x: int = twice(4);\n    return x;\n}";
+        let path = PathBuf::from("test.rapt");
+        let mut ast = parser::parse(lexer::tokenize(src, &path).unwrap(), path.clone()).unwrap();
+        infer_return_types(&mut ast, &HashMap::new(), &path).unwrap();
+        assert_eq!(ast.functions[0].return_type, Some(Type::Int));
+        assert!(check(src).is_ok());
+        // An explicit annotation wins
+        let err = check("fn name() -> int {\n    return \"x\";\n}\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        let err = check("fn pick(n: int) {\n    if n < 0 {\n        return 0;\n    }\n    return \"many\";\n}\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        // A path without a return is reported against the inferred type
+        let err = check("fn sign(n: int) {\n    if n < 0 {\n        return 0;\n    }\n}\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(err.message, "function `sign` is inferred to return `Int` but not all paths return a value");
+    }
+
+    #[test]
+    fn test_return_type_inferred_through_calls() {
+        let infer = |src: &str| {
+            let path = PathBuf::from("test.rapt");
+            let mut ast = parser::parse(lexer::tokenize(src, &path).unwrap(), path.clone()).unwrap();
+            infer_return_types(&mut ast, &HashMap::new(), &path).map_err(|error| error.message)?;
+            Ok::<_, String>(ast.functions.iter().map(|f| f.return_type.clone()).collect::<Vec<_>>())
+        };
+        // Recursion takes its type from the base case, forward calls from the later function
+        let fact = "fn fact(n: int) { if n <= 1 { return 1; } return n * fact(n - 1); }\nfn main() -> int { return fact(5); }";
+        assert_eq!(infer(fact).unwrap()[0], Some(Type::Int));
+        assert!(check(fact).is_ok(), "{:?}", check(fact));
+        let forward = "fn a() { return b() + 1; }\nfn b() { return 2; }\nfn main() -> int { return a(); }";
+        assert_eq!(infer(forward).unwrap()[..2], [Some(Type::Int), Some(Type::Int)]);
+        assert!(check(forward).is_ok(), "{:?}", check(forward));
+        // A recursive return of the wrong type is still caught once the type is known
+        let err = check("fn f(n: int) { if n == 0 { return 1; } return \"x\" + f(n - 1).to_string(); }\nfn main() -> int { return f(1); }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        let endless = infer("fn f() { return g(); }\nfn g() { return f(); }\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(endless, "cannot infer the return type of `f`: every value it returns depends on a function whose type is not known");
+    }
+
+    #[test]
+    fn test_match_on_void_is_rejected() {
+        let err = check("fn log(msg: string) {\n    println(msg);\n}\nfn main() -> int {\n    match log(\"x\") {\n        _ => {}\n    }\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        assert!(err.message.contains("no value"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_loop_break_values() {
+        assert!(check("fn main() -> int {\n    let values = [3, 8, 15];\n    let mut i = 0;\n    let found: int = loop {\n        if values[i] > 5 {\n            break values[i];\n        }\n        i = i + 1;\n    };\n    return found;\n}").is_ok());
+        let err = check("fn main() -> int {\n    let x = loop {\n        break;\n    };\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperation);
+        let err = check("fn main() -> int {\n    let x = loop {\n        return 1;\n    };\n    return 0;\n}").unwrap_err();
+        assert!(err.message.contains("never breaks with a value"), "{}", err.message);
+        let err = check("fn main() -> int {\n    let b = true;\n    let x = loop {\n        if b {\n            break 1;\n        }\n        break \"one\";\n    };\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        let err = check("fn main() -> int {\n    while true {\n        break 1;\n    }\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperation);
+    }
+
+    #[test]
+    fn test_break_and_continue_need_a_loop() {
+        let src = "fn next(n: int) -> Option<int> { if n < 3 { return Option::Some(n); } return Option::None; }\nfn main() -> int { let mut i = 0; while let Option::Some(x) = next(i) { i = i + 1; if x == 0 { continue; } if x == 2 { break; } } return i; }";
+        assert!(check(src).is_ok(), "{:?}", check(src));
+        let err = check("fn main() -> int { let o: Option<int> = Option::Some(1); if let Option::Some(x) = o { break; } return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperation);
+        assert_eq!(err.message, "`break` outside of a loop");
+        let err = check("fn main() -> int { continue; return 0; }").unwrap_err();
+        assert_eq!(err.message, "`continue` outside of a loop");
+    }
+
+    #[test]
+    fn test_array_equality() {
+        assert!(check("fn main() -> int { let a = new [int](); let b = new [int](); if a == b { return 1; } return 0; }").is_ok());
+        let err = check("fn main() -> int { let a = [1, 2]; let b = [1, 2]; if a == b { return 1; } return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperation);
+        assert!(err.message.starts_with("cannot compare fixed-size arrays"), "{}", err.message);
+        let err = check("fn main() -> int { let a = new [int](); let b = new [int](); if a < b { return 1; } return 0; }").unwrap_err();
+        assert!(err.message.starts_with("cannot order values"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_value_match_must_be_exhaustive() {
+        let err = check("fn main() -> int { let n = 3; let x = match n { 0 => 1, 1 => 2 }; return x; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidSyntax);
+        assert!(err.message.starts_with("non-exhaustive match"), "{}", err.message);
+        assert!(check("fn main() -> int { let n = 3; let x = match n { 0 => 1, _ => 2 }; return x; }").is_ok());
+        assert!(check("fn main() -> int { let b = true; let x = match b { true => 1, false => 0 }; return x; }").is_ok());
+        // A statement match may skip values
+        assert!(check("fn main() -> int { let n = 3; match n { 0 => { return 1; } } return 0; }").is_ok());
+    }
+
+    #[test]
+    fn test_implicit_self_field() {
+        let src = "struct Counter { count: int, label: string }\nimpl Counter { fn next(self) -> int { let label = 2; return count + label; } }\nfn main() -> int { return 0; }";
+        assert!(check(src).is_ok(), "{:?}", check(src));
+        // A field is a method receiver too, not a module name
+        let receiver = "struct User { id: int, tags: DynamicArray[int] }\nimpl User { fn label(self) -> string { println(tags.length()); return id.to_string(); } }\nfn main() -> int { return 0; }";
+        assert!(check(receiver).is_ok(), "{:?}", check(receiver));
+        // Outside a method a field name is no variable
+        let err = check("struct Counter { count: int }\nfn main() -> int { return count; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UndefinedVariable);
+    }
+
+    #[test]
+    fn test_assert_type() {
+        assert!(check("fn main() -> int { assert_type(1.5, float); let n = assert_type(2 + 3, int); return n; }").is_ok());
+        assert!(check("enum Color { Red }\nfn main() -> int { assert_type(Color::Red, Color); assert_type(\"s\", string); return 0; }").is_ok());
+        let err = check("fn main() -> int { assert_type(1.5, int); return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        assert_eq!(err.message, "assert_type failed: expression has type `Float`, expected `Int`");
+    }
+
+    #[test]
+    fn test_while_header_binding_scope() {
+        assert!(check("fn main() -> int { let mut n = 3; while (let x = n; x > 0) { n = x - 1; } return n; }").is_ok());
+        let err = check("fn main() -> int { let mut n = 3; while (let x = n; x > 0) { n = x - 1; } return x; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UndefinedVariable);
+    }
+
+    #[test]
+    fn test_ref_parameters() {
+        let ok = "fn inc(mut ref n: int) { n = n + 1; }\nfn get(ref n: int) -> int { return n; }\nfn main() -> int { let mut a = 1; inc(a); return get(a); }";
+        assert!(check(ok).is_ok(), "{:?}", check(ok));
+        // Only a `mut ref` parameter can be assigned through
+        let read_only = "struct P { x: int }\nfn set(ref p: P) { p.x = 2; }\nfn main() -> int { return 0; }";
+        let err = check(read_only).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ImmutableAssignment);
+        assert_eq!(err.message, "cannot assign through `ref` parameter `p`");
+        let passed_on = "fn inc(mut ref n: int) { n = n + 1; }\nfn f(ref n: int) { inc(n); }\nfn main() -> int { return 0; }";
+        assert_eq!(check(passed_on).unwrap_err().kind, ErrorKind::ImmutableAssignment);
+        // A `mut ref` argument must be declared mutable
+        let immutable = "fn set(mut ref n: int) { n = 3; }\nfn main() -> int { let a = 1; set(a); return a; }";
+        let err = check(immutable).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ImmutableAssignment);
+        assert_eq!(err.message, "cannot pass immutable `a` as `mut ref` `n`");
+        let field = "struct P { x: int }\nfn set(mut ref n: int) { n = 3; }\nfn main() -> int { let p = P { x: 1 }; set(p.x); return p.x; }";
+        assert_eq!(check(field).unwrap_err().kind, ErrorKind::ImmutableAssignment);
+        let by_value = "fn set(mut ref n: int) { n = 3; }\nfn f(n: int) { set(n); }\nfn main() -> int { return 0; }";
+        assert_eq!(check(by_value).unwrap_err().kind, ErrorKind::ImmutableAssignment);
+        let forwarded = "fn set(mut ref n: int) { n = 3; }\nfn f(mut ref n: int) { set(n); }\nfn main() -> int { let mut a = 1; f(a); return a; }";
+        assert!(check(forwarded).is_ok(), "{:?}", check(forwarded));
+        // The caller must pass a variable of the parameter's type
+        let temporary = "fn inc(mut ref n: int) { n = n + 1; }\nfn main() -> int { inc(1 + 2); return 0; }";
+        assert_eq!(check(temporary).unwrap_err().kind, ErrorKind::InvalidOperation);
+        let wrong_type = "fn inc(mut ref n: int) { n = n + 1; }\nfn main() -> int { let mut x = 1.5; inc(x); return 0; }";
+        assert_eq!(check(wrong_type).unwrap_err().kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_integer_width_arithmetic() {
+        let widened = "fn main() -> int { let big: long = 3000000000; let n = 2; assert_type(n + big, long); assert_type(big * n, long); assert_type(n + n, int); return 0; }";
+        assert!(check(widened).is_ok(), "{:?}", check(widened));
+        // Floats still dominate integers of any width
+        assert!(check("fn main() -> int { let big: long = 3; assert_type(1 + 0.5, float); assert_type(big + 0.5, float); return 0; }").is_ok());
+        let err = check("fn main() -> int { let big: long = 3; assert_type(1 + big, int); return 0; }").unwrap_err();
+        assert_eq!(err.message, "assert_type failed: expression has type `Long`, expected `Int`");
+        // Narrowing back to int takes a cast
+        assert!(check("fn main() -> int { let big: long = 3; let n: int = big; return n; }").is_err());
+        assert!(check("fn main() -> int { let big: long = 3; return big as int; }").is_ok());
+    }
+
+    #[test]
+    fn test_drop_method_signature() {
+        assert!(check("struct G { id: int }\nimpl G { fn drop(self) { println(id); } }\nfn main() -> int { let g = G { id: 1 }; return g.id; }").is_ok());
+        let err = check("struct G { id: int }\nimpl G { fn drop(self, n: int) {} }\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(err.message, "`G::drop` must take only `self` and return nothing");
+        assert!(check("struct G { id: int }\nimpl G { fn drop(self) -> int { return id; } }\nfn main() -> int { return 0; }").is_err());
+        // Calling it by hand would drop the value twice
+        let explicit = check("struct G { id: int }\nimpl G { fn drop(self) {} }\nfn main() -> int { let g = G { id: 1 }; g.drop(); return 0; }").unwrap_err();
+        assert_eq!(explicit.kind, ErrorKind::InvalidOperation);
+    }
+
+    #[test]
+    fn test_read_lines_builtin() {
+        assert!(check("fn main() -> int { let lines = read_lines(\"in.txt\"); assert_type(lines, DynamicArray[string]); return lines.length(); }").is_ok());
+        assert_eq!(check("fn main() -> int { let lines = read_lines(3); return 0; }").unwrap_err().kind, ErrorKind::TypeMismatch);
+        assert_eq!(check("fn main() -> int { let lines = read_lines(); return 0; }").unwrap_err().kind, ErrorKind::WrongArgumentCount);
+    }
+
+    #[test]
+    fn test_optional_field_types() {
+        let prelude = "struct Point { x: int, y: float }\nfn find() -> Option<Point> { return Option::None; }\n";
+        let ok = format!("{}fn main() -> int {{ assert_type(find()?.x, Option<int>); let p = Point {{ x: 1, y: 2.0 }}; let ptr = &p; assert_type(ptr?.y, Option<float>); return 0; }}", prelude);
+        assert!(check(&ok).is_ok(), "{:?}", check(&ok));
+        let unknown = format!("{}fn main() -> int {{ let z = find()?.z; return 0; }}", prelude);
+        assert_eq!(check(&unknown).unwrap_err().message, "unknown field `Point.z`");
+        let not_optional = format!("{}fn main() -> int {{ let p = Point {{ x: 1, y: 2.0 }}; let x = p?.x; return 0; }}", prelude);
+        assert_eq!(check(&not_optional).unwrap_err().kind, ErrorKind::InvalidOperation);
+    }
+
+    #[test]
+    fn test_dynamic_array_data_and_len() {
+        assert!(check("fn main() -> int { let mut v = new [int](); v.push(1); assert_type(v.data(), *int); return v.len(); }").is_ok());
+        assert_eq!(check("fn main() -> int { let v = new [int](); let p = v.data(1); return 0; }").unwrap_err().kind, ErrorKind::WrongArgumentCount);
+    }
+
+    #[test]
+    fn test_nil_literal() {
+        assert!(check("fn main() -> int { let x = 1; let mut p: *int = nil; p = &x; if p == nil { return 1; } let s: string = nil; if nil != s { return 2; } return 0; }").is_ok());
+        assert_eq!(check("fn main() -> int { let n: int = nil; return 0; }").unwrap_err().kind, ErrorKind::TypeMismatch);
+        assert_eq!(check("fn main() -> int { let p = nil; return 0; }").unwrap_err().message, "cannot infer the type of `p` from `nil`");
+        assert_eq!(check("fn main() -> int { return *nil; }").unwrap_err().kind, ErrorKind::InvalidOperation);
+    }
+
+    #[test]
+    fn test_null_checks() {
+        assert!(check("fn main() -> int { let x = 1; let p = &x; let s: string = nil; if p is not null && s is null { return 0; } assert_type(p is null, bool); return 1; }").is_ok());
+        assert_eq!(check("fn main() -> int { let n = 3; if n is null { return 1; } return 0; }").unwrap_err().kind, ErrorKind::InvalidOperation);
+    }
+
+    #[test]
+    fn test_const_array_global() {
+        assert!(check("const TABLE: [int; 3] = [1, 2, 3];\nfn main() -> int { return TABLE[1]; }").is_ok());
+        let short = check("const TABLE: [int; 4] = [1, 2, 3];\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(short.message, "global variable `TABLE` has 4 elements but its initializer has 3");
+        assert_eq!(check("const TABLE: [int; 2] = [1, \"two\"];\nfn main() -> int { return 0; }").unwrap_err().kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_break_value_names_the_loop_kind() {
+        let err = check("fn main() -> int { for i: 0..3 { break i; } return 0; }").unwrap_err();
+        assert_eq!(err.message, "`break` with a value in a `for` loop");
+        let err = check("fn main() -> int { let mut i = 0; while i < 3 { if i == 1 { break i; } i = i + 1; } return 0; }").unwrap_err();
+        assert_eq!(err.message, "`break` with a value in a `while` loop");
+        let err = check("fn main() -> int { loop { break 1; } return 0; }").unwrap_err();
+        assert_eq!(err.message, "`break` with a value in a `loop` whose value is not used");
+    }
+
+    #[test]
+    fn test_noreturn_call_ends_a_path() {
+        let fail = "fn fail(msg: string) { println(msg); exit(1); }\n";
+        let body = "fn check(x: int) -> int { if x < 0 { fail(\"negative\"); } else { return x; } }\nfn main() -> int { return check(1); }";
+        let marked = format!("@noreturn\n{}{}", fail, body);
+        assert!(check(&marked).is_ok(), "{:?}", check(&marked));
+        assert_eq!(check(&format!("{}{}", fail, body)).unwrap_err().kind, ErrorKind::MissingReturnType);
+        let returns = check("@noreturn\nfn fail() -> int { exit(1); return 0; }\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(returns.message, "`@noreturn` function `fail` must not return");
+    }
+
+    #[test]
+    fn test_chained_string_methods() {
+        assert!(check("fn main() -> int { let s = \" hi \"; assert_type(s.trim().to_upper(), string); return s.trim().to_upper().length(); }").is_ok());
+        assert_eq!(check("fn main() -> int { let s = \"a\"; return s.trim().shout(); }").unwrap_err().kind, ErrorKind::UndefinedFunction);
+    }
+
+    #[test]
+    fn test_imported_enum_exhaustiveness() {
+        let dir = std::env::temp_dir().join(format!("rapter_enum_import_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("colors.rapt"), "export enum Color { Red, Green, Blue = 5 }\n").unwrap();
+        let analyze = |body: &str| {
+            let path = PathBuf::from("test.rapt");
+            let source = format!("import colors\n{}", body);
+            let ast = parser::parse(lexer::tokenize(&source, &path).unwrap(), path.clone()).unwrap();
+            let mut resolver = crate::modules::ModuleResolver::new(dir.to_str().unwrap());
+            let imported = resolver.resolve_imports(&ast).unwrap();
+            analyze_with_imports(&ast, &imported, &path).map(|_| ()).map_err(|error| error.message)
+        };
+        let partial = analyze("fn name(c: Color) -> int { match c { Color::Red => { return 1; } Color::Green => { return 2; } } return 0; }\nfn main() -> int { return name(Color::Red); }");
+        let cast = analyze("fn main() -> int { let c = 5 as Color; return c as int; }");
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(partial.unwrap_err(), "non-exhaustive match on enum `Color`, missing variants: [\"Blue\"]");
+        assert!(cast.is_ok(), "{:?}", cast);
+    }
+
+    #[test]
+    fn test_tuple_patterns() {
+        let source = |arms: &str| format!("fn main() -> int {{ let p = (1, 'a'); return match p {{ {} }}; }}", arms);
+        assert!(check(&source("(0, 'a') => 0, (n @ _, _) => n")).is_ok());
+        let arity = check(&source("(0, 'a', 1) => 0, _ => 1")).unwrap_err();
+        assert_eq!(arity.message, "tuple pattern has 3 elements but the scrutinee has 2");
+        assert_eq!(check(&source("('a', 0) => 0, _ => 1")).unwrap_err().kind, ErrorKind::TypeMismatch);
+        assert_eq!(check("fn main() -> int { return match 1 { (0, 0) => 0, _ => 1 }; }").unwrap_err().kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_operator_methods() {
+        let vec2 = "struct V { x: int }\nimpl V {\n    fn add(self, other: V) -> V { return V { x: self.x + other.x }; }\n    fn eq(self, other: V) -> int { return 1; }\n}\n";
+        let sum = format!("{}fn main() -> int {{ let a = V {{ x: 1 }}; let b = a + a; assert_type(b, V); return b.x; }}", vec2);
+        assert!(check(&sum).is_ok(), "{:?}", check(&sum));
+        let sub = check(&format!("{}fn main() -> int {{ let a = V {{ x: 1 }}; let b = a - a; return 0; }}", vec2)).unwrap_err();
+        assert_eq!(sub.message, "cannot apply arithmetic operator to types `Struct(\"V\")` and `Struct(\"V\")`");
+        let eq = check(&format!("{}fn main() -> int {{ let a = V {{ x: 1 }}; if a == a {{ return 1; }} return 0; }}", vec2)).unwrap_err();
+        assert_eq!(eq.message, "`V::eq` must return `bool` to overload `==`, but returns `Int`");
+        assert_eq!(check(&format!("{}fn main() -> int {{ let a = V {{ x: 1 }}; let b = a + 1; return 0; }}", vec2)).unwrap_err().kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_range_pattern_types() {
+        assert!(check("fn main() -> int {\n    let c = 'q';\n    let lower = match c {\n        'a'..='z' => true,\n        _ => false,\n    };\n    return 0;\n}").is_ok());
+        let err = check("fn main() -> int {\n    let s = \"q\";\n    match s {\n        'a'..='z' => {}\n        _ => {}\n    }\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+        let err = check("fn main() -> int {\n    let n = 4;\n    match n {\n        9..1 => {}\n        _ => {}\n    }\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperation);
+        assert!(check("fn main() -> int {\n    let c = 'q';\n    match c {\n        'a'..='a' => {}\n        _ => {}\n    }\n    return 0;\n}").is_ok());
+    }
+
+    #[test]
+    fn test_overlapping_range_patterns() {
+        let arms = |arms: &str| format!("fn main() -> int {{\n    let n = 10;\n    match n {{\n{}        _ => {{}}\n    }}\n    return 0;\n}}", arms);
+        assert!(check(&arms("        0..10 => {}\n        10..100 => {}\n")).is_ok());
+        // Overlapping values go to the first arm
+        assert!(check(&arms("        5 => {}\n        0..10 => {}\n")).is_ok());
+        assert!(check(&arms("        0..10 => {}\n        9..20 => {}\n")).is_ok());
+        // A pattern covered by earlier arms is unreachable
+        let err = check(&arms("        0..10 => {}\n        5 => {}\n")).unwrap_err();
+        assert!(err.message.contains("unreachable"), "{}", err.message);
+        let err = check(&arms("        0..5 => {}\n        5..=10 => {}\n        3..8 => {}\n")).unwrap_err();
+        assert!(err.message.contains("unreachable"), "{}", err.message);
+        let err = check("fn main() -> int {\n    let n = 10;\n    match n {\n        5..5 => {}\n        _ => {}\n    }\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperation);
+    }
+
+    #[test]
+    fn test_or_patterns() {
+        // Both alternatives count towards exhaustiveness
+        assert!(check("enum Color { Red, Green, Blue }\nfn warm(c: Color) -> bool {\n    return match c {\n        Color::Red | Color::Green => true,\n        Color::Blue => false,\n    };\n}\nfn main() -> int { return 0; }").is_ok());
+        let err = check("fn main() -> int {\n    let o = Option::Some(1);\n    match o {\n        Option::Some(v) | Option::None => {}\n    }\n    return 0;\n}").unwrap_err();
+        assert!(err.message.contains("cannot bind"), "{}", err.message);
+        let err = check("fn main() -> int {\n    let n = 1;\n    match n {\n        1 | \"one\" => {}\n        _ => {}\n    }\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_int_and_bool_are_distinct() {
+        assert!(!types_compatible(&Type::Int, &Type::Bool));
+        assert!(!types_compatible(&Type::Bool, &Type::Int));
+        for src in [
+            "fn main() -> int {\n    let b: bool = 5;\n    return 0;\n}",
+            "fn main() -> int {\n    let n: int = true;\n    return 0;\n}",
+            "fn main() -> int {\n    let mut b = true;\n    b = 1;\n    return 0;\n}",
+            "fn flag() -> bool {\n    return 1;\n}\nfn main() -> int { return 0; }",
+            "fn count() -> int {\n    return false;\n}\nfn main() -> int { return 0; }",
+            "fn main() -> int {\n    if 1 {\n    }\n    return 0;\n}",
+            "fn main() -> int {\n    let n = 1;\n    while n {\n    }\n    return 0;\n}",
+        ] {
+            let err = check(src).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::TypeMismatch, "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_enum_int_casts() {
+        assert!(check("enum Color { Red, Green = 5, Blue }\nfn main() -> int {\n    let n = Color::Green as int;\n    let c: Color = n as Color;\n    let b = 6 as Color;\n    return n;\n}").is_ok());
+        let err = check("enum Color { Red, Green = 5, Blue }\nfn main() -> int {\n    let c = 3 as Color;\n    return 0;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperation);
+        assert!(err.message.contains("not the value of any variant"), "{}", err.message);
+        assert!(check("enum Color { Red }\nfn main() -> int {\n    let f = Color::Red as float;\n    return 0;\n}").is_err());
+    }
+
+    #[test]
+    fn test_global_initializers_must_be_constant() {
+        assert!(check("enum Level { Low, High }\nlet LIMIT = 10 * 4 + 2;\nlet READY: bool = true && !false;\nlet LEVEL = Level::High;\nfn main() -> int {\n    return LIMIT;\n}").is_ok());
+        let err = check("fn start() -> int {\n    return 1;\n}\nlet FIRST = start();\nfn main() -> int {\n    return FIRST;\n}").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOperation);
+        assert!(err.message.contains("compile-time constant"), "{}", err.message);
+        assert!(check("let A = 1;\nlet B = A + 1;\nfn main() -> int {\n    return B;\n}").is_err());
+    }
+
+    #[test]
+    fn test_pad_builtin() {
+        assert!(check("fn main() -> int { let row: string = pad(\"id\", 6); return 0; }").is_ok());
+        assert!(check("fn main() -> int { let row = pad(42, 6); return 0; }").is_err());
+        assert!(check("fn main() -> int { let row = pad(\"id\"); return 0; }").is_err());
+    }
+
+    #[test]
+    fn test_primitive_to_string() {
+        assert!(check("fn main() -> int { let n = 1; let s: string = n.to_string() + 2.5.to_string() + true.to_string() + 'c'.to_string() + (n * 3).to_string(); return len(s); }").is_ok());
+        let err = check("fn main() -> int { let n = 1; let s = n.to_string(16); return 0; }").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::WrongArgumentCount);
+    }
+
+    #[test]
+    fn test_tuple_destructuring() {
+        let divmod = "fn divmod(a: int, b: int) -> (int, int) {\n    return (a / b, a % b);\n}\n";
+        assert!(check(&format!("{}fn main() -> int {{ let (q, r) = divmod(7, 2); let (_, s) = divmod(q, r); return q + r + s; }}", divmod)).is_ok());
+        let arity = check(&format!("{}fn main() -> int {{ let (q, r, x) = divmod(7, 2); return q; }}", divmod)).unwrap_err();
+        assert_eq!(arity.message, "cannot destructure `Tuple([Int, Int])` into 3 names");
+        let skipped = check(&format!("{}fn main() -> int {{ let (_, r) = divmod(7, 2); return q; }}", divmod)).unwrap_err();
+        assert_eq!(skipped.kind, ErrorKind::UndefinedVariable);
+        let wrong_return = check("fn f() -> (int, int) { return (1, \"a\"); }\nfn main() -> int { return 0; }").unwrap_err();
+        assert_eq!(wrong_return.kind, ErrorKind::TypeMismatch);
+    }
+}
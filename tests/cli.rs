@@ -0,0 +1,49 @@
+use std::fs;
+use std::process::Command;
+
+// `--dump-ast` prints the parsed program and stops before semantic analysis, so even a
+// program with a type error is dumped; a parse error is reported and fails the run
+#[test]
+fn dump_ast_prints_parsed_program() {
+    let dir = std::env::temp_dir().join(format!("rapter_dump_ast_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("main.rapt");
+    fs::write(&source, "fn main() -> int {\n    let s: int = \"not an int\";\n    return 0;\n}\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rapter-lang")).arg(&source).arg("--dump-ast").output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("functions: ["), "{}", stdout);
+    assert!(stdout.contains("Function {\n            name: \"main\","), "{}", stdout);
+
+    fs::write(&source, "fn main( -> int {}\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rapter-lang")).arg(&source).arg("--dump-ast").output().unwrap();
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("error"));
+}
+
+// A `-D` without a name, or with one that is not an identifier, is a usage error
+#[test]
+fn define_requires_identifier() {
+    let dir = std::env::temp_dir().join(format!("rapter_define_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("main.rapt");
+    fs::write(&source, "fn main() -> int {\n    return LEVEL;\n}\n").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_rapter-lang")).arg(&source).args(args).current_dir(&dir).output().unwrap()
+    };
+    let valid = run(&["-o", "main.c", "-D", "LEVEL=2"]);
+    let attached = run(&["-o", "main.c", "-DLEVEL"]);
+    let rejected: Vec<_> = [&["-D"][..], &["-D=5"], &["-D", "=5"], &["-D", "2FAST"], &["-DA-B=1"]]
+        .iter()
+        .map(|args| run(args))
+        .collect();
+    fs::remove_dir_all(&dir).ok();
+    assert!(valid.status.success(), "{}", String::from_utf8_lossy(&valid.stderr));
+    assert!(attached.status.success(), "{}", String::from_utf8_lossy(&attached.stderr));
+    for output in rejected {
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Usage: -D NAME[=value]"), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+}
@@ -0,0 +1,95 @@
+use rapter_lang::{compile_with_options, CompileOptions};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// A Rapter program in its own temporary directory, compiled to `<name>.c` and built with gcc
+// into `<name>`. The directory is removed when the program is dropped.
+pub struct Program {
+    pub dir: PathBuf,
+    name: String,
+}
+
+impl Program {
+    pub fn new(name: &str) -> Program {
+        let dir = std::env::temp_dir().join(format!("rapter_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        Program { dir, name: name.to_string() }
+    }
+
+    pub fn c_file(&self) -> PathBuf {
+        self.dir.join(format!("{}.c", self.name))
+    }
+
+    // Compile `source` to C and return the generated code
+    #[allow(dead_code)] // test_runner.rs always passes options
+    pub fn compile(&self, source: &str) -> String {
+        self.compile_with_options(source, &CompileOptions::default())
+    }
+
+    pub fn compile_with_options(&self, source: &str, options: &CompileOptions) -> String {
+        let rapt_file = self.dir.join(format!("{}.rapt", self.name));
+        fs::write(&rapt_file, source).unwrap();
+        let c_file = self.c_file();
+        compile_with_options(&rapt_file, Some(c_file.to_str().unwrap()), options).unwrap();
+        fs::read_to_string(&c_file).unwrap()
+    }
+
+    // Compile `source` with the rapter binary run in the program's directory, next to the
+    // `(name, source)` modules it imports, and return the generated code
+    #[allow(dead_code)] // only generated_c.rs imports modules
+    pub fn compile_with_modules(&self, source: &str, modules: &[(&str, &str)]) -> String {
+        for (module, module_source) in modules {
+            fs::write(self.dir.join(format!("{}.rapt", module)), module_source).unwrap();
+        }
+        let rapt_file = format!("{}.rapt", self.name);
+        let c_file = format!("{}.c", self.name);
+        fs::write(self.dir.join(&rapt_file), source).unwrap();
+        let compiled = Command::new(env!("CARGO_BIN_EXE_rapter-lang"))
+            .args([&rapt_file, "-o", &c_file])
+            .current_dir(&self.dir)
+            .output()
+            .unwrap();
+        assert!(compiled.status.success(), "{}", String::from_utf8_lossy(&compiled.stderr));
+        fs::read_to_string(self.c_file()).unwrap()
+    }
+
+    // Run gcc with `args` in the program's directory; `None` when gcc is not installed
+    pub fn gcc(&self, args: &[&str]) -> Option<Output> {
+        match Command::new("gcc").args(args).current_dir(&self.dir).output() {
+            Ok(built) => Some(built),
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                eprintln!("skipping: gcc is not available");
+                None
+            }
+            Err(error) => panic!("cannot run gcc: {}", error),
+        }
+    }
+
+    // Build the compiled C with gcc and run it. `None` only when gcc is not installed; C that
+    // gcc rejects fails the test.
+    pub fn run(&self) -> Option<Output> {
+        let c_file = format!("{}.c", self.name);
+        let built = self.gcc(&["-w", "-o", &self.name, &c_file])?;
+        assert!(
+            built.status.success(),
+            "gcc rejected the generated C:\n{}\n{}",
+            String::from_utf8_lossy(&built.stderr),
+            fs::read_to_string(self.c_file()).unwrap_or_default()
+        );
+        Some(Command::new(self.dir.join(&self.name)).output().unwrap())
+    }
+
+    // Standard output of `run()`
+    #[allow(dead_code)] // test_runner.rs also checks stderr and the exit code
+    pub fn stdout(&self) -> Option<String> {
+        self.run().map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
@@ -0,0 +1,437 @@
+mod common;
+
+use common::Program;
+use rapter_lang::CompileOptions;
+use std::fs;
+
+// Array printing, map and length-bounded loops mix sizes with Rapter ints; the C must still
+// build cleanly.
+#[test]
+fn test_size_loops_compile_without_warnings() {
+    let program = Program::new("sizes");
+    let generated = program.compile(
+        "fn square(n: int) -> int {\n    return n * n;\n}\n\n\
+         fn main() -> int {\n    let nums = new [int]();\n    nums.push(1);\n    nums.push(2);\n    println(nums);\n\
+         \x20   let squares = nums.map(square);\n    println(squares);\n    let word = \"hello\";\n\
+         \x20   for i: 0..nums.length() {\n        println(nums[i] + len(word));\n    }\n    return 0;\n}\n",
+    );
+
+    let Some(output) = program.gcc(&["-Wall", "-Wsign-compare", "-c", "-o", "sizes.o", "sizes.c"]) else { return };
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let warnings = String::from_utf8_lossy(&output.stderr);
+    assert!(!warnings.contains("warning"), "{}\n{}", warnings, generated);
+}
+
+// pad() fills short strings up to the width and leaves longer ones whole.
+#[test]
+fn test_pad_widths() {
+    let program = Program::new("pad");
+    program.compile(
+        "fn main() -> int {\n    println(\"[\" + pad(\"ab\", 5) + \"]\");\n    println(\"[\" + pad(\"ab\", -5) + \"]\");\n\
+         \x20   println(\"[\" + pad(\"too long\", 3) + \"]\");\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "[ab   ]\n[   ab]\n[too long]\n");
+}
+
+// to_string() gives each primitive's printed form; bools become true/false.
+#[test]
+fn test_primitive_to_string() {
+    let program = Program::new("to_string");
+    program.compile(
+        "fn main() -> int {\n    let n = -42;\n    let f = 2.5;\n    let c = 'x';\n    println(n.to_string());\n\
+         \x20   println(f.to_string());\n    println((n < 0).to_string());\n    println(c.to_string());\n\
+         \x20   println(\"n + 2 = \" + (n + 2).to_string());\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "-42\n2.500000\ntrue\nx\nn + 2 = -40\n");
+}
+
+// `break` and `continue` in a `while let` body act on the `while let` loop itself, not on the
+// match it stands for.
+#[test]
+fn test_break_out_of_while_let() {
+    let program = Program::new("while_let");
+    program.compile(
+        "fn next(n: int) -> Option<int> {\n    if n < 10 {\n        return Option::Some(n);\n    }\n    return Option::None;\n}\n\
+         fn main() -> int {\n    let mut i = 0;\n    let mut total = 0;\n    while let Option::Some(x) = next(i) {\n\
+         \x20       i = i + 1;\n        if x == 2 {\n            continue;\n        }\n        if x == 5 {\n            break;\n        }\n\
+         \x20       total = total + x;\n    }\n    println(total);\n    println(i);\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "8\n6\n");
+}
+
+// `==` on dynamic arrays compares their sizes and then each element.
+#[test]
+fn test_dynamic_array_equality() {
+    let program = Program::new("array_eq");
+    program.compile(
+        "fn main() -> int {\n    let mut a = new [int]();\n    let mut b = new [int]();\n    a.push(1);\n    a.push(2);\n\
+         \x20   b.push(1);\n    b.push(2);\n    println(a == b);\n    b.push(3);\n    println(a == b);\n    println(a != b);\n\
+         \x20   a.push(4);\n    println(a == b);\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "1\n0\n1\n0\n");
+}
+
+// Array comparisons nested in `&&` and `||` still go through the element-wise helper
+#[test]
+fn test_nested_dynamic_array_equality() {
+    let program = Program::new("nested_array_eq");
+    program.compile(
+        "fn main() -> int {\n    let mut a = new [int]();\n    let mut b = new [int]();\n    a.push(1);\n    b.push(1);\n\
+         \x20   let ok = true;\n    if a == b && ok { println(\"same\"); }\n    if a != b || !ok { println(\"wrong\"); }\n\
+         \x20   b.push(2);\n    if ok && a != b { println(\"differ\"); }\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "same\ndiffer\n");
+}
+
+// The variable bound in a `while (let ...; cond)` header is recomputed before every check,
+// also after a `continue`.
+#[test]
+fn test_while_header_binding() {
+    let program = Program::new("while_binding");
+    program.compile(
+        "fn main() -> int {\n    let mut n = 5;\n    while (let x = n * 2; x > 0) {\n        n = n - 1;\n\
+         \x20       if x == 6 {\n            continue;\n        }\n        println(x);\n    }\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "10\n8\n4\n2\n");
+}
+
+// A `mut ref` parameter writes through to the caller's variable, field or element, also when
+// passed on to another `mut ref` parameter.
+#[test]
+fn test_mut_ref_parameter() {
+    let program = Program::new("mut_ref");
+    program.compile(
+        "struct Point { x: int, y: int }\n\
+         fn inc(mut ref n: int) {\n    n = n + 1;\n}\n\
+         fn twice(mut ref n: int) {\n    inc(n);\n    inc(n);\n}\n\
+         fn sum(ref p: Point) -> int {\n    return p.x + p.y;\n}\n\
+         fn main() -> int {\n    let mut a = 1;\n    inc(a);\n    twice(a);\n    println(a);\n\
+         \x20   let mut p = Point { x: 1, y: 2 };\n    inc(p.y);\n    println(sum(p));\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "4\n4\n");
+}
+
+// A local whose struct has a `drop` method is dropped when its block ends, on `continue` and on
+// early returns, but not after being moved out.
+#[test]
+fn test_drop_at_scope_end() {
+    let program = Program::new("drop");
+    program.compile(
+        "struct Guard { id: int }\n\
+         impl Guard {\n    fn drop(self) {\n        println(\"drop :id:\");\n    }\n}\n\
+         fn make(id: int) -> Guard {\n    let g = Guard { id: id };\n    return g;\n}\n\
+         fn main() -> int {\n    let outer = make(1);\n    let mut i = 0;\n    while i < 2 {\n        let g = Guard { id: 10 + i };\n\
+         \x20       i = i + 1;\n        if i == 1 {\n            continue;\n        }\n        println(\"body\");\n    }\n\
+         \x20   println(\"end\");\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "drop 10\nbody\ndrop 11\nend\ndrop 1\n");
+}
+
+// A local moved out in a branch is dropped by its new owner when the branch runs, and by
+// itself at the end of its block when it does not
+#[test]
+fn test_drop_after_conditional_move() {
+    let program = Program::new("conditional_move");
+    program.compile(
+        "struct Guard { id: int }\n\
+         impl Guard {\n    fn drop(self) {\n        println(\"drop :id:\");\n    }\n}\n\
+         fn run(flag: bool, id: int) {\n    let a = Guard { id: id };\n    if flag {\n        let k = a;\n        println(\"moved\");\n    }\n\
+         \x20   println(\"end\");\n}\n\
+         fn main() -> int {\n    run(false, 1);\n    run(true, 2);\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "end\ndrop 1\nmoved\ndrop 2\nend\n");
+}
+
+// Reassigning a local with a `drop` method drops the value it overwrites once the new one exists
+#[test]
+fn test_drop_on_reassignment() {
+    let program = Program::new("drop_reassign");
+    program.compile(
+        "struct Guard { id: int }\n\
+         impl Guard {\n    fn drop(self) {\n        println(\"drop :id:\");\n    }\n}\n\
+         fn make(id: int) -> Guard {\n    println(\"make :id:\");\n    let g = Guard { id: id };\n    return g;\n}\n\
+         fn main() -> int {\n    let mut g = make(1);\n    g = make(2);\n    println(\"end\");\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "make 1\nmake 2\ndrop 1\nend\ndrop 2\n");
+}
+
+// read_lines() splits a file on `\n`, dropping a `\r` before it and keeping a last line without a
+// newline.
+#[test]
+fn test_read_lines() {
+    let program = Program::new("read_lines");
+    let text = program.dir.join("lines.txt");
+    fs::write(&text, "alpha\r\nbeta\n\ngamma").unwrap();
+    program.compile(&format!(
+        "fn main() -> int {{\n    let lines = read_lines(\"{}\");\n    println(lines.length());\n    println(lines);\n    return 0;\n}}\n",
+        text.to_string_lossy().replace('\\', "/")
+    ));
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "4\n[\"alpha\", \"beta\", \"\", \"gamma\"]\n");
+}
+
+// `?.` reads a field of an Option's struct, giving None when the Option is None.
+#[test]
+fn test_optional_chaining() {
+    let program = Program::new("chain");
+    program.compile(
+        "struct Point { x: int, y: int }\n\
+         fn find(ok: bool) -> Option<Point> {\n    if ok {\n        return Option::Some(Point { x: 3, y: 4 });\n    }\n    return Option::None;\n}\n\
+         fn show(v: Option<int>) {\n    match v {\n        Option::Some(n) => { println(n); }\n        Option::None => { println(\"none\"); }\n    }\n}\n\
+         fn main() -> int {\n    show(find(true)?.x);\n    show(find(false)?.y);\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "3\nnone\n");
+}
+
+// A dynamic array's data() and len() go straight to a variadic C function.
+#[test]
+fn test_dynamic_array_data_to_extern() {
+    let program = Program::new("array_data");
+    program.compile(
+        "extern fn printf(format: *char, ...) -> int;\n\
+         fn main() -> int {\n    let mut v = new [float]();\n    v.push(1.5);\n    v.push(2.5);\n\
+         \x20   let data = v.data();\n    printf(\"%d values, last %.1f\\n\", v.len(), data[v.len() - 1]);\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "2 values, last 2.5\n");
+}
+
+// `nil` is a null pointer or string: assigned, returned, passed and compared.
+#[test]
+fn test_nil_pointers() {
+    let program = Program::new("nil");
+    program.compile(
+        "fn find(flag: bool, p: *int) -> *int {\n    if flag { return p; }\n    return nil;\n}\n\
+         fn label(s: string) -> string {\n    if s == nil { return \"none\"; }\n    return s;\n}\n\
+         fn main() -> int {\n    let x = 3;\n    let mut p: *int = nil;\n    if p == nil { println(\"null\"); }\n\
+         \x20   p = find(true, &x);\n    if p != nil { println(*p); }\n    if find(false, &x) == nil { println(label(nil)); }\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "null\n3\nnone\n");
+}
+
+// `is null` and `is not null` on a null and a non-null pointer.
+#[test]
+fn test_null_checks() {
+    let program = Program::new("null_check");
+    program.compile(
+        "fn show(p: *int) {\n    if p is null { println(\"null\"); }\n    if p is not null { println(*p); }\n}\n\
+         fn main() -> int {\n    let x = 7;\n    show(nil);\n    show(&x);\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "null\n7\n");
+}
+
+// A `const` array is a real C array initializer, indexed from other functions.
+#[test]
+fn test_const_array() {
+    let program = Program::new("const_array");
+    let c = program.compile(
+        "const PRIMES: [int; 4] = [2, 3, 5, 7];\n\
+         fn sum() -> int {\n    let mut total = 0;\n    for i: 0..4 { total = total + PRIMES[i]; }\n    return total;\n}\n\
+         fn main() -> int {\n    println(sum());\n    println(PRIMES[3]);\n    return 0;\n}\n",
+    );
+    assert!(c.contains("static const int PRIMES[4] = {2, 3, 5, 7};"), "{}", c);
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "17\n7\n");
+}
+
+// A tuple pattern tests each element against its own pattern and binds inside the elements.
+//
+#[test]
+fn test_tuple_match() {
+    let program = Program::new("tuple_match");
+    program.compile(
+        "fn classify(x: int, y: int) -> int {\n    let p = (x, y);\n    return match p {\n        (0, 0) => 0,\n        (_, 0) => 1,\n        (0, n @ _) => n,\n        _ => 100,\n    };\n}\n\
+         fn main() -> int {\n    println(classify(0, 0));\n    println(classify(5, 0));\n    println(classify(0, 7));\n    println(classify(3, 4));\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "0\n1\n7\n100\n");
+}
+
+// Chars popped from a char array or read from a string print as glyphs, not code points.
+//
+#[test]
+fn test_print_chars() {
+    let program = Program::new("print_chars");
+    program.compile(
+        "fn main() -> int {\n    let mut chars = new [char]();\n    chars.push('a');\n    chars.push('b');\n    let c = chars.pop();\n    println(c);\n    println(chars[0]);\n\
+         \x20   let s = \"xyz\";\n    println(s[1]);\n    let last = s[2];\n    println(last);\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "b\na\ny\nz\n");
+}
+
+// `+` and `==` on structs call their `add` and `eq` methods.
+#[test]
+fn test_operator_methods() {
+    let program = Program::new("vectors");
+    let c = program.compile(
+        "struct Vec2 { x: int, y: int }\n\
+         impl Vec2 {\n    fn add(self, other: Vec2) -> Vec2 { return Vec2 { x: self.x + other.x, y: self.y + other.y }; }\n\
+         \x20   fn eq(self, other: Vec2) -> bool { return self.x == other.x && self.y == other.y; }\n}\n\
+         fn main() -> int {\n    let a = Vec2 { x: 1, y: 2 };\n    let b = Vec2 { x: 3, y: 4 };\n    let c = a + b;\n    println(c.x);\n    println(c.y);\n\
+         \x20   if c == Vec2 { x: 4, y: 6 } { println(\"equal\"); }\n    if a != b { println(\"different\"); }\n    return 0;\n}\n",
+    );
+    assert!(c.contains("Vec2 c = Vec2_add(a, b);"), "{}", c);
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "4\n6\nequal\ndifferent\n");
+}
+
+// An overloaded `==` or `+` nested in `&&` and `||` still calls the struct's method
+#[test]
+fn test_nested_operator_methods() {
+    let program = Program::new("nested_vectors");
+    program.compile(
+        "struct Vec2 { x: int, y: int }\n\
+         impl Vec2 {\n    fn add(self, other: Vec2) -> Vec2 { return Vec2 { x: self.x + other.x, y: self.y + other.y }; }\n\
+         \x20   fn eq(self, other: Vec2) -> bool { return self.x == other.x && self.y == other.y; }\n}\n\
+         fn main() -> int {\n    let p = Vec2 { x: 1, y: 2 };\n    let q = Vec2 { x: 1, y: 2 };\n    let ok = false;\n\
+         \x20   if p == q || ok { println(\"equal\"); }\n    if ok && p != q { println(\"wrong\"); }\n\
+         \x20   if !ok && p + q == Vec2 { x: 2, y: 4 } { println(\"sum\"); }\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "equal\nsum\n");
+}
+
+// The `--emit-header` header defines every type the exported signatures use, so C code that
+// includes it compiles on its own
+#[test]
+fn test_header_compiles() {
+    let program = Program::new("geometry");
+    let options = CompileOptions { emit_header: true, ..CompileOptions::default() };
+    program.compile_with_options(
+        "struct Point { x: int, y: int }\n\
+         export fn find(n: int) -> Option<int> {\n    if n > 0 { return Option::Some(n); }\n    return Option::None;\n}\n\
+         export fn origin() -> Point {\n    return Point { x: 0, y: 0 };\n}\n\
+         export fn squares(n: int) -> DynamicArray[int] {\n    let mut v = new [int]();\n    for i: 0..n { v.push(i * i); }\n    return v;\n}\n\
+         export fn tagged(p: Point) -> (int, Point) {\n    return (1, p);\n}\n\
+         fn main() -> int { return 0; }\n",
+        &options,
+    );
+    fs::write(
+        program.dir.join("consumer.c"),
+        "#include \"geometry.h\"\n\
+         int consume(void) {\n    Option_int found = find(2);\n    Point p = origin();\n    DynamicArray_int v = squares(3);\n\
+         \x20   Tuple_int_Point t = tagged(p);\n    return found.data.some_value + p.x + (int)v.size + t._0;\n}\n",
+    ).unwrap();
+    let Some(output) = program.gcc(&["-c", "-o", "consumer.o", "consumer.c"]) else { return };
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+// A negative index on a call's result calls it once
+#[test]
+fn test_negative_index_evaluates_receiver_once() {
+    let program = Program::new("negative_index");
+    program.compile(
+        "fn make() -> DynamicArray[int] {\n    println(\"make\");\n    let mut v = new [int]();\n    v.push(1);\n    v.push(2);\n    return v;\n}\n\
+         fn name() -> string {\n    println(\"name\");\n    return \"abc\";\n}\n\
+         fn main() -> int {\n    println(make()[-1]);\n    println(name()[-1]);\n    let v = make();\n    println(v[-2]);\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "make\n2\nname\nc\nmake\n1\n");
+}
+
+// A `name @` binding is only visible in its own arm; other arms see the outer variable
+#[test]
+fn test_outer_binding_scoped_to_arm() {
+    let program = Program::new("outer_binding");
+    program.compile(
+        "fn pick(x: int, k: int) -> int {\n    return match x {\n        k @ 0 => k + 10,\n        1 => k,\n        _ => k * 2,\n    };\n}\n\
+         fn bump(x: int, k: int) -> int {\n    match x {\n        k @ 1..5 => { return k; }\n        _ => { return k + 1; }\n    }\n    return 0;\n}\n\
+         fn main() -> int {\n    println(pick(0, 7));\n    println(pick(1, 7));\n    println(pick(2, 7));\n    println(bump(3, 7));\n    println(bump(9, 7));\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "10\n7\n14\n3\n8\n");
+}
+
+// Struct payloads of Option compare field by field, so equal strings at different addresses
+// are equal and padding bytes are ignored
+#[test]
+fn test_option_struct_payload_equality() {
+    let program = Program::new("option_struct_eq");
+    program.compile(
+        "struct Tag { kind: char, id: int, name: string }\n\
+         fn tag(kind: char, id: int, name: string) -> Option<Tag> {\n    return Option::Some(Tag { kind: kind, id: id, name: name });\n}\n\
+         fn main() -> int {\n    let a = tag('x', 1, \"ab\".to_upper());\n    let b = tag('x', 1, \"AB\");\n    let c = tag('x', 2, \"AB\");\n\
+         \x20   if a == b { println(\"same\"); }\n    if a != c { println(\"different\"); }\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "same\ndifferent\n");
+}
+
+// `data()` reaches the storage of any array expression, not just a variable
+#[test]
+fn test_data_of_array_expression() {
+    let program = Program::new("array_data");
+    let c = program.compile(
+        "fn make() -> DynamicArray[int] {\n    let mut v = new [int]();\n    v.push(4);\n    v.push(5);\n    return v;\n}\n\
+         fn main() -> int {\n    let p = make().data();\n    println(p[1]);\n    println(make().data()[0]);\n    return 0;\n}\n",
+    );
+    assert!(c.contains("int* p = (make()).data;"), "{}", c);
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "5\n4\n");
+}
+
+// `in_range` includes both bounds and evaluates its value once
+#[test]
+fn test_in_range() {
+    let program = Program::new("in_range");
+    program.compile(
+        "fn next(n: int) -> int {\n    println(\"next\");\n    return n;\n}\n\
+         fn main() -> int {\n    println(in_range(3, 1, 5));\n    println(in_range(1, 1, 5));\n    println(in_range(5, 1, 5));\n    println(in_range(0, 1, 5));\n    println(in_range(6, 1, 5));\n\
+         \x20   if in_range(next(4), 1, 5) { println(\"inside\"); }\n    if !in_range(next(9), 1, 5) { println(\"outside\"); }\n    return 0;\n}\n",
+    );
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "1\n1\n1\n0\n0\nnext\ninside\nnext\noutside\n");
+}
+
+// Prototypes of imported functions leave out the parameter names, which may be the names of
+// variables in the importing program
+#[test]
+fn test_imported_prototypes_omit_parameter_names() {
+    let program = Program::new("prototypes");
+    let c = program.compile_with_modules(
+        "import mathlib\nlet factor = 10;\nfn main() -> int {\n    let value = 3;\n    println(mathlib.scale(value, factor));\n    return 0;\n}\n",
+        &[("mathlib", "export fn scale(value: int, factor: int) -> int { return value * factor; }\n")],
+    );
+    assert!(c.contains("int scale(int, int);"), "{}", c);
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "30\n");
+}
+
+// Equality helpers exist only for compared types, and compare array payloads and fields
+// element by element
+#[test]
+fn test_option_array_payload_equality() {
+    let program = Program::new("option_array_eq");
+    let source = |comparisons: &str| format!(
+        "struct Bag {{ id: int, items: DynamicArray[int] }}\n\
+         fn bag(id: int, n: int) -> Option<Bag> {{\n    let mut items = new [int]();\n    for i: 0..n {{ items.push(i); }}\n    return Option::Some(Bag {{ id: id, items: items }});\n}}\n\
+         fn squares(n: int) -> Option<DynamicArray[int]> {{\n    let mut v = new [int]();\n    for i: 0..n {{ v.push(i * i); }}\n    return Option::Some(v);\n}}\n\
+         fn main() -> int {{\n    match squares(3) {{\n        Option::Some(v) => {{ println(v.len()); }}\n        Option::None => {{}}\n    }}\n{}    return 0;\n}}\n",
+        comparisons
+    );
+    let c = program.compile(&source(""));
+    assert!(!c.contains("_eq("), "{}", c);
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "3\n");
+    program.compile(&source(
+        "    if squares(3) == squares(3) { println(\"same squares\"); }\n    if squares(3) != squares(4) { println(\"different squares\"); }\n\
+         \x20   if bag(1, 2) == bag(1, 2) { println(\"same bag\"); }\n    if bag(1, 2) != bag(1, 3) { println(\"different bag\"); }\n",
+    ));
+    let Some(stdout) = program.stdout() else { return };
+    assert_eq!(stdout, "3\nsame squares\ndifferent squares\nsame bag\ndifferent bag\n");
+}
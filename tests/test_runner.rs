@@ -0,0 +1,26 @@
+mod common;
+
+use common::Program;
+use rapter_lang::CompileOptions;
+
+// Compiles a file with two `@test` functions under `--test`, builds the C with gcc and
+// checks what the runner reports
+#[test]
+fn test_runner_reports_passes_and_failures() {
+    let program = Program::new("math");
+    let options = CompileOptions { test: true, ..CompileOptions::default() };
+    program.compile_with_options(
+        "fn add(a: int, b: int) -> int {\n    return a + b;\n}\n\n\
+         @test\nfn adds_small_numbers() {\n    assert(add(2, 2) == 4);\n}\n\n\
+         @test\nfn catches_a_bug() {\n    assert(add(2, 2) == 5, \"2 + 2 should be 5\");\n}\n",
+        &options,
+    );
+    let Some(output) = program.run() else { return };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("test adds_small_numbers ... ok"), "{}", stdout);
+    assert!(stdout.contains("test catches_a_bug ... FAILED"), "{}", stdout);
+    assert!(stdout.contains("test result: FAILED. 1 passed; 1 failed"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("assertion failed: 2 + 2 should be 5"));
+    assert_eq!(output.status.code(), Some(1));
+}